@@ -12,7 +12,5 @@ pub const T0: f64 = 0.0;
 pub const T1: f64 = 1.0;
 pub const OUTPUT_FILENAME: &str = "output.png";
 
-pub const ENABLE_LIGHTS: bool = true;
-
 // I got the defaults from https://github.com/rudolphalmeida/raytrac so I can compare the
 // performance of mine. I also changed the random scene so it matches.
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::Material;
+use crate::util::{hitable::Hitable, json, vector3::Vec3};
+
+pub mod directional_light;
+pub mod environment_light;
+pub mod point_light;
+pub mod sky;
+pub mod sphere_light;
+
+pub use crate::lights::directional_light::DirectionalLight;
+pub use crate::lights::environment_light::EnvironmentLight;
+
+pub struct LightSample {
+    // Unit vector from the lit point towards the light.
+    pub direction: Vec3,
+    // How far the light is, for the shadow ray. Infinite for lights that are far away.
+    pub distance: f64,
+    pub radiance: Vec3,
+    // Solid angle density of `direction`. Lights that can't be hit by rays (points, directional
+    // lights) have a pdf of 0, and their `radiance` is already the full contribution.
+    pub pdf: f64,
+}
+
+// Lights that are sampled explicitly with shadow rays, rather than waiting for a scattered ray
+// to stumble into them.
+pub trait Light {
+    fn sample(&self, p: &Vec3) -> Option<LightSample>;
+
+    // The density `sample` would have picked `direction` from `p` with.
+    fn pdf(&self, _p: &Vec3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // Whether a ray that hit something with this material hit this light.
+    fn emits_with(&self, _material: &Arc<Material>) -> bool {
+        false
+    }
+
+    // What a ray going off into `direction` without hitting anything sees of this light, for
+    // lights that surround the scene.
+    fn background(&self, _direction: &Vec3) -> Option<Vec3> {
+        None
+    }
+}
+
+// The geometry of the lights that can be seen gets added to `objects`.
+pub fn load_from_json(
+    values: &Value,
+    objects: &mut Vec<Box<Hitable + Sync>>,
+    verbose: bool,
+) -> Vec<Box<Light + Sync + Send>> {
+    let mut lights: Vec<Box<Light + Sync + Send>> = Vec::new();
+
+    let id = "lights";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        match values[id][i]["type"].as_str() {
+            Some("point") | Some("spot") => match point_light::load_from_json(&values[id][i]) {
+                Some(light) => lights.push(light),
+                None => eprintln!("ERROR: Can't get position of light {}! Skipping...", i),
+            },
+            Some("directional") => match directional_light::load_from_json(&values[id][i]) {
+                Some(light) => lights.push(light),
+                None => eprintln!("ERROR: Can't get direction of light {}! Skipping...", i),
+            },
+            Some("sphere") => match sphere_light::load_from_json(&values[id][i]) {
+                Some((object, light)) => {
+                    objects.push(object);
+                    lights.push(light);
+                }
+                None => eprintln!(
+                    "ERROR: Can't get position and radius of light {}! Skipping...",
+                    i
+                ),
+            },
+            _ => eprintln!("ERROR: Can't get type of light {}! Skipping...", i),
+        }
+    }
+
+    if verbose {
+        println!("Loaded {} lights.", lights.len());
+    }
+
+    lights
+}
+
+// `color` times `intensity`, both optional.
+pub fn load_power_from_json(values: &Value) -> Vec3 {
+    let color = match json::get_vec3_or_rand(&values["color"]) {
+        Some(color) => color,
+        _ => Vec3::unit(),
+    };
+    let intensity = match json::get_f64_or_rand(&values["intensity"]) {
+        Some(i) => i,
+        _ => 1.0,
+    };
+    color * intensity
+}
@@ -0,0 +1,104 @@
+use rand::Rng;
+use serde_json::Value;
+
+use crate::lights::{self, Light, LightSample};
+use crate::util::{json, math, vector3::Vec3};
+
+// Light from something so far away that it arrives from the same direction everywhere, like the
+// sun. A non-zero angular diameter spreads the directions over a cone, which softens shadows.
+// Visible ones also show up as a disk in the sky and in reflections.
+pub struct DirectionalLight {
+    // Unit vector pointing towards the light, i.e. against the direction the light travels.
+    to_light: Vec3,
+    irradiance: Vec3,
+    cos_max: f64,
+    visible: bool,
+}
+
+impl DirectionalLight {
+    pub fn create(
+        direction: Vec3,
+        irradiance: Vec3,
+        angular_diameter: f64,
+        visible: bool,
+    ) -> Box<DirectionalLight> {
+        let cos_max = (angular_diameter.to_radians() / 2.0).cos();
+        Box::new(DirectionalLight {
+            to_light: -math::unit_vector(&direction),
+            irradiance,
+            cos_max,
+            // A disk with no size can't be seen.
+            visible: visible && cos_max < 1.0,
+        })
+    }
+
+    fn solid_angle(&self) -> f64 {
+        2.0 * std::f64::consts::PI * (1.0 - self.cos_max)
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: &Vec3) -> Option<LightSample> {
+        // Uniform in the cone around `to_light`.
+        let mut rng = rand::thread_rng();
+        let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - self.cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+        let (u, v) = math::orthonormal_basis(&self.to_light);
+        let direction =
+            u * (sin_theta * phi.cos()) + v * (sin_theta * phi.sin()) + self.to_light * cos_theta;
+
+        // The same light either way, but visible ones can also be found by scattered rays so
+        // they need a proper pdf.
+        let (radiance, pdf) = if self.visible {
+            (
+                self.irradiance / self.solid_angle(),
+                1.0 / self.solid_angle(),
+            )
+        } else {
+            (self.irradiance, 0.0)
+        };
+
+        Some(LightSample {
+            direction,
+            distance: std::f64::INFINITY,
+            radiance,
+            pdf,
+        })
+    }
+
+    fn pdf(&self, _p: &Vec3, direction: &Vec3) -> f64 {
+        if self.visible && math::dot(&math::unit_vector(direction), &self.to_light) >= self.cos_max
+        {
+            1.0 / self.solid_angle()
+        } else {
+            0.0
+        }
+    }
+
+    fn background(&self, direction: &Vec3) -> Option<Vec3> {
+        if self.visible && math::dot(&math::unit_vector(direction), &self.to_light) >= self.cos_max
+        {
+            Some(self.irradiance / self.solid_angle())
+        } else {
+            None
+        }
+    }
+}
+
+pub fn load_from_json(values: &Value) -> Option<Box<Light + Sync + Send>> {
+    let direction = json::get_vec3_or_rand(&values["direction"])?;
+    let angular_diameter = match json::get_f64_or_rand(&values["angular_diameter"]) {
+        Some(a) => a,
+        _ => 0.0,
+    };
+
+    let visible = values["visible"].as_bool() == Some(true);
+
+    Some(DirectionalLight::create(
+        direction,
+        lights::load_power_from_json(values),
+        angular_diameter,
+        visible,
+    ))
+}
@@ -0,0 +1,190 @@
+use rand::Rng;
+use serde_json::Value;
+
+use crate::lights::{Light, LightSample};
+use crate::util::{
+    hdr_image::{self, HdrImage},
+    json,
+    vector3::Vec3,
+};
+
+// An equirectangular image wrapped around the whole scene at an infinite distance. Bright parts
+// of the image are sampled more often, so a small sun in a big sky still casts clean shadows.
+pub struct EnvironmentLight {
+    image: HdrImage,
+    intensity: f64,
+    // Around the y axis, in radians.
+    rotation: f64,
+    // Chance of sampling each pixel.
+    probability: Vec<f64>,
+    // Cumulative distribution of the rows, and of the pixels within each row.
+    rows: Vec<f64>,
+    columns: Vec<f64>,
+}
+
+fn luminance(c: &Vec3) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+// First index with a cumulative value past `x`.
+fn find(cdf: &[f64], x: f64) -> usize {
+    cdf.partition_point(|c| *c <= x).min(cdf.len() - 1)
+}
+
+fn rotate_y(d: &Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    Vec3::new(cos * d.x + sin * d.z, d.y, -sin * d.x + cos * d.z)
+}
+
+// Where a point of the image is seen, before rotating. The middle of the image is towards -z.
+pub fn direction_of(u: f64, v: f64) -> Vec3 {
+    let phi = (u - 0.5) * 2.0 * std::f64::consts::PI;
+    let theta = v * std::f64::consts::PI;
+    Vec3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+impl EnvironmentLight {
+    pub fn create(image: HdrImage, intensity: f64, rotation: f64) -> Box<EnvironmentLight> {
+        let (width, height) = (image.width, image.height);
+
+        // Rows near the poles cover less of the sphere, hence the sine.
+        let mut weights: Vec<f64> = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (std::f64::consts::PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..width {
+                weights.push(luminance(&image.pixel(x, y)).max(0.0) * sin_theta);
+            }
+        }
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 || !total.is_finite() {
+            // All black, sample it uniformly rather than not at all.
+            for y in 0..height {
+                let sin_theta = (std::f64::consts::PI * (y as f64 + 0.5) / height as f64).sin();
+                for x in 0..width {
+                    weights[x + width * y] = sin_theta;
+                }
+            }
+        }
+        let total: f64 = weights.iter().sum();
+
+        let mut rows = Vec::with_capacity(height);
+        let mut columns = Vec::with_capacity(width * height);
+        let mut sum = 0.0;
+        for y in 0..height {
+            let row = &weights[width * y..width * (y + 1)];
+            let row_sum: f64 = row.iter().sum();
+            let mut running = 0.0;
+            for w in row.iter() {
+                running += if row_sum > 0.0 {
+                    w / row_sum
+                } else {
+                    1.0 / width as f64
+                };
+                columns.push(running);
+            }
+            sum += row_sum;
+            rows.push(sum / total);
+        }
+
+        Box::new(EnvironmentLight {
+            probability: weights.iter().map(|w| w / total).collect(),
+            image,
+            intensity,
+            rotation,
+            rows,
+            columns,
+        })
+    }
+
+    // The pixel a world space direction looks at, and the sine of its polar angle.
+    fn pixel_of(&self, direction: &Vec3) -> (usize, usize, f64) {
+        let d = rotate_y(direction, -self.rotation);
+        let length = d.length();
+        let cos_theta = (d.y / length).clamp(-1.0, 1.0);
+        let u = 0.5 + d.x.atan2(-d.z) / (2.0 * std::f64::consts::PI);
+        let v = cos_theta.acos() / std::f64::consts::PI;
+        let x = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+        let y = ((v * self.image.height as f64) as usize).min(self.image.height - 1);
+        (x, y, (1.0 - cos_theta * cos_theta).max(0.0).sqrt())
+    }
+
+    fn pdf_of(&self, x: usize, y: usize, sin_theta: f64) -> f64 {
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let pixels = (self.image.width * self.image.height) as f64;
+        self.probability[x + self.image.width * y] * pixels
+            / (2.0 * std::f64::consts::PI * std::f64::consts::PI * sin_theta)
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample(&self, _p: &Vec3) -> Option<LightSample> {
+        let mut rng = rand::thread_rng();
+        let (width, height) = (self.image.width, self.image.height);
+        let y = find(&self.rows, rng.gen::<f64>());
+        let x = find(&self.columns[width * y..width * (y + 1)], rng.gen::<f64>());
+
+        let u = (x as f64 + rng.gen::<f64>()) / width as f64;
+        let v = (y as f64 + rng.gen::<f64>()) / height as f64;
+        let sin_theta = (v * std::f64::consts::PI).sin();
+        let pdf = self.pdf_of(x, y, sin_theta);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let direction = direction_of(u, v);
+
+        Some(LightSample {
+            direction: rotate_y(&direction, self.rotation),
+            distance: std::f64::INFINITY,
+            radiance: self.image.pixel(x, y) * self.intensity,
+            pdf,
+        })
+    }
+
+    fn pdf(&self, _p: &Vec3, direction: &Vec3) -> f64 {
+        let (x, y, sin_theta) = self.pixel_of(direction);
+        self.pdf_of(x, y, sin_theta)
+    }
+
+    fn background(&self, direction: &Vec3) -> Option<Vec3> {
+        let (x, y, _) = self.pixel_of(direction);
+        Some(self.image.pixel(x, y) * self.intensity)
+    }
+}
+
+pub fn load_from_json(values: &Value) -> Option<Box<EnvironmentLight>> {
+    let filename = match values["filename"].as_str() {
+        Some(filename) => filename,
+        None => {
+            eprintln!("ERROR: Can't get filename of environment! Skipping...");
+            return None;
+        }
+    };
+    let image = match hdr_image::load(filename) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("ERROR [{}]: {}", filename, e);
+            return None;
+        }
+    };
+
+    let intensity = match json::get_f64_or_rand(&values["intensity"]) {
+        Some(i) => i,
+        _ => 1.0,
+    };
+    let rotation = match json::get_f64_or_rand(&values["rotation"]) {
+        Some(r) => r,
+        _ => 0.0,
+    };
+
+    Some(EnvironmentLight::create(
+        image,
+        intensity,
+        rotation.to_radians(),
+    ))
+}
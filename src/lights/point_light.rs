@@ -0,0 +1,76 @@
+use serde_json::Value;
+
+use crate::lights::{self, Light, LightSample};
+use crate::materials::diffuse_light::{self, Profile};
+use crate::util::{json, math, vector3::Vec3};
+
+// An infinitely small bulb. With a profile it becomes a spot light (or any fixture with an IES
+// profile) pointing along `direction`.
+pub struct PointLight {
+    position: Vec3,
+    intensity: Vec3,
+    profile: Option<(Profile, Vec3)>,
+}
+
+impl PointLight {
+    pub fn create(
+        position: Vec3,
+        intensity: Vec3,
+        profile: Option<(Profile, Vec3)>,
+    ) -> Box<PointLight> {
+        Box::new(PointLight {
+            position,
+            intensity,
+            profile: profile.map(|(profile, direction)| (profile, math::unit_vector(&direction))),
+        })
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: &Vec3) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+
+        let falloff = match &self.profile {
+            Some((profile, axis)) => profile.value(-math::dot(&direction, axis)),
+            None => 1.0,
+        };
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * falloff / (distance * distance),
+            pdf: 0.0,
+        })
+    }
+}
+
+pub fn load_from_json(values: &Value) -> Option<Box<Light + Sync + Send>> {
+    let position = json::get_vec3_or_rand(&values["position"])?;
+    let direction = match json::get_vec3_or_rand(&values["direction"]) {
+        Some(direction) => direction,
+        _ => Vec3::new(0.0, -1.0, 0.0),
+    };
+
+    // Spot lights without a `spot` key get the same default cone as spot light materials.
+    let profile = match diffuse_light::load_profile_from_json(values) {
+        Some(profile) => Some((profile, direction)),
+        None if values["type"].as_str() == Some("spot") => {
+            Some((diffuse_light::load_spot_from_json(&Value::Null), direction))
+        }
+        None => None,
+    };
+
+    Some(PointLight::create(
+        position,
+        lights::load_power_from_json(values),
+        profile,
+    ))
+}
@@ -0,0 +1,283 @@
+use serde_json::Value;
+
+use crate::lights::{environment_light, DirectionalLight, EnvironmentLight, Light};
+use crate::util::{hdr_image::HdrImage, json, math, vector3::Vec3};
+
+// Preetham et al., "A Practical Analytic Model for Daylight" (1999). The sky is baked into an
+// environment map so it gets importance sampled like any other, and the sun is a visible
+// directional light next to it.
+
+// Kilocandelas per square meter to scene units, so a clear zenith ends up around 0.5.
+const SKY_SCALE: f64 = 0.05;
+// Irradiance of the sun before it goes through the atmosphere, in the same units.
+const SUN_IRRADIANCE: f64 = 6.0;
+
+pub struct Sky {
+    // Unit vector towards the sun.
+    sun: Vec3,
+    turbidity: f64,
+    zenith: Vec3,
+    // Perez coefficients for Y, x and y.
+    perez: [[f64; 5]; 3],
+}
+
+fn perez(theta_cos: f64, gamma: f64, c: &[f64; 5]) -> f64 {
+    (1.0 + c[0] * (c[1] / theta_cos.max(0.01)).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos() * gamma.cos())
+}
+
+fn chromaticity(m: &[[f64; 4]; 3], turbidity: f64, theta: f64) -> f64 {
+    let t = [turbidity * turbidity, turbidity, 1.0];
+    let th = [theta * theta * theta, theta * theta, theta, 1.0];
+    let mut sum = 0.0;
+    for i in 0..3 {
+        for j in 0..4 {
+            sum += t[i] * m[i][j] * th[j];
+        }
+    }
+    sum
+}
+
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Vec3 {
+    if y <= 0.0 {
+        return Vec3::zero();
+    }
+    let cx = x / y * luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+    Vec3::new(
+        (3.2406 * cx - 1.5372 * luminance - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * luminance + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * luminance + 1.0570 * cz).max(0.0),
+    )
+}
+
+impl Sky {
+    // `elevation` and `azimuth` in degrees. An azimuth of 0 puts the sun towards -z, 90 towards +x.
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Sky {
+        let elevation = elevation.clamp(0.0, 90.0).to_radians();
+        let azimuth = azimuth.to_radians();
+        let sun = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+        let t = turbidity.clamp(1.7, 10.0);
+        let theta_s = std::f64::consts::FRAC_PI_2 - elevation;
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f64::consts::PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = chromaticity(
+            &[
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ],
+            t,
+            theta_s,
+        );
+        let zenith_y = chromaticity(
+            &[
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ],
+            t,
+            theta_s,
+        );
+
+        Sky {
+            sun,
+            turbidity: t,
+            zenith: Vec3::new(zenith_luminance.max(0.0), zenith_x, zenith_y),
+            perez: [
+                [
+                    0.1787 * t - 1.4630,
+                    -0.3554 * t + 0.4275,
+                    -0.0227 * t + 5.3251,
+                    0.1206 * t - 2.5771,
+                    -0.0670 * t + 0.3703,
+                ],
+                [
+                    -0.0193 * t - 0.2592,
+                    -0.0665 * t + 0.0008,
+                    -0.0004 * t + 0.2125,
+                    -0.0641 * t - 0.8989,
+                    -0.0033 * t + 0.0452,
+                ],
+                [
+                    -0.0167 * t - 0.2608,
+                    -0.0950 * t + 0.0092,
+                    -0.0079 * t + 0.2102,
+                    -0.0441 * t - 1.6537,
+                    -0.0109 * t + 0.0529,
+                ],
+            ],
+        }
+    }
+
+    // Radiance of the sky towards a unit `direction`. Below the horizon it stays the same as at
+    // the horizon.
+    pub fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let theta_cos = direction.y.max(0.0);
+        let gamma = math::dot(direction, &self.sun).clamp(-1.0, 1.0).acos();
+        let theta_s = self.sun.y.clamp(-1.0, 1.0).acos();
+
+        let mut values = [0.0; 3];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = self.zenith[i] * perez(theta_cos, gamma, &self.perez[i])
+                / perez(1.0, theta_s, &self.perez[i]);
+        }
+        xyy_to_rgb(values[1], values[2], values[0]) * SKY_SCALE
+    }
+
+    // Sunlight left after going through the atmosphere, from Rayleigh and aerosol scattering at
+    // roughly red, green and blue wavelengths.
+    pub fn sun_irradiance(&self) -> Vec3 {
+        let elevation = self.sun.y.clamp(0.0, 1.0).asin().to_degrees();
+        let zenith = 90.0 - elevation;
+        let air_mass =
+            1.0 / (zenith.to_radians().cos() + 0.15 * (93.885 - zenith).powf(-1.253)).max(1.0e-3);
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        let mut irradiance = Vec3::zero();
+        for (i, lambda) in [0.65f64, 0.55, 0.45].iter().enumerate() {
+            let rayleigh = (-air_mass * 0.008735 * lambda.powf(-4.08)).exp();
+            let aerosol = (-air_mass * beta * lambda.powf(-1.3)).exp();
+            irradiance[i] = SUN_IRRADIANCE * rayleigh * aerosol;
+        }
+        irradiance
+    }
+
+    pub fn to_image(&self, width: usize, height: usize) -> HdrImage {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let direction = environment_light::direction_of(
+                    (x as f64 + 0.5) / width as f64,
+                    (y as f64 + 0.5) / height as f64,
+                );
+                pixels.push(self.radiance(&direction));
+            }
+        }
+        HdrImage::new(width, height, pixels)
+    }
+}
+
+pub fn load_from_json(values: &Value) -> Vec<Box<Light + Sync + Send>> {
+    let mut lights: Vec<Box<Light + Sync + Send>> = Vec::new();
+
+    match values["model"].as_str() {
+        Some("preetham") | None => (),
+        Some(model) => {
+            eprintln!("ERROR: Unknown sky model {}! Skipping...", model);
+            return lights;
+        }
+    }
+
+    let elevation = match json::get_f64_or_rand(&values["elevation"]) {
+        Some(e) => e,
+        _ => 45.0,
+    };
+    let azimuth = match json::get_f64_or_rand(&values["azimuth"]) {
+        Some(a) => a,
+        _ => 0.0,
+    };
+    let turbidity = match json::get_f64_or_rand(&values["turbidity"]) {
+        Some(t) => t,
+        _ => 3.0,
+    };
+    let intensity = match json::get_f64_or_rand(&values["intensity"]) {
+        Some(i) => i,
+        _ => 1.0,
+    };
+    let resolution = match values["resolution"].as_u64() {
+        Some(r) if r > 0 => r as usize,
+        _ => 256,
+    };
+
+    let sky = Sky::new(elevation, azimuth, turbidity);
+    lights.push(EnvironmentLight::create(
+        sky.to_image(resolution, (resolution / 2).max(1)),
+        intensity,
+        0.0,
+    ));
+
+    // "sun": false leaves just the sky.
+    if values["sun"].as_bool() != Some(false) {
+        let sun_intensity = match json::get_f64_or_rand(&values["sun"]["intensity"]) {
+            Some(i) => i,
+            _ => 1.0,
+        };
+        let size = match json::get_f64_or_rand(&values["sun"]["size"]) {
+            Some(s) => s,
+            _ => 0.53,
+        };
+        lights.push(DirectionalLight::create(
+            -sky.sun,
+            sky.sun_irradiance() * intensity * sun_intensity,
+            size,
+            true,
+        ));
+    }
+
+    lights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luminance(c: Vec3) -> f64 {
+        0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+    }
+
+    #[test]
+    fn sun_direction() {
+        let sky = Sky::new(0.0, 90.0, 3.0);
+        assert!((sky.sun.x - 1.0).abs() < 1.0e-9 && sky.sun.y.abs() < 1.0e-9);
+        let sky = Sky::new(30.0, 0.0, 3.0);
+        assert!((sky.sun.y - 0.5).abs() < 1.0e-9 && sky.sun.z < 0.0);
+    }
+
+    #[test]
+    fn zenith_matches_the_model() {
+        // The Perez terms cancel out straight up, leaving the zenith luminance.
+        let sky = Sky::new(40.0, 0.0, 3.0);
+        let zenith = sky.radiance(&Vec3::new(0.0, 1.0, 0.0));
+        assert!((luminance(zenith) / SKY_SCALE - sky.zenith.x).abs() < 0.01 * sky.zenith.x);
+        // A clear day sky is blue.
+        assert!(zenith.z > zenith.x);
+    }
+
+    #[test]
+    fn radiance_is_finite_and_brightest_near_the_sun() {
+        // Straight up has no opposite side, so it stops short of 90.
+        for elevation in [0.0, 5.0, 30.0, 60.0, 85.0].iter() {
+            for turbidity in [1.7, 3.0, 10.0].iter() {
+                let sky = Sky::new(*elevation, 45.0, *turbidity);
+                let image = sky.to_image(32, 16);
+                assert!(image
+                    .pixels
+                    .iter()
+                    .all(|p| (0..3).all(|i| p[i].is_finite() && p[i] >= 0.0)));
+
+                // At the same height on the opposite side.
+                let towards = math::unit_vector(&(sky.sun + Vec3::new(0.0, 0.1, 0.0)));
+                let near = sky.radiance(&towards);
+                let away = Vec3::new(-towards.x, towards.y, -towards.z);
+                assert!(luminance(near) > luminance(sky.radiance(&away)));
+            }
+        }
+    }
+
+    #[test]
+    fn sunlight_dims_and_reddens_towards_the_horizon() {
+        let high = Sky::new(80.0, 0.0, 3.0).sun_irradiance();
+        let low = Sky::new(5.0, 0.0, 3.0).sun_irradiance();
+        assert!(luminance(low) < luminance(high));
+        assert!(low.x / low.z > high.x / high.z);
+        let hazy = Sky::new(80.0, 0.0, 8.0).sun_irradiance();
+        assert!(luminance(hazy) < luminance(high));
+        assert!(high.x <= SUN_IRRADIANCE && high.z > 0.0);
+    }
+}
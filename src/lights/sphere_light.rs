@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use serde_json::Value;
+
+use crate::lights::{self, Light, LightSample};
+use crate::materials::{DiffuseLight, Material};
+use crate::shapes::sphere::Sphere;
+use crate::textures::ConstantTexture;
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// A glowing sphere that's both seen by rays and sampled directly, so a small bulb doesn't need
+// thousands of samples to converge.
+pub struct SphereLight {
+    center: Vec3,
+    radius: f64,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl SphereLight {
+    pub fn create(
+        center: Vec3,
+        radius: f64,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<SphereLight> {
+        Box::new(SphereLight {
+            center,
+            radius,
+            material,
+        })
+    }
+
+    // Cosine of the half angle of the cone the sphere covers as seen from `p`, or None when
+    // `p` is inside of it.
+    fn cos_max(&self, p: &Vec3) -> Option<f64> {
+        let distance_squared = (self.center - *p).squared_length();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, p: &Vec3) -> Option<LightSample> {
+        let cos_max = self.cos_max(p)?;
+
+        // Uniform in the cone of directions that hit the sphere.
+        let mut rng = rand::thread_rng();
+        let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+        let w = math::unit_vector(&(self.center - *p));
+        let (u, v) = math::orthonormal_basis(&w);
+        let direction = u * (sin_theta * phi.cos()) + v * (sin_theta * phi.sin()) + w * cos_theta;
+
+        // Find where on the sphere that is so the material can tell how bright it is there.
+        let ray = Ray::new(*p, direction, 0.0);
+        let t = match math::sphere_roots(*p, direction, self.center, self.radius) {
+            Some((t0, _)) if t0 > 0.0 => t0,
+            _ => return None,
+        };
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = ray.point_at_parameter(t);
+        rec.local = rec.p - self.center;
+        let outward_normal = rec.local / self.radius;
+        rec.set_normal(&ray, outward_normal);
+        math::get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+        rec.material = self.material.clone();
+
+        Some(LightSample {
+            direction,
+            distance: rec.t,
+            radiance: self.material.emit(&ray, &rec),
+            pdf: 1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_max)),
+        })
+    }
+
+    fn pdf(&self, p: &Vec3, direction: &Vec3) -> f64 {
+        match self.cos_max(p) {
+            Some(cos_max) => {
+                let w = math::unit_vector(&(self.center - *p));
+                if math::dot(&w, &math::unit_vector(direction)) < cos_max {
+                    0.0
+                } else {
+                    1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_max))
+                }
+            }
+            None => 0.0,
+        }
+    }
+
+    fn emits_with(&self, material: &Arc<Material>) -> bool {
+        // Compare the data pointers only, the vtables of the same type can differ.
+        Arc::as_ptr(material) as *const u8 == Arc::as_ptr(&self.material) as *const u8
+    }
+}
+
+pub fn load_from_json(values: &Value) -> Option<(Box<Hitable + Sync>, Box<Light + Sync + Send>)> {
+    let center = json::get_vec3_or_rand(&values["position"])?;
+    let radius = json::get_f64_or_rand(&values["radius"])?;
+
+    let material: Arc<Material + Sync + Send> = DiffuseLight::create(ConstantTexture::create(
+        lights::load_power_from_json(values),
+    ));
+
+    Some((
+        Sphere::create(center, radius, material.clone()),
+        SphereLight::create(center, radius, material),
+    ))
+}
@@ -8,6 +8,7 @@ use structopt::StructOpt;
 use time::Duration;
 
 mod defaults;
+mod lights;
 mod materials;
 mod shapes;
 mod textures;
@@ -68,8 +69,9 @@ fn main() -> std::io::Result<()> {
                         for _ in 0..config.samples {
                             let u = (f64::from(x) + rng.gen::<f64>()) / f64::from(config.width);
                             let v = (f64::from(y) + rng.gen::<f64>()) / f64::from(config.height);
-                            let r = cam.get_ray(u, v);
-                            col += world::color(&r, &world, 0);
+                            if let Some(r) = cam.get_ray(u, v) {
+                                col += world::color(&r, &world, 0);
+                            }
                         }
 
                         // Thanks to the Rust Book Ch 16.3 for providing me this.
@@ -142,8 +144,9 @@ fn main() -> std::io::Result<()> {
                 for _ in 0..config.samples {
                     let u = (f64::from(i) + rng.gen::<f64>()) / f64::from(config.width);
                     let v = (f64::from(j) + rng.gen::<f64>()) / f64::from(config.height);
-                    let r = cam.get_ray(u, v);
-                    col += world::color(&r, &world, 0);
+                    if let Some(r) = cam.get_ray(u, v) {
+                        col += world::color(&r, &world, 0);
+                    }
                 }
                 col /= f64::from(config.samples);
                 col = Vec3::new(col.x.sqrt(), col.y.sqrt(), col.z.sqrt());
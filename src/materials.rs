@@ -2,22 +2,29 @@ use std::sync::Arc;
 
 use serde_json::Value;
 
+pub mod anisotropic;
 pub mod blank;
+pub mod bump_map;
+pub mod cutout;
 pub mod dielectric;
 pub mod diffuse_light;
+pub mod hair;
 pub mod isotropic;
 pub mod lambertian;
 pub mod metal;
+pub mod one_sided;
 
+pub use crate::materials::anisotropic::Anisotropic;
 pub use crate::materials::blank::Blank;
 pub use crate::materials::dielectric::Dielectric;
 pub use crate::materials::diffuse_light::DiffuseLight;
+pub use crate::materials::hair::Hair;
 pub use crate::materials::isotropic::Isotropic;
 pub use crate::materials::lambertian::Lambertian;
 pub use crate::materials::metal::Metal;
 
-use crate::textures::{CheckeredTexture, ConstantTexture, ImageTexture, NoiseTexture, TextureType};
-use crate::util::{hitable::HitRecord, json, ray::Ray, vector3::Vec3};
+use crate::textures::{self, TextureType};
+use crate::util::{hitable::HitRecord, ray::Ray, vector3::Vec3};
 
 pub trait Material {
     fn scatter(
@@ -28,15 +35,38 @@ pub trait Material {
         scattered: &mut Ray,
     ) -> bool;
 
-    fn emit(&self, _u: f64, _v: f64, _p: &Vec3) -> Vec3 {
+    fn emit(&self, _r_in: &Ray, _rec: &HitRecord) -> Vec3 {
         Vec3::zero()
     }
+
+    // Used to light the surface by sampling the lights directly: how much of the light arriving
+    // from `direction` gets scattered back along `r_in`, i.e. the BRDF (or phase function) times
+    // the cosine term. Materials like Metal and Dielectric that only scatter in one direction
+    // can't be lit this way and return None.
+    fn scattering(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> Option<Vec3> {
+        None
+    }
+
+    // The probability density of `scatter` picking `direction`, so that direct lighting and
+    // scattered rays that happen to hit a light can be weighted against each other.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // The chance of a ray stopping at the surface rather than going straight through it as if it
+    // weren't there.
+    fn opacity(&self, _rec: &HitRecord) -> f64 {
+        1.0
+    }
 }
 
 pub enum MaterialType {
     Lambertian,
     Isotropic,
+    Anisotropic(f64),
     Metal(f64),
+    // Specular, shift and roughness.
+    Hair(f64, f64, f64),
 }
 
 // Yay, got rid of the repeating code in lambertian.rs and metal.rs, but I don't know how
@@ -47,109 +77,44 @@ pub fn create_material(
     texture_type: TextureType,
     material_type: MaterialType,
 ) -> Arc<Material + Sync + Send> {
-    match texture_type {
-        TextureType::Checkered => {
-            let or = json::get_f64_or_rand(&values["material"]["colors"][0]["r"]);
-            let og = json::get_f64_or_rand(&values["material"]["colors"][0]["g"]);
-            let ob = json::get_f64_or_rand(&values["material"]["colors"][0]["b"]);
-            let er = json::get_f64_or_rand(&values["material"]["colors"][1]["r"]);
-            let eg = json::get_f64_or_rand(&values["material"]["colors"][1]["g"]);
-            let eb = json::get_f64_or_rand(&values["material"]["colors"][1]["b"]);
-            let (or, og, ob, er, eg, eb) = match (or, og, ob, er, eg, eb) {
-                (Some(or), Some(og), Some(ob), Some(er), Some(eg), Some(eb)) => {
-                    (or, og, ob, er, eg, eb)
-                }
-                (_, _, _, _, _, _) => (0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-            };
-
-            let scale = json::get_f64_or_rand(&values["material"]["scale"]);
-            let scale = match scale {
-                Some(s) => s,
-                _ => 1.0,
-            };
-
-            let texture = CheckeredTexture::create(
-                ConstantTexture::create(Vec3::new(or, og, ob)),
-                ConstantTexture::create(Vec3::new(er, eg, eb)),
-                scale,
-            );
-            match material_type {
-                MaterialType::Lambertian => Lambertian::create(texture),
-                MaterialType::Metal(fuzz) => Metal::create(texture, fuzz),
-                MaterialType::Isotropic => Isotropic::create(texture),
-            }
+    let texture = textures::create_texture(&values["material"], texture_type);
+    match material_type {
+        MaterialType::Lambertian => Lambertian::create(texture),
+        MaterialType::Metal(fuzz) => Metal::create(texture, fuzz),
+        MaterialType::Isotropic => Isotropic::create(texture),
+        MaterialType::Anisotropic(g) => Anisotropic::create(texture, g),
+        MaterialType::Hair(specular, shift, roughness) => {
+            Hair::create(texture, specular, shift, roughness)
         }
+    }
+}
 
-        TextureType::Constant => {
-            let r = json::get_f64_or_rand(&values["material"]["color"]["r"]);
-            let g = json::get_f64_or_rand(&values["material"]["color"]["g"]);
-            let b = json::get_f64_or_rand(&values["material"]["color"]["b"]);
-            let (r, g, b) = match (r, g, b) {
-                (Some(r), Some(g), Some(b)) => (r, g, b),
-                (_, _, _) => (0.0, 0.0, 0.0),
-            };
-
-            let texture = ConstantTexture::create(Vec3::new(r, g, b));
-            match material_type {
-                MaterialType::Lambertian => Lambertian::create(texture),
-                MaterialType::Metal(fuzz) => Metal::create(texture, fuzz),
-                MaterialType::Isotropic => Isotropic::create(texture),
-            }
-        }
-
-        TextureType::Image => {
-            let filename = values["material"]["filename"].as_str();
-            let filename = match filename {
-                Some(filename) => filename,
-                _ => {
-                    return Lambertian::create(CheckeredTexture::create(
-                        ConstantTexture::create(Vec3::new(0.0, 0.0, 0.0)),
-                        ConstantTexture::create(Vec3::new(1.0, 0.0, 1.0)),
-                        10.0,
-                    ));
-                }
-            };
-
-            let scale = json::get_f64_or_rand(&values["material"]["scale"]);
-            let scale = match scale {
-                Some(s) => s,
-                _ => 1.0,
-            };
-
-            let image_file = image::open(filename);
-            let image_file = match image_file {
-                Ok(image_file) => image_file,
-                Err(e) => {
-                    eprintln!("ERROR [{}]: {}", filename, e);
-                    return Lambertian::create(CheckeredTexture::create(
-                        ConstantTexture::create(Vec3::new(0.0, 0.0, 0.0)),
-                        ConstantTexture::create(Vec3::new(1.0, 0.0, 1.0)),
-                        10.0,
-                    ));
-                }
-            };
-
-            let texture = ImageTexture::create(&image_file, scale);
-            match material_type {
-                MaterialType::Lambertian => Lambertian::create(texture),
-                MaterialType::Metal(fuzz) => Metal::create(texture, fuzz),
-                MaterialType::Isotropic => Isotropic::create(texture),
-            }
-        }
-
-        TextureType::Noise => {
-            let scale = json::get_f64_or_rand(&values["material"]["scale"]);
-            let scale = match scale {
-                Some(s) => s,
-                _ => 1.0,
-            };
-
-            let texture = NoiseTexture::create(scale);
-            match material_type {
-                MaterialType::Lambertian => Lambertian::create(texture),
-                MaterialType::Metal(fuzz) => Metal::create(texture, fuzz),
-                MaterialType::Isotropic => Isotropic::create(texture),
-            }
+// Reads the `material` of an object. Every shape used to have its own copy of this match, so
+// adding a material meant touching all of them.
+pub fn load_from_json(values: &Value) -> Option<Arc<Material + Sync + Send>> {
+    let material_type = values["material"]["type"].as_str()?;
+    let mut parts = material_type.splitn(2, '/');
+    let kind = parts.next()?;
+    let texture_type = parts.next().map(TextureType::parse);
+
+    let material = match (kind, texture_type) {
+        ("matte", Some(Some(texture_type))) => lambertian::load_from_json(values, texture_type),
+        ("metal", Some(Some(texture_type))) => metal::load_from_json(values, texture_type),
+        ("isotropic", Some(Some(texture_type))) => isotropic::load_from_json(values, texture_type),
+        ("anisotropic", Some(Some(texture_type))) => {
+            anisotropic::load_from_json(values, texture_type)
         }
+        ("hair", Some(Some(texture_type))) => hair::load_from_json(values, texture_type),
+        ("dielectric", None) => dielectric::load_from_json(values),
+        ("light", None) => diffuse_light::load_from_json(values, TextureType::Constant),
+        ("light", Some(Some(texture_type))) => diffuse_light::load_from_json(values, texture_type),
+        (_, _) => return None,
+    };
+
+    let material = bump_map::load_from_json(&values["material"], material);
+    let material = cutout::load_from_json(&values["material"], material);
+    if kind == "light" {
+        return Some(material);
     }
+    Some(one_sided::load_from_json(&values["material"], material))
 }
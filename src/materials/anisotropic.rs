@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use serde_json::Value;
+
+use crate::materials::{create_material, Material, MaterialType};
+use crate::textures::{Texture, TextureType};
+use crate::util::{hitable::HitRecord, json, math, ray::Ray, vector3::Vec3};
+
+// Henyey-Greenstein phase function for volumes. `g` > 0 scatters light forward (fog, clouds),
+// `g` < 0 scatters it back towards where it came from, and `g` = 0 is the same as Isotropic.
+pub struct Anisotropic {
+    texture: Arc<Texture + Sync + Send>,
+    g: f64,
+}
+
+impl Anisotropic {
+    pub fn create(texture: Arc<Texture + Sync + Send>, g: f64) -> Arc<Anisotropic> {
+        Arc::new(Anisotropic {
+            texture,
+            // g = ±1 is a delta distribution and divides by zero below.
+            g: g.clamp(-0.999, 0.999),
+        })
+    }
+
+    fn sample_cos_theta(&self, xi: f64) -> f64 {
+        if self.g.abs() < 1.0e-3 {
+            return 1.0 - 2.0 * xi;
+        }
+        let g = self.g;
+        let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+    }
+
+    fn phase(&self, r_in: &Ray, direction: &Vec3) -> f64 {
+        let cos_theta = math::dot(
+            &math::unit_vector(&r_in.direction),
+            &math::unit_vector(direction),
+        );
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * std::f64::consts::PI * denominator * denominator.sqrt())
+    }
+}
+
+impl Material for Anisotropic {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let mut rng = rand::thread_rng();
+        let cos_theta = self.sample_cos_theta(rng.gen::<f64>());
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+
+        let w = math::unit_vector(&r_in.direction);
+        let (u, v) = math::orthonormal_basis(&w);
+        let direction = u * (sin_theta * phi.cos()) + v * (sin_theta * phi.sin()) + w * cos_theta;
+
+        *scattered = Ray::new(rec.p, direction, r_in.time);
+        *attenuation = self.texture.sample(rec);
+        true
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Vec3> {
+        Some(self.texture.sample(rec) * self.phase(r_in, direction))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, direction: &Vec3) -> f64 {
+        self.phase(r_in, direction)
+    }
+}
+
+pub fn load_from_json(values: &Value, texture_type: TextureType) -> Arc<Material + Sync + Send> {
+    let g = json::get_f64_or_rand(&values["material"]["anisotropy"]);
+    let g = match g {
+        Some(g) => g,
+        _ => 0.0,
+    };
+
+    create_material(values, texture_type, MaterialType::Anisotropic(g))
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::Material;
+use crate::textures::{self, image_texture, Texture, TextureType};
+use crate::util::{hitable::HitRecord, json, math, ray::Ray, vector3::Vec3};
+
+pub enum Perturbation {
+    // Heights from the brightness of the texture, scaled by the strength.
+    Bump(Arc<Texture + Sync + Send>, f64),
+    // Tangent space normals, with red along u, green along v and blue out of the surface. The
+    // strength blends between the surface's own normal (0) and the map's (1).
+    Normal(Arc<Texture + Sync + Send>, f64),
+}
+
+// Wraps another material and makes its surface look bumpy by changing the normal it sees.
+pub struct BumpMap {
+    material: Arc<Material + Sync + Send>,
+    perturbation: Perturbation,
+}
+
+impl BumpMap {
+    pub fn create(
+        material: Arc<Material + Sync + Send>,
+        perturbation: Perturbation,
+    ) -> Arc<BumpMap> {
+        Arc::new(BumpMap {
+            material,
+            perturbation,
+        })
+    }
+
+    fn perturb(&self, rec: &HitRecord) -> HitRecord {
+        let mut rec = rec.clone();
+        let n = rec.normal;
+        // Shapes that don't have a surface, like volumes, are left alone.
+        if math::cross(&rec.dpdu, &rec.dpdv).squared_length() <= 0.0 {
+            return rec;
+        }
+
+        let normal = match &self.perturbation {
+            Perturbation::Bump(texture, strength) => {
+                let height = |u: f64, v: f64, p: Vec3| {
+                    textures::brightness(&texture.value(u, v, p)) * strength
+                };
+                // Small steps in world space, however big the surface is.
+                let du = 1.0e-3 / rec.dpdu.length();
+                let dv = 1.0e-3 / rec.dpdv.length();
+                let h = height(rec.u, rec.v, rec.p);
+                let hu = (height(rec.u + du, rec.v, rec.p + rec.dpdu * du) - h) / du;
+                let hv = (height(rec.u, rec.v + dv, rec.p + rec.dpdv * dv) - h) / dv;
+                math::cross(&(rec.dpdu + n * hu), &(rec.dpdv + n * hv))
+            }
+            Perturbation::Normal(texture, strength) => {
+                let c = texture.sample(&rec) * 2.0 - Vec3::unit();
+                let t = math::unit_vector(&(rec.dpdu - n * math::dot(&n, &rec.dpdu)));
+                let mut b = math::cross(&n, &t);
+                if math::dot(&b, &rec.dpdv) < 0.0 {
+                    b = -b;
+                }
+                let mapped = math::unit_vector(&(t * c.x + b * c.y + n * c.z));
+                n * (1.0 - strength) + mapped * *strength
+            }
+        };
+
+        if normal.squared_length() > 0.0 {
+            let normal = math::unit_vector(&normal);
+            // Keep it on the same side as the surface.
+            rec.normal = if math::dot(&normal, &n) < 0.0 {
+                -normal
+            } else {
+                normal
+            };
+        }
+        rec
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        self.material
+            .scatter(r_in, &mut self.perturb(rec), attenuation, scattered)
+    }
+
+    fn emit(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        self.material.emit(r_in, &self.perturb(rec))
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Vec3> {
+        self.material
+            .scattering(r_in, &self.perturb(rec), direction)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        self.material
+            .scattering_pdf(r_in, &self.perturb(rec), direction)
+    }
+
+    fn opacity(&self, rec: &HitRecord) -> f64 {
+        self.material.opacity(rec)
+    }
+}
+
+// Wraps `material` with the `bump` and `normal_map` of a material's JSON, if it has them.
+pub fn load_from_json(
+    values: &Value,
+    material: Arc<Material + Sync + Send>,
+) -> Arc<Material + Sync + Send> {
+    let mut material = material;
+
+    let normal_map = &values["normal_map"];
+    if !normal_map.is_null() {
+        let strength = match json::get_f64_or_rand(&normal_map["strength"]) {
+            Some(s) => s,
+            _ => 1.0,
+        };
+        let texture = image_texture::load_from_json(normal_map, false);
+        material = BumpMap::create(material, Perturbation::Normal(texture, strength));
+    }
+
+    let bump = &values["bump"];
+    if !bump.is_null() {
+        let strength = match json::get_f64_or_rand(&bump["strength"]) {
+            Some(s) => s,
+            _ => 1.0,
+        };
+        match bump["type"].as_str().and_then(TextureType::parse) {
+            Some(texture_type) => {
+                let texture = textures::create_texture(bump, texture_type);
+                material = BumpMap::create(material, Perturbation::Bump(texture, strength));
+            }
+            None => eprintln!("ERROR: Can't get texture type of bump map! Ignoring..."),
+        }
+    }
+
+    material
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::Material;
+use crate::textures::{self, Texture, TextureType};
+use crate::util::{hitable::HitRecord, ray::Ray, vector3::Vec3};
+
+// Wraps another material and cuts holes into it wherever the mask is transparent, like the
+// gaps between the leaves of a branch drawn on a single quad.
+pub struct Cutout {
+    material: Arc<Material + Sync + Send>,
+    mask: Arc<Texture + Sync + Send>,
+}
+
+impl Cutout {
+    pub fn create(
+        material: Arc<Material + Sync + Send>,
+        mask: Arc<Texture + Sync + Send>,
+    ) -> Arc<Cutout> {
+        Arc::new(Cutout { material, mask })
+    }
+}
+
+impl Material for Cutout {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        self.material.scatter(r_in, rec, attenuation, scattered)
+    }
+
+    fn emit(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        self.material.emit(r_in, rec)
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Vec3> {
+        self.material.scattering(r_in, rec, direction)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        self.material.scattering_pdf(r_in, rec, direction)
+    }
+
+    fn opacity(&self, rec: &HitRecord) -> f64 {
+        self.mask.opacity(rec).clamp(0.0, 1.0) * self.material.opacity(rec)
+    }
+}
+
+// Wraps `material` with the `opacity` mask of a material's JSON, if it has one.
+pub fn load_from_json(
+    values: &Value,
+    material: Arc<Material + Sync + Send>,
+) -> Arc<Material + Sync + Send> {
+    let opacity = &values["opacity"];
+    if opacity.is_null() {
+        return material;
+    }
+
+    match opacity["type"].as_str().and_then(TextureType::parse) {
+        Some(texture_type) => {
+            Cutout::create(material, textures::create_texture(opacity, texture_type))
+        }
+        None => {
+            eprintln!("ERROR: Can't get texture type of opacity mask! Ignoring...");
+            material
+        }
+    }
+}
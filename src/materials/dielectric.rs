@@ -32,22 +32,15 @@ impl Material for Dielectric {
         *attenuation = self.color;
         let mut refracted = Vec3::zero();
         let reflection_probability;
-        let (outward_normal, ni_over_nt, cosine) = if math::dot(&r_in.direction, &rec.normal) > 0.0
-        {
-            (
-                -rec.normal,
-                self.refractive_index,
-                self.refractive_index * math::dot(&r_in.direction, &rec.normal)
-                    / r_in.direction.length(),
-            )
+        // The normal faces the ray, so only which side of the surface it's on decides which way
+        // the light bends.
+        let cosine = -math::dot(&r_in.direction, &rec.normal) / r_in.direction.length();
+        let (ni_over_nt, cosine) = if rec.front_face {
+            (1.0 / self.refractive_index, cosine)
         } else {
-            (
-                rec.normal,
-                1.0 / self.refractive_index,
-                -math::dot(&r_in.direction, &rec.normal) / r_in.direction.length(),
-            )
+            (self.refractive_index, self.refractive_index * cosine)
         };
-        if math::refract(&r_in.direction, &outward_normal, ni_over_nt, &mut refracted) {
+        if math::refract(&r_in.direction, &rec.normal, ni_over_nt, &mut refracted) {
             reflection_probability = math::schlik(cosine, self.refractive_index);
         } else {
             *scattered = Ray::new(rec.p, reflected, r_in.time);
@@ -81,3 +74,46 @@ pub fn load_from_json(values: &Value) -> Arc<Material + Sync + Send> {
 
     Dielectric::create(ri, Vec3::new(r, g, b))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A ray 60 degrees from the normal of a glass surface at the origin facing up.
+    fn scatter(direction: Vec3) -> Vec<Vec3> {
+        let glass = Dielectric::create(1.5, Vec3::unit());
+        let r = Ray::new(-direction, direction, 0.0);
+        let mut rec = HitRecord::new();
+        rec.set_normal(&r, Vec3::new(0.0, 0.0, 1.0));
+        (0..100)
+            .map(|_| {
+                let mut attenuation = Vec3::zero();
+                let mut scattered = Ray::new(Vec3::zero(), Vec3::zero(), 0.0);
+                assert!(glass.scatter(&r, &mut rec, &mut attenuation, &mut scattered));
+                math::unit_vector(&scattered.direction)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn entering_bends_towards_the_normal() {
+        let (sin, cos) = (60.0f64.to_radians().sin(), 60.0f64.to_radians().cos());
+        let directions = scatter(Vec3::new(sin, 0.0, -cos));
+        let refracted: Vec<&Vec3> = directions.iter().filter(|d| d.z < 0.0).collect();
+        assert!(!refracted.is_empty());
+        for d in refracted {
+            assert!((d.x - sin / 1.5).abs() < 1.0e-9);
+        }
+        for d in directions.iter().filter(|d| d.z > 0.0) {
+            assert!((d.x - sin).abs() < 1.0e-9 && (d.z - cos).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn exiting_past_the_critical_angle_reflects() {
+        let (sin, cos) = (60.0f64.to_radians().sin(), 60.0f64.to_radians().cos());
+        for d in scatter(Vec3::new(sin, 0.0, cos)) {
+            assert!((d.x - sin).abs() < 1.0e-9 && (d.z + cos).abs() < 1.0e-9);
+        }
+    }
+}
@@ -1,19 +1,77 @@
 use std::sync::Arc;
 
-use image;
 use serde_json::Value;
 
 use crate::materials::Material;
-use crate::textures::{constant_texture::ConstantTexture, Texture};
-use crate::util::{hitable::HitRecord, json, ray::Ray, vector3::Vec3};
+use crate::textures::{self, constant_texture::ConstantTexture, Texture, TextureType};
+use crate::util::{hitable::HitRecord, ies, json, math, ray::Ray, vector3::Vec3};
+
+// How the emitted light falls off with the angle from the light's axis.
+pub enum Profile {
+    // Full brightness inside `inner`, fading out smoothly until `outer`. Both are cosines.
+    Spot { inner: f64, outer: f64 },
+    // (angle in degrees, intensity) pairs sorted by angle, linearly interpolated.
+    Table(Vec<(f64, f64)>),
+}
+
+impl Profile {
+    pub fn value(&self, cos_theta: f64) -> f64 {
+        match self {
+            Profile::Spot { inner, outer } => {
+                if cos_theta >= *inner {
+                    1.0
+                } else if cos_theta <= *outer {
+                    0.0
+                } else {
+                    let t = (cos_theta - outer) / (inner - outer);
+                    t * t * (3.0 - 2.0 * t)
+                }
+            }
+            Profile::Table(table) => {
+                let angle = cos_theta.clamp(-1.0, 1.0).acos().to_degrees();
+                match table.iter().position(|(a, _)| *a >= angle) {
+                    Some(0) => table[0].1,
+                    Some(i) => {
+                        let (a0, v0) = table[i - 1];
+                        let (a1, v1) = table[i];
+                        v0 + (v1 - v0) * (angle - a0) / (a1 - a0)
+                    }
+                    // Past the end of the table, e.g. a fixture that only lights downwards.
+                    None => 0.0,
+                }
+            }
+        }
+    }
+}
 
 pub struct DiffuseLight {
     emitter: Arc<Texture + Sync + Send>,
+    intensity: f64,
+    two_sided: bool,
+    profile: Option<Profile>,
+    // The profile's axis. Uses the surface normal if there is none.
+    direction: Option<Vec3>,
 }
 
 impl DiffuseLight {
     pub fn create(emitter: Arc<Texture + Sync + Send>) -> Arc<DiffuseLight> {
-        Arc::new(DiffuseLight { emitter })
+        DiffuseLight::create_with(emitter, 1.0, true, None, None)
+    }
+
+    pub fn create_with(
+        emitter: Arc<Texture + Sync + Send>,
+        intensity: f64,
+        two_sided: bool,
+        profile: Option<Profile>,
+        direction: Option<Vec3>,
+    ) -> Arc<DiffuseLight> {
+        Arc::new(DiffuseLight {
+            emitter,
+            intensity,
+            two_sided,
+            profile,
+            direction: direction.map(|d| math::unit_vector(&d)),
+        })
     }
 }
 
@@ -28,19 +86,116 @@ impl Material for DiffuseLight {
         false
     }
 
-    fn emit(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
-        self.emitter.value(u, v, *p)
+    fn emit(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        let to_viewer = -math::unit_vector(&r_in.direction);
+        let facing = math::dot(&to_viewer, &rec.normal);
+        if !self.two_sided && !rec.front_face {
+            return Vec3::zero();
+        }
+
+        let falloff = match &self.profile {
+            Some(profile) => {
+                let cos_theta = match self.direction {
+                    Some(direction) => math::dot(&to_viewer, &direction),
+                    None => facing.abs(),
+                };
+                profile.value(cos_theta)
+            }
+            None => 1.0,
+        };
+        if falloff <= 0.0 {
+            return Vec3::zero();
+        }
+
+        self.emitter.sample(rec) * self.intensity * falloff
+    }
+}
+
+// Reads the `angle` and `falloff` of a spot, in degrees. A null `spot` gives the default cone.
+pub fn load_spot_from_json(spot: &Value) -> Profile {
+    let angle = match json::get_f64_or_rand(&spot["angle"]) {
+        Some(a) => a,
+        _ => 30.0,
+    };
+    let falloff = match json::get_f64_or_rand(&spot["falloff"]) {
+        Some(f) => f,
+        _ => 0.0,
+    };
+    // `angle` is the full cone, and the light fades out over `falloff` degrees past it.
+    let inner = (angle / 2.0).to_radians().cos();
+    let outer = (angle / 2.0 + falloff.max(1.0e-3))
+        .min(180.0)
+        .to_radians()
+        .cos();
+    Profile::Spot { inner, outer }
+}
+
+// Reads a `spot` or `profile` key from `values`.
+pub fn load_profile_from_json(values: &Value) -> Option<Profile> {
+    let spot = &values["spot"];
+    if !spot.is_null() {
+        return Some(load_spot_from_json(spot));
+    }
+
+    let profile = &values["profile"];
+    if let Some(filename) = profile["filename"].as_str() {
+        return match ies::load(filename) {
+            Ok(table) => Some(Profile::Table(table)),
+            Err(e) => {
+                eprintln!("ERROR [{}]: {}", filename, e);
+                None
+            }
+        };
+    }
+
+    let angles = profile["angles"].as_array();
+    let intensities = profile["values"].as_array();
+    match (angles, intensities) {
+        (Some(angles), Some(intensities)) if !angles.is_empty() => {
+            let mut table: Vec<(f64, f64)> = angles
+                .iter()
+                .zip(intensities.iter())
+                .map(|(a, v)| (a.as_f64().unwrap_or(0.0), v.as_f64().unwrap_or(0.0)))
+                .collect();
+            table.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            Some(Profile::Table(table))
+        }
+        (_, _) => None,
     }
 }
 
-pub fn load_from_json(values: &Value) -> Arc<Material + Sync + Send> {
-    let r = json::get_f64_or_rand(&values["material"]["color"]["r"]);
-    let g = json::get_f64_or_rand(&values["material"]["color"]["g"]);
-    let b = json::get_f64_or_rand(&values["material"]["color"]["b"]);
-    let (r, g, b) = match (r, g, b) {
-        (Some(r), Some(g), Some(b)) => (r, g, b),
-        (_, _, _) => (1.0, 1.0, 1.0),
+pub fn load_from_json(values: &Value, texture_type: TextureType) -> Arc<Material + Sync + Send> {
+    // Plain `light`s have always been white when no color is given.
+    let emitter = match texture_type {
+        TextureType::Constant if values["material"]["color"].is_null() => {
+            ConstantTexture::create(Vec3::unit())
+        }
+        texture_type => textures::create_texture(&values["material"], texture_type),
+    };
+
+    let intensity = match json::get_f64_or_rand(&values["material"]["intensity"]) {
+        Some(i) => i,
+        _ => 1.0,
+    };
+
+    let two_sided = match values["material"]["two_sided"].as_bool() {
+        Some(two_sided) => two_sided,
+        _ => true,
+    };
+
+    let dx = json::get_f64_or_rand(&values["material"]["direction"]["x"]);
+    let dy = json::get_f64_or_rand(&values["material"]["direction"]["y"]);
+    let dz = json::get_f64_or_rand(&values["material"]["direction"]["z"]);
+    let direction = match (dx, dy, dz) {
+        (Some(x), Some(y), Some(z)) => Some(Vec3::new(x, y, z)),
+        (_, _, _) => None,
     };
 
-    DiffuseLight::create(ConstantTexture::create(Vec3::new(r, g, b)))
+    DiffuseLight::create_with(
+        emitter,
+        intensity,
+        two_sided,
+        load_profile_from_json(&values["material"]),
+        direction,
+    )
 }
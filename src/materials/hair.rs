@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use serde_json::Value;
+
+use crate::materials::{create_material, Material, MaterialType};
+use crate::textures::{Texture, TextureType};
+use crate::util::{hitable::HitRecord, json, math, ray::Ray, vector3::Vec3};
+
+// A simplified Marschner model for fibers running along u: a white highlight reflected off the
+// surface, a colored one that went through the fiber and bounced back off its far side, and a
+// diffuse part for light scattered around inside of a lot of them. The cuticle scales tilt both
+// highlights, in opposite directions, by `shift`.
+pub struct Hair {
+    color: Arc<Texture + Sync + Send>,
+    // How much light is reflected off the surface, rather than going into the fiber.
+    specular: f64,
+    // In radians.
+    shift: f64,
+    roughness: f64,
+}
+
+fn gaussian(x: f64, width: f64) -> f64 {
+    (-x * x / (2.0 * width * width)).exp() / (width * (2.0 * std::f64::consts::PI).sqrt())
+}
+
+// Angle of `direction` from the plane perpendicular to the fiber.
+fn angle(tangent: &Vec3, direction: &Vec3) -> f64 {
+    math::dot(tangent, &math::unit_vector(direction))
+        .clamp(-1.0, 1.0)
+        .asin()
+}
+
+impl Hair {
+    pub fn create(
+        color: Arc<Texture + Sync + Send>,
+        specular: f64,
+        shift: f64,
+        roughness: f64,
+    ) -> Arc<Hair> {
+        Arc::new(Hair {
+            color,
+            specular: specular.clamp(0.0, 1.0),
+            shift,
+            roughness: roughness.max(1.0e-3),
+        })
+    }
+
+    fn tangent(rec: &HitRecord) -> Vec3 {
+        if rec.dpdu.squared_length() > 0.0 {
+            math::unit_vector(&rec.dpdu)
+        } else {
+            math::orthonormal_basis(&rec.normal).0
+        }
+    }
+
+    // Where the two highlights are centered and how wide they are, along the fiber.
+    fn lobes(&self) -> [(f64, f64); 2] {
+        [
+            (-self.shift, self.roughness),
+            (1.5 * self.shift, 2.0 * self.roughness),
+        ]
+    }
+
+    // How likely each of the highlights is picked by `scatter`, the rest goes to the diffuse part.
+    fn weights(&self) -> [f64; 2] {
+        [self.specular, (1.0 - self.specular) / 2.0]
+    }
+}
+
+impl Material for Hair {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let mut rng = rand::thread_rng();
+        let tangent = Hair::tangent(rec);
+        let theta_out = angle(&tangent, &-r_in.direction);
+
+        let weights = self.weights();
+        let pick = rng.gen::<f64>();
+        let lobe = if pick < weights[0] {
+            Some(self.lobes()[0])
+        } else if pick < weights[0] + weights[1] {
+            Some(self.lobes()[1])
+        } else {
+            None
+        };
+
+        let direction = match lobe {
+            Some((center, width)) => {
+                // Gaussian from Box-Muller, for the half angle between both directions.
+                let normal = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt()
+                    * (2.0 * std::f64::consts::PI * rng.gen::<f64>()).cos();
+                let theta_in = 2.0 * (center + width * normal) - theta_out;
+                if theta_in.abs() >= std::f64::consts::FRAC_PI_2 {
+                    // Not a direction at all. The ray carries nothing on, but the surface still
+                    // gets lit directly.
+                    *scattered = Ray::new(rec.p, math::random_unit_vector(), r_in.time);
+                    *attenuation = Vec3::zero();
+                    return true;
+                }
+                let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+                let (a, b) = math::orthonormal_basis(&tangent);
+                tangent * theta_in.sin() + (a * phi.cos() + b * phi.sin()) * theta_in.cos()
+            }
+            None => math::random_unit_vector(),
+        };
+
+        let pdf = self.scattering_pdf(r_in, rec, &direction);
+        *scattered = Ray::new(rec.p, direction, r_in.time);
+        *attenuation = match self.scattering(r_in, rec, &direction) {
+            Some(f) if pdf > 0.0 => f / pdf,
+            _ => Vec3::zero(),
+        };
+        true
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Vec3> {
+        let pi = std::f64::consts::PI;
+        let tangent = Hair::tangent(rec);
+        let theta_out = angle(&tangent, &-r_in.direction);
+        let theta_in = angle(&tangent, direction);
+        let half = (theta_in + theta_out) / 2.0;
+        let difference = (theta_out - theta_in) / 2.0;
+        // Each highlight spreads evenly around the fiber.
+        let spread = 2.0 * pi * difference.cos().powi(2).max(1.0e-2);
+
+        let [(reflected_center, reflected_width), (bounced_center, bounced_width)] = self.lobes();
+        let reflected = gaussian(half - reflected_center, reflected_width) / spread;
+        let bounced = gaussian(half - bounced_center, bounced_width) / spread;
+        let diffuse = 1.0 / (pi * pi);
+
+        let color = self.color.sample(rec);
+        let f = Vec3::unit() * (self.specular * reflected)
+            + color * ((1.0 - self.specular) * 0.5 * (bounced + diffuse));
+        Some(f * theta_in.cos())
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        let pi = std::f64::consts::PI;
+        let tangent = Hair::tangent(rec);
+        let theta_out = angle(&tangent, &-r_in.direction);
+        let theta_in = angle(&tangent, direction);
+        let half = (theta_in + theta_out) / 2.0;
+
+        // Picking the half angle picks the incoming one at twice the rate, and the solid angle
+        // shrinks towards the ends of the fiber.
+        let solid_angle = 2.0 * 2.0 * pi * theta_in.cos().max(1.0e-4);
+        let weights = self.weights();
+        let highlights = self
+            .lobes()
+            .iter()
+            .zip(weights.iter())
+            .map(|((center, width), weight)| weight * gaussian(half - center, *width))
+            .sum::<f64>()
+            / solid_angle;
+        highlights + (1.0 - weights[0] - weights[1]) / (4.0 * pi)
+    }
+}
+
+pub fn load_from_json(values: &Value, texture_type: TextureType) -> Arc<Material + Sync + Send> {
+    let specular = match json::get_f64_or_rand(&values["material"]["specular"]) {
+        Some(s) => s,
+        _ => 0.25,
+    };
+    let shift = match json::get_f64_or_rand(&values["material"]["shift"]) {
+        Some(s) => s,
+        _ => 5.0,
+    };
+    let roughness = match json::get_f64_or_rand(&values["material"]["roughness"]) {
+        Some(r) => r,
+        _ => 10.0,
+    };
+
+    create_material(
+        values,
+        texture_type,
+        MaterialType::Hair(specular, shift.to_radians(), roughness.to_radians()),
+    )
+}
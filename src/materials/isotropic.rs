@@ -25,9 +25,17 @@ impl Material for Isotropic {
         scattered: &mut Ray,
     ) -> bool {
         *scattered = Ray::new(rec.p, math::random_in_unit_sphere(), r_in.time);
-        *attenuation = self.texture.value(rec.u, rec.v, rec.p);
+        *attenuation = self.texture.sample(rec);
         true
     }
+
+    fn scattering(&self, _r_in: &Ray, rec: &HitRecord, _direction: &Vec3) -> Option<Vec3> {
+        Some(self.texture.sample(rec) / (4.0 * std::f64::consts::PI))
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
 }
 
 pub fn load_from_json(values: &Value, texture_type: TextureType) -> Arc<Material + Sync + Send> {
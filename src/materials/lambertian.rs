@@ -1,6 +1,5 @@
 use std::sync::Arc;
 
-use image;
 use serde_json::Value;
 
 use crate::materials::{create_material, Material, MaterialType};
@@ -25,11 +24,21 @@ impl Material for Lambertian {
         attenuation: &mut Vec3,
         scattered: &mut Ray,
     ) -> bool {
-        let target = rec.p + rec.normal + math::random_in_unit_sphere();
+        // A point on the unit sphere rather than in it gives an exact cosine distribution.
+        let target = rec.p + rec.normal + math::random_unit_vector();
         *scattered = Ray::new(rec.p, target - rec.p, r_in.time);
-        *attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+        *attenuation = self.albedo.sample(rec);
         true
     }
+
+    fn scattering(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Vec3> {
+        let cosine = math::dot(&rec.normal, &math::unit_vector(direction)).max(0.0);
+        Some(self.albedo.sample(rec) * cosine / std::f64::consts::PI)
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        math::dot(&rec.normal, &math::unit_vector(direction)).max(0.0) / std::f64::consts::PI
+    }
 }
 
 pub fn load_from_json(values: &Value, texture_type: TextureType) -> Arc<Material + Sync + Send> {
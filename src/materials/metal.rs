@@ -37,7 +37,7 @@ impl Material for Metal {
             reflected + self.fuzz * math::random_in_unit_sphere(),
             r_in.time,
         );
-        *attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+        *attenuation = self.albedo.sample(rec);
         math::dot(&scattered.direction, &rec.normal) > 0.0
     }
 }
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::Material;
+use crate::util::{hitable::HitRecord, ray::Ray, vector3::Vec3};
+
+// Wraps another material so only the front of the surface is there. Rays that reach the back
+// go straight through, like looking into a room through a wall that's only seen from inside.
+pub struct OneSided {
+    material: Arc<Material + Sync + Send>,
+}
+
+impl OneSided {
+    pub fn create(material: Arc<Material + Sync + Send>) -> Arc<OneSided> {
+        Arc::new(OneSided { material })
+    }
+}
+
+impl Material for OneSided {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        rec.front_face && self.material.scatter(r_in, rec, attenuation, scattered)
+    }
+
+    fn emit(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        if rec.front_face {
+            self.material.emit(r_in, rec)
+        } else {
+            Vec3::zero()
+        }
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Vec3> {
+        if rec.front_face {
+            self.material.scattering(r_in, rec, direction)
+        } else {
+            None
+        }
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        if rec.front_face {
+            self.material.scattering_pdf(r_in, rec, direction)
+        } else {
+            0.0
+        }
+    }
+
+    fn opacity(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            self.material.opacity(rec)
+        } else {
+            0.0
+        }
+    }
+}
+
+// Wraps `material` if its JSON has `"two_sided": false`. Lights read the key themselves, since
+// the back of a one-sided light is still there, just dark.
+pub fn load_from_json(
+    values: &Value,
+    material: Arc<Material + Sync + Send>,
+) -> Arc<Material + Sync + Send> {
+    match values["two_sided"].as_bool() {
+        Some(false) => OneSided::create(material),
+        _ => material,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use crate::textures::ConstantTexture;
+
+    #[test]
+    fn back_faces_are_not_there() {
+        let material = OneSided::create(Lambertian::create(ConstantTexture::create(Vec3::new(
+            0.5, 0.5, 0.5,
+        ))));
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let mut rec = HitRecord::new();
+        let mut attenuation = Vec3::zero();
+        let mut scattered = Ray::new(Vec3::zero(), Vec3::zero(), 0.0);
+
+        let front = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        rec.set_normal(&front, normal);
+        assert_eq!(material.opacity(&rec), 1.0);
+        assert!(material.scatter(&front, &mut rec, &mut attenuation, &mut scattered));
+        assert!(material.scattering(&front, &rec, &normal).is_some());
+
+        let back = Ray::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        rec.set_normal(&back, normal);
+        assert_eq!(material.opacity(&rec), 0.0);
+        assert!(!material.scatter(&back, &mut rec, &mut attenuation, &mut scattered));
+        assert!(material.scattering(&back, &rec, &-normal).is_none());
+        assert_eq!(material.scattering_pdf(&back, &rec, &-normal), 0.0);
+    }
+}
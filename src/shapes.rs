@@ -1,7 +1,19 @@
+pub mod capsule;
 pub mod constant_medium;
+pub mod csg;
 pub mod cuboid; // Can't name it box :(
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod heightfield;
+pub mod heterogeneous_medium;
 pub mod mesh;
 pub mod moving_sphere;
 pub mod plane;
+pub mod point_cloud;
+pub mod quad;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
 pub mod triangle;
+pub mod volume;
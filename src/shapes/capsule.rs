@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::{self, Blank, Material};
+use crate::shapes::heterogeneous_medium;
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// A cylinder standing on the y axis with half spheres on both ends, centered on the origin.
+// `height` is the length of the straight part in between.
+pub struct Capsule {
+    radius: f64,
+    height: f64,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl Capsule {
+    pub fn create(radius: f64, height: f64, material: Arc<Material + Sync + Send>) -> Box<Capsule> {
+        Box::new(Capsule {
+            radius,
+            height,
+            material,
+        })
+    }
+
+    // Where on the line through the middle `p` is closest to.
+    fn closest_on_axis(&self, p: &Vec3) -> Vec3 {
+        let half = self.height / 2.0;
+        Vec3::new(0.0, p.y.clamp(-half, half), 0.0)
+    }
+}
+
+impl Hitable for Capsule {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let half = self.height / 2.0;
+        let (o, d) = (r.origin, r.direction);
+        let mut closest = t_max;
+
+        // Each part only counts where it's on the outside of the capsule.
+        let mut consider = |t: f64, lowest: f64, highest: f64| {
+            let y = o.y + t * d.y;
+            if t > t_min && t < closest && y >= lowest && y <= highest {
+                closest = t;
+            }
+        };
+
+        // The straight part.
+        let a = d.x * d.x + d.z * d.z;
+        if a > 0.0 {
+            let b = o.x * d.x + o.z * d.z;
+            let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+            let discriminant = b * b - a * c;
+            if discriminant >= 0.0 {
+                let sqrt = discriminant.sqrt();
+                consider((-b - sqrt) / a, -half, half);
+                consider((-b + sqrt) / a, -half, half);
+            }
+        }
+
+        // The ends.
+        let infinity = f64::INFINITY;
+        if let Some((t0, t1)) = math::sphere_roots(o, d, Vec3::new(0.0, half, 0.0), self.radius) {
+            consider(t0, half, infinity);
+            consider(t1, half, infinity);
+        }
+        if let Some((t0, t1)) = math::sphere_roots(o, d, Vec3::new(0.0, -half, 0.0), self.radius) {
+            consider(t0, -infinity, -half);
+            consider(t1, -infinity, -half);
+        }
+
+        if closest >= t_max {
+            return false;
+        }
+
+        let p = r.point_at_parameter(closest);
+        let normal = (p - self.closest_on_axis(&p)) / self.radius;
+
+        // v goes from the bottom pole to the top one, evenly along the outline.
+        let pi = std::f64::consts::PI;
+        let outline = self.height + pi * self.radius;
+        let along = if p.y > half {
+            pi * self.radius / 2.0 + self.height + self.radius * normal.y.clamp(-1.0, 1.0).asin()
+        } else if p.y < -half {
+            pi * self.radius / 2.0 + self.radius * normal.y.clamp(-1.0, 1.0).asin()
+        } else {
+            pi * self.radius / 2.0 + p.y + half
+        };
+
+        let around = (normal.x * normal.x + normal.z * normal.z).sqrt();
+        let outwards = if around > 0.0 {
+            Vec3::new(normal.x, 0.0, normal.z) / around
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let (u, dpdu) = math::get_revolution_u(&p);
+        rec.t = closest;
+        rec.p = p;
+        rec.local = p;
+        rec.set_normal(r, normal);
+        rec.u = u;
+        rec.v = along / outline;
+        rec.dpdu = dpdu;
+        rec.dpdv = (Vec3::new(0.0, around, 0.0) - outwards * normal.y) * outline;
+        rec.material = self.material.clone();
+        true
+    }
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "capsules";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
+
+            let position = json::get_vec3_or_rand(&values[id][i]["position"]);
+            let radius = json::get_f64_or_rand(&values[id][i]["radius"]);
+            let height = json::get_f64_or_rand(&values[id][i]["height"]);
+            let (position, radius, height) = match (position, radius, height) {
+                (Some(position), Some(radius), Some(height)) => (position, radius, height),
+                (_, _, _) => {
+                    eprintln!(
+                        "ERROR: Can't get position, radius and height of capsule {}! Skipping...",
+                        i
+                    );
+                    continue;
+                }
+            };
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of capsule {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of capsule {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let object: Box<Hitable + Sync> = match density {
+                Some(density) => density
+                    .create_medium(Capsule::create(radius, height, Blank::create()), material),
+                None => Capsule::create(radius, height, material),
+            };
+            list.push(Translate::translate(
+                Rotate::rotate(object, rotation),
+                position,
+            ));
+        }
+    }
+
+    list
+}
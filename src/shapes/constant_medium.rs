@@ -51,11 +51,13 @@ impl Hitable for ConstantMedium {
                 rec1.t = 0.0;
             }
             let distance_inside_boundary = (rec2.t - rec1.t) * r.direction.length();
-            let hit_distance = -(1.0 / self.density) * rng.gen::<f64>().log10();
+            // Free-flight distances are exponentially distributed, hence the natural log.
+            let hit_distance = -(1.0 / self.density) * (1.0 - rng.gen::<f64>()).ln();
             if hit_distance < distance_inside_boundary {
                 rec.t = rec1.t + hit_distance / r.direction.length();
                 rec.p = r.point_at_parameter(rec.t);
-                rec.normal = Vec3::new(1.0, 0.0, 0.0); // arbitrary
+                rec.local = rec.p;
+                rec.set_normal(r, Vec3::new(1.0, 0.0, 0.0)); // arbitrary
                 rec.material = self.material.clone();
                 return true;
             }
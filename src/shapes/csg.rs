@@ -0,0 +1,326 @@
+use serde_json::{json, Value};
+
+use crate::shapes::{capsule, cuboid, cylinder, mesh, sphere, torus};
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+#[derive(Clone, Copy)]
+pub enum Operation {
+    Union,
+    Intersection,
+    // The first object with the second one carved out of it.
+    Difference,
+}
+
+impl Operation {
+    pub fn parse(name: &str) -> Option<Operation> {
+        match name {
+            "union" => Some(Operation::Union),
+            "intersection" => Some(Operation::Intersection),
+            "difference" => Some(Operation::Difference),
+            _ => None,
+        }
+    }
+
+    fn inside(self, a: bool, b: bool) -> bool {
+        match self {
+            Operation::Union => a || b,
+            Operation::Intersection => a && b,
+            Operation::Difference => a && !b,
+        }
+    }
+}
+
+// Combines two closed objects by walking along the ray through where it enters and leaves each
+// of them, which a plain closest hit can't tell.
+pub struct Csg {
+    operation: Operation,
+    a: Box<Hitable + Sync>,
+    b: Box<Hitable + Sync>,
+}
+
+impl Csg {
+    pub fn create(
+        operation: Operation,
+        a: Box<Hitable + Sync>,
+        b: Box<Hitable + Sync>,
+    ) -> Box<Csg> {
+        Box::new(Csg { operation, a, b })
+    }
+}
+
+// Every time the line along `r` crosses the surface of `object`, in order.
+fn crossings(object: &(Hitable + Sync), r: &Ray) -> Vec<HitRecord> {
+    let mut list = Vec::new();
+    let mut t_min = f64::MIN;
+    let mut rec = HitRecord::new();
+    // Bail out on surfaces that keep getting hit at the same spot.
+    while list.len() < 64 && object.hit(r, t_min, f64::MAX, &mut rec) {
+        t_min = rec.t + 0.0001;
+        list.push(rec.clone());
+    }
+    list
+}
+
+// Whether the line starts out inside, which it does when it first leaves the object.
+fn starts_inside(list: &[HitRecord]) -> bool {
+    match list.first() {
+        Some(first) => !first.front_face,
+        None => false,
+    }
+}
+
+impl Hitable for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let a = crossings(&*self.a, r);
+        let b = crossings(&*self.b, r);
+        let (mut inside_a, mut inside_b) = (starts_inside(&a), starts_inside(&b));
+        let mut inside = self.operation.inside(inside_a, inside_b);
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            let from_a = j >= b.len() || (i < a.len() && a[i].t <= b[j].t);
+            let crossing = if from_a {
+                inside_a = !inside_a;
+                i += 1;
+                &a[i - 1]
+            } else {
+                inside_b = !inside_b;
+                j += 1;
+                &b[j - 1]
+            };
+
+            let now_inside = self.operation.inside(inside_a, inside_b);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+            if crossing.t <= t_min || crossing.t >= t_max {
+                continue;
+            }
+
+            *rec = crossing.clone();
+            // What's carved out faces into the hole it leaves, so its inside becomes the outside.
+            if let (Operation::Difference, false) = (self.operation, from_a) {
+                rec.front_face = !rec.front_face;
+            }
+            return true;
+        }
+        false
+    }
+}
+
+// The `type`s of the closed objects that can go in a `csg`, and which block they'd be in.
+fn block_id(object_type: &str) -> Option<&'static str> {
+    match object_type {
+        "sphere" => Some("spheres"),
+        "cuboid" => Some("cuboids"),
+        "cylinder" => Some("cylinders"),
+        "cone" => Some("cones"),
+        "torus" => Some("tori"),
+        "capsule" => Some("capsules"),
+        "mesh" => Some("meshes"),
+        _ => None,
+    }
+}
+
+// One of the `objects` of a `csg`, loaded the same way as in its own block. Ones without a
+// material get the `csg`'s.
+fn load_object_from_json(
+    values: &Value,
+    material: &Value,
+    verbose: bool,
+) -> Option<Box<Hitable + Sync>> {
+    let object_type = values["type"].as_str().unwrap_or("");
+    if object_type == "csg" {
+        return load_csg_from_json(values, material, verbose);
+    }
+    let id = block_id(object_type)?;
+    // A medium has no surface for the crossings to be found on.
+    if !values["density"].is_null() {
+        eprintln!("ERROR: A csg object can't have a density!");
+        return None;
+    }
+
+    let mut object = values.clone();
+    if object["material"].is_null() {
+        object["material"] = material.clone();
+    }
+    object["copies"] = json!(1);
+    let loader = match id {
+        "spheres" => sphere::load_from_json,
+        "cuboids" => cuboid::load_from_json,
+        "cylinders" | "cones" => cylinder::load_from_json,
+        "tori" => torus::load_from_json,
+        "capsules" => capsule::load_from_json,
+        _ => mesh::load_from_json,
+    };
+    loader(&json!({ id: [object] }), verbose).pop()
+}
+
+fn load_csg_from_json(
+    values: &Value,
+    material: &Value,
+    verbose: bool,
+) -> Option<Box<Hitable + Sync>> {
+    let operation = match values["operation"].as_str().and_then(Operation::parse) {
+        Some(operation) => operation,
+        None => {
+            eprintln!("ERROR: Can't get operation of csg! Skipping...");
+            return None;
+        }
+    };
+
+    let material = if values["material"].is_null() {
+        material
+    } else {
+        &values["material"]
+    };
+
+    let length = match values["objects"].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+    let mut objects = Vec::new();
+    for i in 0..length {
+        match load_object_from_json(&values["objects"][i], material, verbose) {
+            Some(object) => objects.push(object),
+            None => eprintln!("ERROR: Can't load object {} of csg! Skipping...", i),
+        }
+    }
+    if objects.len() < 2 {
+        eprintln!("ERROR: A csg needs at least two objects! Skipping...");
+        return None;
+    }
+
+    // More than two are combined in order, e.g. the first minus all of the others.
+    let mut objects = objects.into_iter();
+    let first = objects.next()?;
+    let combined = objects.fold(first, |a, b| -> Box<Hitable + Sync> {
+        Csg::create(operation, a, b)
+    });
+
+    let position = match json::get_vec3_or_rand(&values["position"]) {
+        Some(position) => position,
+        None => Vec3::zero(),
+    };
+    let rotation = match json::get_vec3_or_rand(&values["rotation"]) {
+        Some(rotation) => rotation,
+        None => Vec3::zero(),
+    };
+    Some(Translate::translate(
+        Rotate::rotate(combined, rotation),
+        position,
+    ))
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "csg";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            if let Some(object) = load_csg_from_json(&values[id][i], &Value::Null, verbose) {
+                list.push(object);
+            }
+        }
+    }
+
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Blank;
+    use crate::shapes::sphere::Sphere;
+
+    // Two unit spheres at `x = -offset` and `x = offset`.
+    fn pair(operation: Operation, offset: f64) -> Box<Csg> {
+        Csg::create(
+            operation,
+            Sphere::create(Vec3::new(-offset, 0.0, 0.0), 1.0, Blank::create()),
+            Sphere::create(Vec3::new(offset, 0.0, 0.0), 1.0, Blank::create()),
+        )
+    }
+
+    // Where a ray along x from `from` hits the csg, and whether it's heading in there.
+    fn hit_x(csg: &Csg, from: f64, t_min: f64) -> Option<(f64, bool)> {
+        let direction = if from < 0.0 { 1.0 } else { -1.0 };
+        let r = Ray::new(
+            Vec3::new(from, 0.0, 0.0),
+            Vec3::new(direction, 0.0, 0.0),
+            0.0,
+        );
+        let mut rec = HitRecord::new();
+        if csg.hit(&r, t_min, f64::MAX, &mut rec) {
+            assert!(rec.normal.x * direction < 0.0);
+            Some((rec.p.x, rec.front_face))
+        } else {
+            None
+        }
+    }
+
+    fn close(hit: Option<(f64, bool)>, x: f64, front_face: bool) -> bool {
+        match hit {
+            Some((hx, f)) => (hx - x).abs() < 1.0e-9 && f == front_face,
+            None => false,
+        }
+    }
+
+    #[test]
+    fn union_skips_inner_surfaces() {
+        let csg = pair(Operation::Union, 0.5);
+        assert!(close(hit_x(&csg, -5.0, 0.0), -1.5, true));
+        // Past the first surface the next one is where it leaves the second sphere.
+        assert!(close(hit_x(&csg, -5.0, 3.6), 1.5, false));
+        // From the inside.
+        assert!(close(hit_x(&csg, 0.0, 0.0), -1.5, false));
+    }
+
+    #[test]
+    fn intersection_keeps_the_overlap() {
+        let csg = pair(Operation::Intersection, 0.5);
+        assert!(close(hit_x(&csg, -5.0, 0.0), -0.5, true));
+        assert!(close(hit_x(&csg, 5.0, 0.0), 0.5, true));
+        assert!(hit_x(&pair(Operation::Intersection, 1.5), -5.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn difference_turns_the_carved_surface_around() {
+        let csg = pair(Operation::Difference, 0.5);
+        assert!(close(hit_x(&csg, -5.0, 0.0), -1.5, true));
+        // Coming from the carved side the ray first meets the inside of the second sphere.
+        assert!(close(hit_x(&csg, 5.0, 0.0), -0.5, true));
+        // Then leaves through the far side of the first one.
+        assert!(close(hit_x(&csg, 5.0, 5.6), -1.5, false));
+        assert!(hit_x(&csg, 5.0, 6.6).is_none());
+    }
+
+    #[test]
+    fn starts_inside_from_the_first_crossing() {
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let sphere = Sphere::create(Vec3::zero(), 1.0, Blank::create());
+        let list = crossings(&*sphere, &r);
+        assert_eq!(list.len(), 2);
+        assert!(!starts_inside(&list));
+        assert!(!starts_inside(&[]));
+    }
+}
@@ -2,9 +2,8 @@ use std::sync::Arc;
 
 use serde_json::Value;
 
-use crate::materials::{blank::Blank, dielectric, diffuse_light, lambertian, metal, Material};
-use crate::shapes::constant_medium::ConstantMedium;
-use crate::textures::TextureType;
+use crate::materials::{self, Blank, Material};
+use crate::shapes::heterogeneous_medium;
 use crate::transform::{rotate::Rotate, translate::Translate};
 use crate::util::{
     hitable::{HitRecord, Hitable},
@@ -108,7 +107,7 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
         } as usize;
 
         for _ in 0..copies {
-            let density = json::get_f64_or_rand(&values[id][i]["density"]);
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
 
             let px = json::get_f64_or_rand(&values[id][i]["position"]["x"]);
             let py = json::get_f64_or_rand(&values[id][i]["position"]["y"]);
@@ -144,32 +143,9 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
                 }
             };
 
-            let material: Arc<Material + Sync + Send> = match values[id][i]["material"]["type"]
-                .as_str()
-            {
-                Some("matte/constant") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("matte/checkered") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("matte/image") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("matte/noise") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("metal/constant") => {
-                    metal::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("metal/checkered") => {
-                    metal::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("metal/image") => metal::load_from_json(&values[id][i], TextureType::Image),
-                Some("metal/noise") => metal::load_from_json(&values[id][i], TextureType::Noise),
-                Some("dielectric") => dielectric::load_from_json(&values[id][i]),
-                Some("light") => diffuse_light::load_from_json(&values[id][i]),
-                _ => {
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
                     eprintln!("ERROR: Can't get material of cuboid {}! Skipping...", i);
                     continue;
                 }
@@ -179,8 +155,7 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
                 Some(density) => {
                     list.push(Translate::translate(
                         Rotate::rotate(
-                            ConstantMedium::create(
-                                density,
+                            density.create_medium(
                                 Cuboid::create(
                                     Vec3::zero(),
                                     Vec3::new(sx, sy, sz),
@@ -274,11 +249,20 @@ impl Hitable for Rect {
                 rec.v = (self.min.y - y) / (self.max.y - self.min.y);
                 rec.t = t;
                 rec.p = r.point_at_parameter(t);
-                rec.normal = if self.normal_flip == NormalType::Flipped {
+                rec.local = rec.p;
+                let outward_normal = if self.normal_flip == NormalType::Flipped {
                     Vec3::new(0.0, 0.0, -1.0)
                 } else {
                     Vec3::new(0.0, 0.0, 1.0)
                 };
+                rec.set_normal(r, outward_normal);
+                rec.dpdu = Vec3::new(self.max.x - self.min.x, 0.0, 0.0)
+                    * if self.normal_flip == NormalType::Flipped {
+                        -1.0
+                    } else {
+                        1.0
+                    };
+                rec.dpdv = Vec3::new(0.0, self.min.y - self.max.y, 0.0);
             }
 
             RectType::RectXZ => {
@@ -300,11 +284,20 @@ impl Hitable for Rect {
                 rec.v = (z - self.min.z) / (self.max.z - self.min.z);
                 rec.t = t;
                 rec.p = r.point_at_parameter(t);
-                rec.normal = if self.normal_flip == NormalType::Flipped {
+                rec.local = rec.p;
+                let outward_normal = if self.normal_flip == NormalType::Flipped {
                     Vec3::new(0.0, -1.0, 0.0)
                 } else {
                     Vec3::new(0.0, 1.0, 0.0)
                 };
+                rec.set_normal(r, outward_normal);
+                rec.dpdu = Vec3::new(self.max.x - self.min.x, 0.0, 0.0)
+                    * if self.normal_flip == NormalType::Flipped {
+                        -1.0
+                    } else {
+                        1.0
+                    };
+                rec.dpdv = Vec3::new(0.0, 0.0, self.max.z - self.min.z);
             }
 
             RectType::RectYZ => {
@@ -326,11 +319,20 @@ impl Hitable for Rect {
                 rec.v = (self.min.y - y) / (self.max.y - self.min.y);
                 rec.t = t;
                 rec.p = r.point_at_parameter(t);
-                rec.normal = if self.normal_flip == NormalType::Flipped {
+                rec.local = rec.p;
+                let outward_normal = if self.normal_flip == NormalType::Flipped {
                     Vec3::new(-1.0, 0.0, 0.0)
                 } else {
                     Vec3::new(1.0, 0.0, 0.0)
                 };
+                rec.set_normal(r, outward_normal);
+                rec.dpdu = Vec3::new(0.0, 0.0, self.max.z - self.min.z)
+                    * if self.normal_flip == NormalType::Flipped {
+                        1.0
+                    } else {
+                        -1.0
+                    };
+                rec.dpdv = Vec3::new(0.0, self.min.y - self.max.y, 0.0);
             }
         }
 
@@ -0,0 +1,588 @@
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::{self, Material};
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+#[derive(Clone, Copy)]
+pub enum CurveShape {
+    // A flat strip that always faces the ray, cheap and fine for thin hair.
+    Ribbon,
+    // A round tube, for wires and anything thick enough to see the shading across it.
+    Cylinder,
+}
+
+#[derive(Clone, Copy)]
+pub enum Basis {
+    // Every third point is on the curve, the ones in between pull it towards them.
+    Bezier,
+    // Uniform cubic B-spline, which doesn't go through any of the points but is smoother.
+    BSpline,
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a * (1.0 - t) + b * t
+}
+
+// The cubic Bézier curve through `p` at `t`.
+fn evaluate(p: &[Vec3; 4], t: f64) -> Vec3 {
+    let (a, b) = (p[0] * (1.0 - t) + p[1] * t, p[1] * (1.0 - t) + p[2] * t);
+    let c = p[2] * (1.0 - t) + p[3] * t;
+    let (d, e) = (a * (1.0 - t) + b * t, b * (1.0 - t) + c * t);
+    d * (1.0 - t) + e * t
+}
+
+fn derivative(p: &[Vec3; 4], t: f64) -> Vec3 {
+    let (a, b, c) = (p[1] - p[0], p[2] - p[1], p[3] - p[2]);
+    ((a * (1.0 - t) + b * t) * (1.0 - t) + (b * (1.0 - t) + c * t) * t) * 3.0
+}
+
+// The two halves of the curve, each as a curve of its own.
+fn split(p: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let (a, b, c) = (
+        (p[0] + p[1]) / 2.0,
+        (p[1] + p[2]) / 2.0,
+        (p[2] + p[3]) / 2.0,
+    );
+    let (d, e) = ((a + b) / 2.0, (b + c) / 2.0);
+    let middle = (d + e) / 2.0;
+    ([p[0], a, d, middle], [middle, e, c, p[3]])
+}
+
+fn bounds(points: &[Vec3]) -> (Vec3, Vec3) {
+    points.iter().fold(
+        (Vec3::unit() * f64::INFINITY, Vec3::unit() * -f64::INFINITY),
+        |(min, max), p| {
+            (
+                Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )
+        },
+    )
+}
+
+// Whether the ray goes through the box between `t_min` and `t_max`.
+fn hits_box(min: &Vec3, max: &Vec3, r: &Ray, t_min: f64, t_max: f64) -> bool {
+    let (mut near, mut far) = (t_min, t_max);
+    for i in 0..3 {
+        let inverse = 1.0 / r.direction[i];
+        let mut t0 = (min[i] - r.origin[i]) * inverse;
+        let mut t1 = (max[i] - r.origin[i]) * inverse;
+        if inverse < 0.0 {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        near = near.max(t0);
+        far = far.min(t1);
+        if far < near {
+            return false;
+        }
+    }
+    true
+}
+
+// One cubic Bézier segment of a strand, getting thinner or thicker along the way.
+pub struct Curve {
+    points: [Vec3; 4],
+    // At the start and the end.
+    radii: (f64, f64),
+    // Which part of the whole strand this is, for the texture coordinates.
+    range: (f64, f64),
+    shape: CurveShape,
+    // How many times to split it in half before it's straight enough to treat as a line.
+    depth: usize,
+    min: Vec3,
+    max: Vec3,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl Curve {
+    pub fn create(
+        points: [Vec3; 4],
+        radii: (f64, f64),
+        range: (f64, f64),
+        shape: CurveShape,
+        material: Arc<Material + Sync + Send>,
+    ) -> Curve {
+        let width = radii.0.max(radii.1);
+        let (min, max) = bounds(&points);
+
+        // How much the control points bend away from a straight line decides how finely to
+        // split it, so the error ends up a small part of its width.
+        let bend = (0..2)
+            .map(|i| (points[i] - points[i + 1] * 2.0 + points[i + 2]).length())
+            .fold(0.0, f64::max);
+        let depth = if bend > 0.0 && width > 0.0 {
+            let splits = 2.0_f64.sqrt() * 6.0 * bend / (8.0 * width * 0.05);
+            (splits.log2() / 2.0).ceil().clamp(0.0, 10.0) as usize
+        } else {
+            0
+        };
+
+        Curve {
+            points,
+            radii,
+            range,
+            shape,
+            depth,
+            min: min - Vec3::unit() * width,
+            max: max + Vec3::unit() * width,
+            material,
+        }
+    }
+
+    fn radius(&self, t: f64) -> f64 {
+        lerp(self.radii.0, self.radii.1, t)
+    }
+
+    // Finds the closest hit on the part of the curve from `t0` to `t1`, whose control points
+    // have been moved to where the ray starts at the origin and goes along z. `closest` is
+    // (z, t along the curve, across it from -1 to 1).
+    fn intersect(
+        &self,
+        points: &[Vec3; 4],
+        t0: f64,
+        t1: f64,
+        depth: usize,
+        z_min: f64,
+        closest: &mut Option<(f64, f64, f64)>,
+    ) {
+        let z_max = match closest {
+            Some((z, _, _)) => *z,
+            None => f64::INFINITY,
+        };
+        let width = self.radius(t0).max(self.radius(t1));
+        let (min, max) = bounds(points);
+        if min.x - width > 0.0
+            || max.x + width < 0.0
+            || min.y - width > 0.0
+            || max.y + width < 0.0
+            || max.z + width < z_min
+            || min.z - width > z_max
+        {
+            return;
+        }
+
+        if depth > 0 {
+            let (first, second) = split(points);
+            let middle = (t0 + t1) / 2.0;
+            self.intersect(&first, t0, middle, depth - 1, z_min, closest);
+            self.intersect(&second, middle, t1, depth - 1, z_min, closest);
+            return;
+        }
+
+        // Straight enough now. Find where along it is closest to the ray, skipping the ends so
+        // the pieces don't overlap.
+        let edge = points[3] - points[0];
+        let length_squared = edge.x * edge.x + edge.y * edge.y;
+        if length_squared == 0.0 {
+            return;
+        }
+        let w = -(points[0].x * edge.x + points[0].y * edge.y) / length_squared;
+        if !(0.0..=1.0).contains(&w) {
+            return;
+        }
+        let p = evaluate(points, w);
+        let t = lerp(t0, t1, w);
+        let radius = self.radius(t);
+        let distance_squared = p.x * p.x + p.y * p.y;
+        if distance_squared > radius * radius {
+            return;
+        }
+        let z = match self.shape {
+            CurveShape::Ribbon => p.z,
+            CurveShape::Cylinder => p.z - (radius * radius - distance_squared).sqrt(),
+        };
+        if z < z_min || z > z_max {
+            return;
+        }
+        let side = if edge.x * p.y - edge.y * p.x > 0.0 {
+            1.0
+        } else {
+            -1.0
+        };
+        *closest = Some((z, t, side * distance_squared.sqrt() / radius));
+    }
+}
+
+impl Hitable for Curve {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if !hits_box(&self.min, &self.max, r, t_min, t_max) {
+            return false;
+        }
+
+        // Line the ray up with z, so the curve only has to be checked for being close to the
+        // origin in x and y.
+        let speed = r.direction.length();
+        let w = r.direction / speed;
+        let (a, b) = math::orthonormal_basis(&w);
+        let to_ray = |p: &Vec3| {
+            let p = *p - r.origin;
+            Vec3::new(math::dot(&p, &a), math::dot(&p, &b), math::dot(&p, &w))
+        };
+        let points = [
+            to_ray(&self.points[0]),
+            to_ray(&self.points[1]),
+            to_ray(&self.points[2]),
+            to_ray(&self.points[3]),
+        ];
+
+        let mut closest = None;
+        self.intersect(&points, 0.0, 1.0, self.depth, t_min * speed, &mut closest);
+        let (z, t, across) = match closest {
+            Some(closest) => closest,
+            None => return false,
+        };
+        if z / speed >= t_max {
+            return false;
+        }
+
+        let p = r.point_at_parameter(z / speed);
+        let direction = derivative(&self.points, t);
+        let tangent = math::unit_vector(&direction);
+        let normal = match self.shape {
+            CurveShape::Ribbon => -w + tangent * math::dot(&w, &tangent),
+            CurveShape::Cylinder => p - evaluate(&self.points, t),
+        };
+        let normal = if normal.squared_length() > 0.0 {
+            math::unit_vector(&normal)
+        } else {
+            -w
+        };
+
+        rec.t = z / speed;
+        rec.p = p;
+        rec.local = p;
+        rec.set_normal(r, normal);
+        rec.u = lerp(self.range.0, self.range.1, t);
+        rec.v = 0.5 + 0.5 * across;
+        rec.dpdu = direction / (self.range.1 - self.range.0);
+        rec.dpdv = math::cross(&normal, &tangent) * (2.0 * self.radius(t));
+        rec.material = self.material.clone();
+        true
+    }
+}
+
+// Children are the next node and `second`, unless `count` is non-zero, which makes it a leaf of
+// `count` segments from `start`.
+struct Node {
+    min: Vec3,
+    max: Vec3,
+    start: usize,
+    count: usize,
+    second: usize,
+    axis: usize,
+}
+
+// A bunch of strands, with a BVH over their segments so a ray only gets tested against the few
+// segments near it.
+pub struct Curves {
+    curves: Vec<Curve>,
+    nodes: Vec<Node>,
+}
+
+const LEAF_SIZE: usize = 4;
+
+impl Curves {
+    // Each strand is split into segments of `basis`, `radii` going from its root to its tip.
+    pub fn create(
+        strands: &[Vec<Vec3>],
+        basis: Basis,
+        shape: CurveShape,
+        radii: (f64, f64),
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Curves> {
+        let mut curves = Vec::new();
+        for points in strands.iter() {
+            let segments: Vec<[Vec3; 4]> = match basis {
+                Basis::Bezier => points
+                    .windows(4)
+                    .step_by(3)
+                    .map(|p| [p[0], p[1], p[2], p[3]])
+                    .collect(),
+                Basis::BSpline => points
+                    .windows(4)
+                    .map(|p| {
+                        [
+                            (p[0] + p[1] * 4.0 + p[2]) / 6.0,
+                            (p[1] * 2.0 + p[2]) / 3.0,
+                            (p[1] + p[2] * 2.0) / 3.0,
+                            (p[1] + p[2] * 4.0 + p[3]) / 6.0,
+                        ]
+                    })
+                    .collect(),
+            };
+            let count = segments.len() as f64;
+            for (i, segment) in segments.into_iter().enumerate() {
+                let range = (i as f64 / count, (i + 1) as f64 / count);
+                curves.push(Curve::create(
+                    segment,
+                    (
+                        lerp(radii.0, radii.1, range.0),
+                        lerp(radii.0, radii.1, range.1),
+                    ),
+                    range,
+                    shape,
+                    material.clone(),
+                ));
+            }
+        }
+
+        let mut list = Curves {
+            curves,
+            nodes: Vec::new(),
+        };
+        if !list.curves.is_empty() {
+            let length = list.curves.len();
+            list.nodes.reserve(2 * length / LEAF_SIZE + 1);
+            list.build(0, length);
+        }
+        Box::new(list)
+    }
+
+    // Splits the segments at the median of their centers along the longest side of the box
+    // around those centers, until there are few enough in each leaf. Returns the index of the
+    // node.
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let curves = &self.curves[start..end];
+        let corners: Vec<Vec3> = curves.iter().flat_map(|c| vec![c.min, c.max]).collect();
+        let (min, max) = bounds(&corners);
+        let centers: Vec<Vec3> = curves.iter().map(|c| (c.min + c.max) / 2.0).collect();
+        let (low, high) = bounds(&centers);
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            min,
+            max,
+            start,
+            count: end - start,
+            second: 0,
+            axis: 0,
+        });
+        if end - start <= LEAF_SIZE {
+            return index;
+        }
+
+        let size = high - low;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        let middle = (end - start) / 2;
+        self.curves[start..end].select_nth_unstable_by(middle, |a, b| {
+            (a.min[axis] + a.max[axis]).total_cmp(&(b.min[axis] + b.max[axis]))
+        });
+
+        self.build(start, start + middle);
+        let second = self.build(start + middle, end);
+        let node = &mut self.nodes[index];
+        node.count = 0;
+        node.second = second;
+        node.axis = axis;
+        index
+    }
+}
+
+impl Hitable for Curves {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+        let mut stack = [0usize; 64];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let node = &self.nodes[stack[top]];
+            if !hits_box(&node.min, &node.max, r, t_min, closest_so_far) {
+                continue;
+            }
+
+            if node.count > 0 {
+                for curve in self.curves[node.start..node.start + node.count].iter() {
+                    if curve.hit(r, t_min, closest_so_far, rec) {
+                        hit_anything = true;
+                        closest_so_far = rec.t;
+                    }
+                }
+            } else if top + 2 <= stack.len() {
+                // The child on the side the ray comes from goes on top, to be looked at first.
+                let first = stack[top] + 1;
+                if r.direction[node.axis] < 0.0 {
+                    stack[top] = first;
+                    stack[top + 1] = node.second;
+                } else {
+                    stack[top] = node.second;
+                    stack[top + 1] = first;
+                }
+                top += 2;
+            }
+        }
+        hit_anything
+    }
+}
+
+// A strand per line of `x y z` control points, all on the same line. Empty lines and ones
+// starting with # are skipped.
+pub fn load_curves_file(filename: &str) -> io::Result<Vec<Vec<Vec3>>> {
+    let data = fs::read_to_string(filename)?;
+    let mut strands = Vec::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let numbers = line
+            .split_whitespace()
+            .map(|n| n.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, e),
+                )
+            })?;
+        if numbers.len() % 3 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: points need three numbers each", number + 1),
+            ));
+        }
+        strands.push(
+            numbers
+                .chunks(3)
+                .map(|p| Vec3::new(p[0], p[1], p[2]))
+                .collect(),
+        );
+    }
+    Ok(strands)
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "curves";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            let strands = match values[id][i]["filename"].as_str() {
+                Some(filename) => match load_curves_file(filename) {
+                    Ok(strands) => strands,
+                    Err(e) => {
+                        eprintln!("ERROR [{}]: {}", filename, e);
+                        continue;
+                    }
+                },
+                None => {
+                    let count = match values[id][i]["strands"].as_array() {
+                        Some(strands) => strands.len(),
+                        _ => 0,
+                    };
+                    (0..count)
+                        .map(|s| {
+                            let points = &values[id][i]["strands"][s];
+                            let count = match points.as_array() {
+                                Some(points) => points.len(),
+                                _ => 0,
+                            };
+                            (0..count)
+                                .filter_map(|p| json::get_vec3_or_rand(&points[p]))
+                                .collect()
+                        })
+                        .collect()
+                }
+            };
+
+            let basis = match values[id][i]["basis"].as_str() {
+                Some("bezier") | None => Basis::Bezier,
+                Some("bspline") => Basis::BSpline,
+                Some(basis) => {
+                    eprintln!("ERROR: Unknown curve basis {}! Using bezier...", basis);
+                    Basis::Bezier
+                }
+            };
+            let shape = match values[id][i]["shape"].as_str() {
+                Some("ribbon") | None => CurveShape::Ribbon,
+                Some("cylinder") => CurveShape::Cylinder,
+                Some(shape) => {
+                    eprintln!("ERROR: Unknown curve shape {}! Using ribbon...", shape);
+                    CurveShape::Ribbon
+                }
+            };
+
+            let radius = match json::get_f64_or_rand(&values[id][i]["radius"]) {
+                Some(r) => r,
+                _ => {
+                    eprintln!("ERROR: Can't get radius of curves {}! Skipping...", i);
+                    continue;
+                }
+            };
+            let tip_radius = match json::get_f64_or_rand(&values[id][i]["tip_radius"]) {
+                Some(r) => r,
+                _ => radius,
+            };
+
+            let position = match json::get_vec3_or_rand(&values[id][i]["position"]) {
+                Some(position) => position,
+                None => Vec3::zero(),
+            };
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of curves {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of curves {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            if verbose {
+                println!("Loaded {} strands.", strands.len());
+            }
+            list.push(Translate::translate(
+                Rotate::rotate(
+                    Curves::create(&strands, basis, shape, (radius, tip_radius), material),
+                    rotation,
+                ),
+                position,
+            ));
+        }
+    }
+
+    list
+}
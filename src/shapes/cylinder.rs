@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::{self, Blank, Material};
+use crate::shapes::{disk::Disk, heterogeneous_medium};
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    hitable_list::HitableList,
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// A cylinder standing on the y axis, centered on the origin. With different radii at the bottom
+// and the top it's a frustum, and with a top radius of 0 a cone.
+pub struct Cylinder {
+    object: HitableList,
+}
+
+impl Cylinder {
+    pub fn create(
+        radii: (f64, f64),
+        height: f64,
+        capped: bool,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Hitable + Sync> {
+        let mut components: Vec<Box<Hitable + Sync>> = Vec::new();
+        let half = height / 2.0;
+
+        components.push(Box::new(Side {
+            radii,
+            height,
+            material: material.clone(),
+        }));
+        if capped {
+            if radii.0 > 0.0 {
+                components.push(Disk::create(
+                    Vec3::new(0.0, -half, 0.0),
+                    Vec3::new(0.0, -1.0, 0.0),
+                    radii.0,
+                    0.0,
+                    material.clone(),
+                ));
+            }
+            if radii.1 > 0.0 {
+                components.push(Disk::create(
+                    Vec3::new(0.0, half, 0.0),
+                    Vec3::new(0.0, 1.0, 0.0),
+                    radii.1,
+                    0.0,
+                    material,
+                ));
+            }
+        }
+
+        Box::new(Cylinder {
+            object: HitableList::new(components),
+        })
+    }
+}
+
+impl Hitable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        self.object.hit(r, t_min, t_max, rec)
+    }
+}
+
+// The curved part, without the caps.
+struct Side {
+    // At the bottom and the top.
+    radii: (f64, f64),
+    height: f64,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl Side {
+    // How much wider it gets going up, and how wide it is halfway up.
+    fn slope(&self) -> (f64, f64) {
+        (
+            (self.radii.1 - self.radii.0) / self.height,
+            (self.radii.0 + self.radii.1) / 2.0,
+        )
+    }
+}
+
+impl Hitable for Side {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (k, middle) = self.slope();
+        let (o, d) = (r.origin, r.direction);
+
+        // x² + z² = (middle + k y)² along the ray.
+        let radius_at_origin = middle + k * o.y;
+        let a = d.x * d.x + d.z * d.z - k * k * d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.z * d.z - k * d.y * radius_at_origin);
+        let c = o.x * o.x + o.z * o.z - radius_at_origin * radius_at_origin;
+
+        let roots = if a.abs() < 1.0e-12 {
+            // Parallel to the slanted side of a cone, so it only crosses it once.
+            if b.abs() < 1.0e-12 {
+                return false;
+            }
+            [-c / b, f64::INFINITY]
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return false;
+            }
+            let sqrt = discriminant.sqrt();
+            let (t0, t1) = ((-b - sqrt) / (2.0 * a), (-b + sqrt) / (2.0 * a));
+            [t0.min(t1), t0.max(t1)]
+        };
+
+        let half = self.height / 2.0;
+        for t in roots.iter() {
+            let t = *t;
+            if t <= t_min || t >= t_max {
+                continue;
+            }
+            let p = r.point_at_parameter(t);
+            let radius = middle + k * p.y;
+            // The other half of the double cone doesn't count.
+            if p.y < -half || p.y > half || radius <= 0.0 {
+                continue;
+            }
+
+            let outwards = Vec3::new(p.x, 0.0, p.z) / radius;
+            let (u, dpdu) = math::get_revolution_u(&p);
+            rec.t = t;
+            rec.p = p;
+            rec.local = p;
+            rec.set_normal(r, math::unit_vector(&(outwards - Vec3::new(0.0, k, 0.0))));
+            rec.u = u;
+            rec.v = (p.y + half) / self.height;
+            rec.dpdu = dpdu;
+            rec.dpdv = outwards * (self.radii.1 - self.radii.0) + Vec3::new(0.0, self.height, 0.0);
+            rec.material = self.material.clone();
+            return true;
+        }
+        false
+    }
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list = load_block_from_json(values, "cylinders", verbose);
+    list.append(&mut load_block_from_json(values, "cones", verbose));
+    list
+}
+
+// Cylinders and cones only differ in what the top radius defaults to.
+fn load_block_from_json(values: &Value, id: &str, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let name = if id == "cones" { "cone" } else { "cylinder" };
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
+
+            let position = json::get_vec3_or_rand(&values[id][i]["position"]);
+            let radius = json::get_f64_or_rand(&values[id][i]["radius"]);
+            let height = json::get_f64_or_rand(&values[id][i]["height"]);
+            let (position, radius, height) = match (position, radius, height) {
+                (Some(position), Some(radius), Some(height)) if height > 0.0 => {
+                    (position, radius, height)
+                }
+                (_, _, _) => {
+                    eprintln!(
+                        "ERROR: Can't get position, radius and height of {} {}! Skipping...",
+                        name, i
+                    );
+                    continue;
+                }
+            };
+            let top_radius = match json::get_f64_or_rand(&values[id][i]["top_radius"]) {
+                Some(r) => r,
+                _ if id == "cones" => 0.0,
+                _ => radius,
+            };
+            let capped = match values[id][i]["capped"].as_bool() {
+                Some(capped) => capped,
+                _ => true,
+            };
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of {} {}! Defaulting to (0,0,0)...",
+                            name, i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of {} {}! Skipping...", name, i);
+                    continue;
+                }
+            };
+
+            let radii = (radius, top_radius);
+            let object = match density {
+                Some(density) => density.create_medium(
+                    Cylinder::create(radii, height, capped, Blank::create()),
+                    material,
+                ),
+                None => Cylinder::create(radii, height, capped, material),
+            };
+            list.push(Translate::translate(
+                Rotate::rotate(object, rotation),
+                position,
+            ));
+        }
+    }
+
+    list
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::{self, Material};
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// A flat disk facing `normal`, with a hole of `inner_radius` in the middle to make it an annulus.
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: f64,
+    inner_radius: f64,
+    // Where u starts going around the disk, and a quarter turn after it.
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl Disk {
+    pub fn create(
+        center: Vec3,
+        normal: Vec3,
+        radius: f64,
+        inner_radius: f64,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Disk> {
+        let normal = math::unit_vector(&normal);
+        let (tangent, _) = math::orthonormal_basis(&normal);
+        Box::new(Disk {
+            center,
+            normal,
+            radius,
+            inner_radius,
+            tangent,
+            bitangent: math::cross(&normal, &tangent),
+            material,
+        })
+    }
+}
+
+impl Hitable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let denominator = math::dot(&self.normal, &r.direction);
+        if denominator.abs() < 1.0e-9 {
+            return false; // The ray is parallel to the disk
+        }
+        let t = math::dot(&self.normal, &(self.center - r.origin)) / denominator;
+        if t < t_min || t > t_max {
+            return false;
+        }
+
+        let p = r.point_at_parameter(t);
+        let d = p - self.center;
+        let distance = d.length();
+        if distance > self.radius || distance < self.inner_radius {
+            return false;
+        }
+
+        // u goes around the disk and v out from the inner edge to the outer one.
+        let pi = std::f64::consts::PI;
+        let phi = math::dot(&d, &self.bitangent).atan2(math::dot(&d, &self.tangent));
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let width = self.radius - self.inner_radius;
+
+        rec.t = t;
+        rec.p = p;
+        rec.local = p;
+        rec.u = phi.rem_euclid(2.0 * pi) / (2.0 * pi);
+        rec.v = if width > 0.0 {
+            (distance - self.inner_radius) / width
+        } else {
+            0.0
+        };
+        rec.set_normal(r, self.normal);
+        rec.dpdu = (self.bitangent * cos_phi - self.tangent * sin_phi) * (2.0 * pi * distance);
+        rec.dpdv = (self.tangent * cos_phi + self.bitangent * sin_phi) * width;
+        rec.material = self.material.clone();
+        true
+    }
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "disks";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            let position = json::get_vec3_or_rand(&values[id][i]["position"]);
+            let radius = json::get_f64_or_rand(&values[id][i]["radius"]);
+            let (position, radius) = match (position, radius) {
+                (Some(position), Some(radius)) => (position, radius),
+                (_, _) => {
+                    eprintln!(
+                        "ERROR: Can't get position and radius of disk {}! Skipping...",
+                        i
+                    );
+                    continue;
+                }
+            };
+            let inner_radius = match json::get_f64_or_rand(&values[id][i]["inner_radius"]) {
+                Some(r) => r,
+                _ => 0.0,
+            };
+
+            let normal = match json::get_vec3_or_rand(&values[id][i]["normal"]) {
+                Some(normal) if normal.squared_length() > 0.0 => normal,
+                _ => {
+                    eprintln!("ERROR: Can't get normal of disk {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of disk {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of disk {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            list.push(Translate::translate(
+                Rotate::rotate(
+                    Disk::create(Vec3::zero(), normal, radius, inner_radius, material),
+                    rotation,
+                ),
+                position,
+            ));
+        }
+    }
+
+    list
+}
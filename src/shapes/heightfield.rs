@@ -0,0 +1,439 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::{self, Material};
+use crate::textures;
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    bounding_box::BoundingBox,
+    hdr_image,
+    hitable::{HitRecord, Hitable},
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// Heights between 0 and 1, one per pixel of a grayscale image, rows from the top down.
+pub struct HeightMap {
+    pub width: usize,
+    pub depth: usize,
+    pub heights: Vec<f64>,
+}
+
+impl HeightMap {
+    // `.hdr` and `.exr` files are read as they are, anything else as 8-bit gray levels without
+    // undoing any gamma, since they're heights and not colors.
+    pub fn load(filename: &str) -> Result<HeightMap> {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        let map = match extension.as_deref() {
+            Some("hdr") | Some("exr") => {
+                let image = hdr_image::load(filename)?;
+                HeightMap {
+                    width: image.width,
+                    depth: image.height,
+                    heights: image.pixels.iter().map(textures::brightness).collect(),
+                }
+            }
+            _ => {
+                let image = image::open(filename)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?
+                    .to_luma();
+                let (width, depth) = image.dimensions();
+                HeightMap {
+                    width: width as usize,
+                    depth: depth as usize,
+                    heights: image.pixels().map(|p| f64::from(p[0]) / 255.0).collect(),
+                }
+            }
+        };
+
+        if map.width < 2 || map.depth < 2 || map.heights.len() < map.width * map.depth {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "heightmap needs at least 2x2 pixels",
+            ));
+        }
+        Ok(map)
+    }
+}
+
+// A terrain made of a grid of triangles, one vertex per pixel of a heightmap, centered on the
+// origin from (-x/2, 0, -z/2) to (x/2, y, z/2) where `size` is (x, y, z). Rays walk down a quadtree
+// of the lowest and highest points in each block of cells, so only the cells near the ray get
+// tested.
+pub struct Heightfield {
+    width: usize,
+    depth: usize,
+    size: Vec3,
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+    // (lowest, highest) of each block, from single cells up to one block covering everything.
+    levels: Vec<Level>,
+    material: Arc<Material + Sync + Send>,
+}
+
+struct Level {
+    width: usize,
+    depth: usize,
+    bounds: Vec<(f64, f64)>,
+}
+
+impl Heightfield {
+    pub fn create(
+        map: &HeightMap,
+        size: Vec3,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Heightfield> {
+        let (width, depth) = (map.width, map.depth);
+        let heights: Vec<f64> = map.heights[..width * depth]
+            .iter()
+            .map(|h| h * size.y)
+            .collect();
+
+        // Smooth normals from the slope between the neighbouring vertices.
+        let dx = size.x / (width - 1) as f64;
+        let dz = size.z / (depth - 1) as f64;
+        let mut normals = Vec::with_capacity(width * depth);
+        for j in 0..depth {
+            for i in 0..width {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(width - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(depth - 1));
+                let slope_x =
+                    (heights[j * width + i1] - heights[j * width + i0]) / ((i1 - i0) as f64 * dx);
+                let slope_z =
+                    (heights[j1 * width + i] - heights[j0 * width + i]) / ((j1 - j0) as f64 * dz);
+                normals.push(math::unit_vector(&Vec3::new(-slope_x, 1.0, -slope_z)));
+            }
+        }
+
+        let mut levels = Vec::new();
+        let mut bounds = Vec::with_capacity((width - 1) * (depth - 1));
+        for j in 0..depth - 1 {
+            for i in 0..width - 1 {
+                let corners = [
+                    heights[j * width + i],
+                    heights[j * width + i + 1],
+                    heights[(j + 1) * width + i],
+                    heights[(j + 1) * width + i + 1],
+                ];
+                bounds.push((
+                    corners.iter().cloned().fold(f64::INFINITY, f64::min),
+                    corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+                ));
+            }
+        }
+        levels.push(Level {
+            width: width - 1,
+            depth: depth - 1,
+            bounds,
+        });
+        loop {
+            let below = &levels[levels.len() - 1];
+            if below.width == 1 && below.depth == 1 {
+                break;
+            }
+            let (w, d) = (below.width.div_ceil(2), below.depth.div_ceil(2));
+            let mut bounds = Vec::with_capacity(w * d);
+            for j in 0..d {
+                for i in 0..w {
+                    let mut block = (f64::INFINITY, f64::NEG_INFINITY);
+                    for (ci, cj) in children(i, j, below.width, below.depth) {
+                        let (lo, hi) = below.bounds[cj * below.width + ci];
+                        block = (block.0.min(lo), block.1.max(hi));
+                    }
+                    bounds.push(block);
+                }
+            }
+            levels.push(Level {
+                width: w,
+                depth: d,
+                bounds,
+            });
+        }
+
+        Box::new(Heightfield {
+            width,
+            depth,
+            size,
+            heights,
+            normals,
+            levels,
+            material,
+        })
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        Vec3::new(
+            self.size.x * (i as f64 / (self.width - 1) as f64 - 0.5),
+            self.heights[j * self.width + i],
+            self.size.z * (j as f64 / (self.depth - 1) as f64 - 0.5),
+        )
+    }
+
+    fn block_box(&self, level: usize, i: usize, j: usize) -> BoundingBox {
+        let cells = 1 << level;
+        let (i0, i1) = (i * cells, ((i + 1) * cells).min(self.width - 1));
+        let (j0, j1) = (j * cells, ((j + 1) * cells).min(self.depth - 1));
+        let (lo, hi) = self.levels[level].bounds[j * self.levels[level].width + i];
+        // Padded a little so flat blocks still have some thickness to hit.
+        let pad = 1.0e-6 * (1.0 + self.size.y.abs());
+        let min = self.vertex(i0, j0);
+        let max = self.vertex(i1, j1);
+        BoundingBox::new(
+            Vec3::new(min.x, lo - pad, min.z),
+            Vec3::new(max.x, hi + pad, max.z),
+        )
+    }
+
+    // Finds the closest hit in a block, nearest children first, shrinking `t_max` as it goes.
+    // Returns the cell, which of its two triangles and the barycentrics of the hit.
+    fn hit_block(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: &mut f64,
+        level: usize,
+        i: usize,
+        j: usize,
+    ) -> Option<(usize, usize, usize, f64, f64)> {
+        if level == 0 {
+            return self.hit_cell(r, t_min, t_max, i, j);
+        }
+
+        let below = &self.levels[level - 1];
+        let mut order: Vec<(f64, usize, usize)> = children(i, j, below.width, below.depth)
+            .filter_map(|(ci, cj)| {
+                self.block_box(level - 1, ci, cj)
+                    .hit(r, t_min, *t_max)
+                    .map(|(t0, _)| (t0, ci, cj))
+            })
+            .collect();
+        order.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut closest = None;
+        for (t0, ci, cj) in order {
+            if t0 > *t_max {
+                break;
+            }
+            if let Some(hit) = self.hit_block(r, t_min, t_max, level - 1, ci, cj) {
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+
+    fn hit_cell(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: &mut f64,
+        i: usize,
+        j: usize,
+    ) -> Option<(usize, usize, usize, f64, f64)> {
+        // Each cell is split along the diagonal from (i, j) to (i + 1, j + 1).
+        let p00 = self.vertex(i, j);
+        let p10 = self.vertex(i + 1, j);
+        let p01 = self.vertex(i, j + 1);
+        let p11 = self.vertex(i + 1, j + 1);
+
+        let mut closest = None;
+        for (triangle, (b, c)) in [(p10, p11), (p11, p01)].iter().enumerate() {
+            if let Some((t, u, v)) = intersect(r, &p00, b, c) {
+                if t > t_min && t < *t_max {
+                    *t_max = t;
+                    closest = Some((i, j, triangle, u, v));
+                }
+            }
+        }
+        closest
+    }
+}
+
+impl Hitable for Heightfield {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let top = self.levels.len() - 1;
+        if self.block_box(top, 0, 0).hit(r, t_min, t_max).is_none() {
+            return false;
+        }
+
+        let mut t = t_max;
+        let (i, j, triangle, b1, b2) = match self.hit_block(r, t_min, &mut t, top, 0, 0) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        let corner = |di: usize, dj: usize| self.normals[(j + dj) * self.width + i + di];
+        let normal = if triangle == 0 {
+            corner(0, 0) * (1.0 - b1 - b2) + corner(1, 0) * b1 + corner(1, 1) * b2
+        } else {
+            corner(0, 0) * (1.0 - b1 - b2) + corner(1, 1) * b1 + corner(0, 1) * b2
+        };
+        let normal = math::unit_vector(&normal);
+        let corners = if triangle == 0 {
+            [
+                self.vertex(i, j),
+                self.vertex(i + 1, j),
+                self.vertex(i + 1, j + 1),
+            ]
+        } else {
+            [
+                self.vertex(i, j),
+                self.vertex(i + 1, j + 1),
+                self.vertex(i, j + 1),
+            ]
+        };
+        // Both triangles of a cell are wound so that this points up.
+        let flat = math::unit_vector(&math::cross(
+            &(corners[2] - corners[0]),
+            &(corners[1] - corners[0]),
+        ));
+
+        // The image is laid over the top with its first row at -z, the way image textures read
+        // it, and the tangents follow the smooth surface rather than the flat triangle.
+        let p = r.point_at_parameter(t);
+        rec.t = t;
+        rec.p = p;
+        rec.local = p;
+        rec.u = (p.x / self.size.x + 0.5).clamp(0.0, 1.0);
+        rec.v = (p.z / self.size.z + 0.5).clamp(0.0, 1.0);
+        rec.set_normals(r, flat, normal);
+        rec.dpdu = Vec3::new(self.size.x, -normal.x / normal.y * self.size.x, 0.0);
+        rec.dpdv = Vec3::new(0.0, -normal.z / normal.y * self.size.z, self.size.z);
+        rec.material = self.material.clone();
+        true
+    }
+}
+
+// The (up to four) blocks one level down that make up block (i, j).
+fn children(
+    i: usize,
+    j: usize,
+    width: usize,
+    depth: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    (0..4)
+        .map(move |k| (2 * i + k % 2, 2 * j + k / 2))
+        .filter(move |&(ci, cj)| ci < width && cj < depth)
+}
+
+// Möller–Trumbore, without any culling. Returns t and the barycentrics of `b` and `c`.
+fn intersect(r: &Ray, a: &Vec3, b: &Vec3, c: &Vec3) -> Option<(f64, f64, f64)> {
+    let edge1 = *b - *a;
+    let edge2 = *c - *a;
+    let h = math::cross(&r.direction, &edge2);
+    let det = math::dot(&edge1, &h);
+    if det.abs() < 1.0e-12 {
+        return None;
+    }
+    let f = 1.0 / det;
+    let s = r.origin - *a;
+    let u = f * math::dot(&s, &h);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = math::cross(&s, &edge1);
+    let v = f * math::dot(&r.direction, &q);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some((f * math::dot(&edge2, &q), u, v))
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "heightfields";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        let filename = match values[id][i]["filename"].as_str() {
+            Some(filename) => filename,
+            None => {
+                eprintln!(
+                    "ERROR: Can't get filename of heightfield {}! Skipping...",
+                    i
+                );
+                continue;
+            }
+        };
+        let map = match HeightMap::load(filename) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!(
+                    "ERROR: Can't load heightmap {} of heightfield {}: {}! Skipping...",
+                    filename, i, e
+                );
+                continue;
+            }
+        };
+        if verbose {
+            println!(
+                "Loaded heightmap {} ({}x{})",
+                filename, map.width, map.depth
+            );
+        }
+
+        for _ in 0..copies {
+            let size = match json::get_vec3_or_rand(&values[id][i]["size"]) {
+                Some(size) if size.x > 0.0 && size.z > 0.0 => size,
+                _ => {
+                    eprintln!("ERROR: Can't get size of heightfield {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let position = match json::get_vec3_or_rand(&values[id][i]["position"]) {
+                Some(position) => position,
+                None => Vec3::zero(),
+            };
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of heightfield {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!(
+                        "ERROR: Can't get material of heightfield {}! Skipping...",
+                        i
+                    );
+                    continue;
+                }
+            };
+
+            list.push(Translate::translate(
+                Rotate::rotate(Heightfield::create(&map, size, material), rotation),
+                position,
+            ));
+        }
+    }
+
+    list
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use serde_json::Value;
+
+use crate::materials::Material;
+use crate::shapes::constant_medium::ConstantMedium;
+use crate::textures::{self, Texture, TextureType};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// A volume whose density changes from point to point, e.g. smoke or clouds. The density is read
+// from a texture (averaging its color) in the boundary's local space and scaled by
+// `max_density`, so textures are expected to stay within [0, 1].
+pub struct HeterogeneousMedium {
+    max_density: f64,
+    density: Arc<Texture + Sync + Send>,
+    object: Box<Hitable + Sync>,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl HeterogeneousMedium {
+    pub fn create(
+        max_density: f64,
+        density: Arc<Texture + Sync + Send>,
+        object: Box<Hitable + Sync>,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<HeterogeneousMedium> {
+        Box::new(HeterogeneousMedium {
+            max_density,
+            density,
+            object,
+            material,
+        })
+    }
+
+    fn density_at(&self, p: Vec3) -> f64 {
+        let value = self.density.value(0.0, 0.0, p);
+        self.max_density * textures::brightness(&value).clamp(0.0, 1.0)
+    }
+}
+
+impl Hitable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if self.max_density <= 0.0 {
+            return false;
+        }
+
+        let (mut rec1, mut rec2) = (HitRecord::new(), HitRecord::new());
+        if !(self.object.hit(r, std::f64::MIN, std::f64::MAX, &mut rec1)
+            && self
+                .object
+                .hit(r, rec1.t + 0.0001, std::f64::MAX, &mut rec2))
+        {
+            return false;
+        }
+
+        let t_enter = rec1.t.max(t_min).max(0.0);
+        let t_exit = rec2.t.min(t_max);
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        // Delta tracking: step through the volume as if it had the maximum density everywhere,
+        // then accept each tentative collision with probability density / max_density. The
+        // rejected ones are "null" collisions that leave the ray unchanged.
+        let mut rng = rand::thread_rng();
+        let length = r.direction.length();
+        let mut t = t_enter;
+        loop {
+            t -= (1.0 - rng.gen::<f64>()).ln() / (self.max_density * length);
+            if t >= t_exit {
+                return false;
+            }
+            let p = r.point_at_parameter(t);
+            if rng.gen::<f64>() * self.max_density < self.density_at(p) {
+                rec.t = t;
+                rec.p = p;
+                rec.local = p;
+                rec.set_normal(r, Vec3::new(1.0, 0.0, 0.0)); // arbitrary
+                rec.material = self.material.clone();
+                return true;
+            }
+        }
+    }
+}
+
+// What goes in an object's `density` key.
+pub enum Density {
+    Constant(f64),
+    Varying(f64, Arc<Texture + Sync + Send>),
+}
+
+impl Density {
+    pub fn create_medium(
+        self,
+        boundary: Box<Hitable + Sync>,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Hitable + Sync> {
+        match self {
+            Density::Constant(density) => ConstantMedium::create(density, boundary, material),
+            Density::Varying(max_density, texture) => {
+                HeterogeneousMedium::create(max_density, texture, boundary, material)
+            }
+        }
+    }
+}
+
+// `density` is either a plain number, or a texture object with an additional `density` key for
+// the density where the texture is white.
+pub fn load_density_from_json(values: &Value) -> Option<Density> {
+    if !values.is_object() {
+        return json::get_f64_or_rand(values).map(Density::Constant);
+    }
+
+    let max_density = match json::get_f64_or_rand(&values["density"]) {
+        Some(d) => d,
+        _ => 1.0,
+    };
+
+    let texture_type = values["type"].as_str().and_then(TextureType::parse);
+    match texture_type {
+        Some(texture_type) => Some(Density::Varying(
+            max_density,
+            textures::create_texture(values, texture_type),
+        )),
+        None => {
+            eprintln!("ERROR: Can't get the density texture type! Using a constant density...");
+            Some(Density::Constant(max_density))
+        }
+    }
+}
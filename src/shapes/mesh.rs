@@ -1,16 +1,15 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::sync::Arc;
 
 use serde_json::Value;
 
-use crate::materials::{
-    blank::Blank, dielectric, diffuse_light, isotropic, lambertian, metal, Material,
-};
+use crate::materials::{self, Blank, Material};
 use crate::shapes::{
-    constant_medium::ConstantMedium,
+    heterogeneous_medium,
     triangle::{Triangle, Vertex},
 };
-use crate::textures::TextureType;
+use crate::textures::{self, Texture, TextureType};
 use crate::transform::{rotate::Rotate, translate::Translate};
 use crate::util::{
     hitable::{HitRecord, Hitable},
@@ -24,11 +23,30 @@ pub struct Mesh {
     triangles: HitableList,
 }
 
+// Moves the vertices along their normals by `amount` times the brightness of `texture`. The
+// texture only gets the position of each vertex, so textures like noise work best. Each level of
+// `subdivisions` splits every triangle into four first so there are enough vertices to move.
+pub struct Displacement {
+    pub texture: Arc<Texture + Sync + Send>,
+    pub amount: f64,
+    pub subdivisions: usize,
+}
+
+// Loop subdivision, which smooths the mesh out rather than just splitting its triangles.
+// Boundaries stay where they are, and so do creases: edges where the faces meet at more than
+// `crease_angle` (in radians), if there is one.
+pub struct Smoothing {
+    pub levels: usize,
+    pub crease_angle: Option<f64>,
+}
+
 impl Mesh {
-    pub fn create(
+    pub fn create_with(
         filename: &str,
         material: Arc<Material + Sync + Send>,
         scale: f64,
+        smoothing: Option<&Smoothing>,
+        displacement: Option<&Displacement>,
     ) -> Box<Hitable + Sync> {
         let mut triangles: Vec<Box<Hitable + Sync>> = Vec::new();
 
@@ -36,41 +54,64 @@ impl Mesh {
         let mut file = OpenOptions::new().read(true).open(filename).unwrap();
         let stl = stl_io::read_stl(&mut file).unwrap();
 
-        let mut vertices: Vec<Vertex> = Vec::new();
-
-        for v in &stl.vertices {
-            vertices.push(Vertex::new(
+        let mut positions: Vec<Vec3> = stl
+            .vertices
+            .iter()
+            .map(|v| {
                 Vec3::new(
                     f64::from(v[0]) * scale,
                     f64::from(v[1]) * scale,
                     f64::from(v[2]) * scale,
-                ),
-                Vec3::zero(),
-            ));
-        }
-
-        for indices in &stl.faces {
-            let (v0, v1, v2) = (
-                vertices[indices.vertices[0] as usize].position,
-                vertices[indices.vertices[1] as usize].position,
-                vertices[indices.vertices[2] as usize].position,
-            );
+                )
+            })
+            .collect();
+        let mut faces: Vec<[usize; 3]> = stl
+            .faces
+            .iter()
+            .map(|f| [f.vertices[0], f.vertices[1], f.vertices[2]])
+            .collect();
 
-            vertices[indices.vertices[0] as usize].normal += math::cross(&(v1 - v0), &(v2 - v0));
-            vertices[indices.vertices[1] as usize].normal += math::cross(&(v1 - v0), &(v2 - v0));
-            vertices[indices.vertices[2] as usize].normal += math::cross(&(v1 - v0), &(v2 - v0));
+        let crease_angle = smoothing.and_then(|smoothing| smoothing.crease_angle);
+        if let Some(smoothing) = smoothing {
+            let mut creases = match crease_angle {
+                Some(angle) => find_creases(&positions, &faces, angle),
+                None => HashSet::new(),
+            };
+            for _ in 0..smoothing.levels {
+                let (p, f, c) = loop_subdivide(&positions, &faces, &creases);
+                positions = p;
+                faces = f;
+                creases = c;
+            }
         }
 
-        for v in vertices.iter_mut() {
-            v.normal = math::unit_vector(&v.normal);
+        if let Some(displacement) = displacement {
+            for _ in 0..displacement.subdivisions {
+                faces = subdivide(&mut positions, &faces);
+            }
+            let normals = vertex_normals(&positions, &faces);
+            for (p, n) in positions.iter_mut().zip(normals.iter()) {
+                let height = textures::brightness(&displacement.texture.value(0.0, 0.0, *p));
+                *p += *n * (height * displacement.amount);
+            }
         }
 
-        for indices in &stl.faces {
+        let normals = match crease_angle {
+            Some(angle) => corner_normals(&positions, &faces, angle),
+            None => {
+                let normals = vertex_normals(&positions, &faces);
+                faces
+                    .iter()
+                    .map(|face| [normals[face[0]], normals[face[1]], normals[face[2]]])
+                    .collect()
+            }
+        };
+        for (face, normals) in faces.iter().zip(normals.iter()) {
             triangles.push(Triangle::create(
                 [
-                    vertices[indices.vertices[0] as usize],
-                    vertices[indices.vertices[1] as usize],
-                    vertices[indices.vertices[2] as usize],
+                    Vertex::new(positions[face[0]], normals[0]),
+                    Vertex::new(positions[face[1]], normals[1]),
+                    Vertex::new(positions[face[2]], normals[2]),
                 ],
                 material.clone(),
             ));
@@ -82,6 +123,206 @@ impl Mesh {
     }
 }
 
+// Smooth normals, averaged from the faces around each vertex weighted by their area.
+fn vertex_normals(positions: &[Vec3], faces: &[[usize; 3]]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::zero(); positions.len()];
+    for face in faces.iter() {
+        let (v0, v1, v2) = (positions[face[0]], positions[face[1]], positions[face[2]]);
+        let normal = math::cross(&(v1 - v0), &(v2 - v0));
+        for &i in face.iter() {
+            normals[i] += normal;
+        }
+    }
+
+    for n in normals.iter_mut() {
+        *n = math::unit_vector(n);
+    }
+    normals
+}
+
+// Like `vertex_normals`, but for each corner of each face, leaving out the faces around the
+// vertex that are across a crease from it so the crease stays sharp.
+fn corner_normals(positions: &[Vec3], faces: &[[usize; 3]], crease_angle: f64) -> Vec<[Vec3; 3]> {
+    let face_normals: Vec<Vec3> = faces
+        .iter()
+        .map(|face| {
+            let (v0, v1, v2) = (positions[face[0]], positions[face[1]], positions[face[2]]);
+            math::cross(&(v1 - v0), &(v2 - v0))
+        })
+        .collect();
+    let mut around: Vec<Vec<usize>> = vec![Vec::new(); positions.len()];
+    for (f, face) in faces.iter().enumerate() {
+        for &i in face.iter() {
+            around[i].push(f);
+        }
+    }
+
+    let cos_crease = crease_angle.cos();
+    faces
+        .iter()
+        .zip(face_normals.iter())
+        .map(|(face, normal)| {
+            let unit = math::unit_vector(normal);
+            let mut corners = [Vec3::zero(); 3];
+            for (corner, &i) in corners.iter_mut().zip(face.iter()) {
+                let sum = around[i]
+                    .iter()
+                    .map(|&f| face_normals[f])
+                    .filter(|n| math::dot(&unit, &math::unit_vector(n)) >= cos_crease)
+                    .fold(Vec3::zero(), |sum, n| sum + n);
+                *corner = math::unit_vector(&sum);
+            }
+            corners
+        })
+        .collect()
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+// The faces on each edge.
+fn edge_faces(faces: &[[usize; 3]]) -> HashMap<(usize, usize), Vec<usize>> {
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (f, &[a, b, c]) in faces.iter().enumerate() {
+        for &(x, y) in [(a, b), (b, c), (c, a)].iter() {
+            edges.entry(edge(x, y)).or_default().push(f);
+        }
+    }
+    edges
+}
+
+// Edges that stay sharp, by the vertices at their ends.
+type Creases = HashSet<(usize, usize)>;
+
+// Edges between two faces that meet at more than `crease_angle`.
+fn find_creases(positions: &[Vec3], faces: &[[usize; 3]], crease_angle: f64) -> Creases {
+    let normal = |f: usize| {
+        let [a, b, c] = faces[f];
+        math::unit_vector(&math::cross(
+            &(positions[b] - positions[a]),
+            &(positions[c] - positions[a]),
+        ))
+    };
+    let cos_crease = crease_angle.cos();
+    edge_faces(faces)
+        .into_iter()
+        .filter(|(_, around)| {
+            around.len() == 2 && math::dot(&normal(around[0]), &normal(around[1])) < cos_crease
+        })
+        .map(|(key, _)| key)
+        .collect()
+}
+
+// One level of Loop subdivision. Every triangle is split into four like `subdivide` does, but
+// the new vertices are pulled towards the triangles around them, and the old ones towards their
+// neighbours. Boundaries, creases and edges with more than two faces use rules that only look
+// along them. Returns the new positions, faces and creases.
+fn loop_subdivide(
+    positions: &[Vec3],
+    faces: &[[usize; 3]],
+    creases: &Creases,
+) -> (Vec<Vec3>, Vec<[usize; 3]>, Creases) {
+    let edges = edge_faces(faces);
+    let sharp =
+        |key: &(usize, usize), around: &Vec<usize>| around.len() != 2 || creases.contains(key);
+
+    let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); positions.len()];
+    let mut sharp_neighbours: Vec<Vec<usize>> = vec![Vec::new(); positions.len()];
+    for (key, around) in edges.iter() {
+        let (a, b) = *key;
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+        if sharp(key, around) {
+            sharp_neighbours[a].push(b);
+            sharp_neighbours[b].push(a);
+        }
+    }
+
+    let pi = std::f64::consts::PI;
+    let mut subdivided: Vec<Vec3> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| match sharp_neighbours[i].len() {
+            // Along a boundary or crease, only the two vertices along it count.
+            2 => {
+                let (a, b) = (sharp_neighbours[i][0], sharp_neighbours[i][1]);
+                *p * 0.75 + (positions[a] + positions[b]) * 0.125
+            }
+            0 | 1 if !neighbours[i].is_empty() => {
+                let n = neighbours[i].len() as f64;
+                let beta = (0.625 - (0.375 + 0.25 * (2.0 * pi / n).cos()).powi(2)) / n;
+                let sum = neighbours[i]
+                    .iter()
+                    .fold(Vec3::zero(), |sum, &j| sum + positions[j]);
+                *p * (1.0 - n * beta) + sum * beta
+            }
+            // Corners where more than two of them meet stay put.
+            _ => *p,
+        })
+        .collect();
+
+    let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut new_creases = HashSet::new();
+    for (key, around) in edges.iter() {
+        let (a, b) = *key;
+        let p = if sharp(key, around) {
+            (positions[a] + positions[b]) * 0.5
+        } else {
+            let opposite = |f: usize| {
+                let face = faces[f];
+                face[0] + face[1] + face[2] - a - b
+            };
+            (positions[a] + positions[b]) * 0.375
+                + (positions[opposite(around[0])] + positions[opposite(around[1])]) * 0.125
+        };
+        subdivided.push(p);
+        let m = subdivided.len() - 1;
+        midpoints.insert(*key, m);
+        if creases.contains(key) {
+            new_creases.insert(edge(a, m));
+            new_creases.insert(edge(m, b));
+        }
+    }
+
+    let mut new_faces = Vec::with_capacity(faces.len() * 4);
+    for &[a, b, c] in faces.iter() {
+        let ab = midpoints[&edge(a, b)];
+        let bc = midpoints[&edge(b, c)];
+        let ca = midpoints[&edge(c, a)];
+        new_faces.push([a, ab, ca]);
+        new_faces.push([ab, b, bc]);
+        new_faces.push([ca, bc, c]);
+        new_faces.push([ab, bc, ca]);
+    }
+    (subdivided, new_faces, new_creases)
+}
+
+// Splits every triangle into four at the middle of its edges. Neighbouring triangles share the
+// new vertices so the mesh doesn't crack when it's displaced.
+fn subdivide(positions: &mut Vec<Vec3>, faces: &[[usize; 3]]) -> Vec<[usize; 3]> {
+    let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut midpoint = |a: usize, b: usize, positions: &mut Vec<Vec3>| -> usize {
+        let key = (a.min(b), a.max(b));
+        *midpoints.entry(key).or_insert_with(|| {
+            positions.push((positions[a] + positions[b]) / 2.0);
+            positions.len() - 1
+        })
+    };
+
+    let mut subdivided = Vec::with_capacity(faces.len() * 4);
+    for &[a, b, c] in faces.iter() {
+        let ab = midpoint(a, b, positions);
+        let bc = midpoint(b, c, positions);
+        let ca = midpoint(c, a, positions);
+        subdivided.push([a, ab, ca]);
+        subdivided.push([ab, b, bc]);
+        subdivided.push([ca, bc, c]);
+        subdivided.push([ab, bc, ca]);
+    }
+    subdivided
+}
+
 impl Hitable for Mesh {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
         self.triangles.hit(r, t_min, t_max, rec)
@@ -106,7 +347,7 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
         } as usize;
 
         for _ in 0..copies {
-            let density = json::get_f64_or_rand(&values[id][i]["density"]);
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
 
             let scale = match json::get_f64_or_rand(&values[id][i]["scale"]) {
                 Some(s) => s,
@@ -145,56 +386,29 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
                 }
             };
 
-            let material: Arc<Material + Sync + Send> = match values[id][i]["material"]["type"]
-                .as_str()
-            {
-                Some("matte/constant") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("matte/checkered") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("matte/image") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("matte/noise") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("metal/constant") => {
-                    metal::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("metal/checkered") => {
-                    metal::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("metal/image") => metal::load_from_json(&values[id][i], TextureType::Image),
-                Some("metal/noise") => metal::load_from_json(&values[id][i], TextureType::Noise),
-                Some("isotropic/constant") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("isotropic/checkered") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("isotropic/image") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("isotropic/noise") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("dielectric") => dielectric::load_from_json(&values[id][i]),
-                Some("light") => diffuse_light::load_from_json(&values[id][i]),
-                _ => {
-                    eprintln!("ERROR: Can't get material of sphere {}! Skipping...", i);
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of mesh {}! Skipping...", i);
                     continue;
                 }
             };
 
+            let smoothing = load_smoothing_from_json(&values[id][i]["subdivision"]);
+            let displacement = load_displacement_from_json(&values[id][i]["displacement"]);
+
             match density {
                 Some(density) => {
                     list.push(Translate::translate(
                         Rotate::rotate(
-                            ConstantMedium::create(
-                                density,
-                                Mesh::create(filename, Blank::create(), scale),
+                            density.create_medium(
+                                Mesh::create_with(
+                                    filename,
+                                    Blank::create(),
+                                    scale,
+                                    smoothing.as_ref(),
+                                    displacement.as_ref(),
+                                ),
                                 material,
                             ),
                             Vec3::new(rx, ry, rz),
@@ -205,7 +419,13 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
                 None => {
                     list.push(Translate::translate(
                         Rotate::rotate(
-                            Mesh::create(filename, material, scale),
+                            Mesh::create_with(
+                                filename,
+                                material,
+                                scale,
+                                smoothing.as_ref(),
+                                displacement.as_ref(),
+                            ),
                             Vec3::new(rx, ry, rz),
                         ),
                         Vec3::new(px, py, pz),
@@ -217,3 +437,138 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
 
     list
 }
+
+// Either just the number of levels, or an object with `levels` and `crease_angle` in degrees.
+fn load_smoothing_from_json(values: &Value) -> Option<Smoothing> {
+    if values.is_null() {
+        return None;
+    }
+
+    let levels = match values.as_u64().or_else(|| values["levels"].as_u64()) {
+        Some(n) => n as usize,
+        _ => 1,
+    };
+    let crease_angle = json::get_f64_or_rand(&values["crease_angle"]).map(f64::to_radians);
+
+    Some(Smoothing {
+        levels,
+        crease_angle,
+    })
+}
+
+fn load_displacement_from_json(values: &Value) -> Option<Displacement> {
+    if values.is_null() {
+        return None;
+    }
+
+    let texture_type = match values["type"].as_str().and_then(TextureType::parse) {
+        Some(texture_type) => texture_type,
+        None => {
+            eprintln!("ERROR: Can't get texture type of displacement! Ignoring...");
+            return None;
+        }
+    };
+    let amount = match json::get_f64_or_rand(&values["amount"]) {
+        Some(a) => a,
+        _ => 0.1,
+    };
+    let subdivisions = match values["subdivisions"].as_u64() {
+        Some(n) => n as usize,
+        _ => 0,
+    };
+
+    Some(Displacement {
+        texture: textures::create_texture(values, texture_type),
+        amount,
+        subdivisions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A unit cube from the origin, two outward facing triangles per side.
+    fn cube() -> (Vec<Vec3>, Vec<[usize; 3]>) {
+        let positions = (0..8)
+            .map(|i| Vec3::new((i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64))
+            .collect();
+        let faces = vec![
+            [0, 2, 3],
+            [0, 3, 1],
+            [4, 5, 7],
+            [4, 7, 6],
+            [0, 1, 5],
+            [0, 5, 4],
+            [2, 6, 7],
+            [2, 7, 3],
+            [0, 4, 6],
+            [0, 6, 2],
+            [1, 3, 7],
+            [1, 7, 5],
+        ];
+        (positions, faces)
+    }
+
+    fn same(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1.0e-12
+    }
+
+    #[test]
+    fn creases_are_the_sharp_edges() {
+        let (positions, faces) = cube();
+        let creases = find_creases(&positions, &faces, 30.0f64.to_radians());
+        assert_eq!(creases.len(), 12);
+        // The diagonals across each side are flat.
+        assert!(!creases.contains(&edge(0, 3)));
+        assert!(creases.contains(&edge(0, 1)));
+        assert!(find_creases(&positions, &faces, 100.0f64.to_radians()).is_empty());
+    }
+
+    #[test]
+    fn creased_cube_keeps_its_shape() {
+        let (positions, faces) = cube();
+        let creases = find_creases(&positions, &faces, 30.0f64.to_radians());
+        let (subdivided, new_faces, new_creases) = loop_subdivide(&positions, &faces, &creases);
+        assert_eq!(new_faces.len(), 48);
+        assert_eq!(new_creases.len(), 24);
+        assert_eq!(subdivided.len(), 8 + 18);
+        // Corners with three creases stay put, and so does everything on the flat sides.
+        for (i, p) in positions.iter().enumerate() {
+            assert!(same(subdivided[i], *p));
+        }
+        for p in subdivided.iter() {
+            let on_side = (0..3).any(|i| p[i].abs() < 1.0e-12 || (p[i] - 1.0).abs() < 1.0e-12);
+            assert!(on_side);
+        }
+    }
+
+    #[test]
+    fn smooth_cube_shrinks_towards_a_sphere() {
+        let (positions, faces) = cube();
+        let (subdivided, _, new_creases) = loop_subdivide(&positions, &faces, &HashSet::new());
+        assert!(new_creases.is_empty());
+        let center = Vec3::new(0.5, 0.5, 0.5);
+        let corner = (positions[0] - center).length();
+        for p in subdivided.iter() {
+            assert!((*p - center).length() < corner);
+        }
+    }
+
+    #[test]
+    fn boundaries_only_look_along_the_edge() {
+        let positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let (subdivided, new_faces, _) = loop_subdivide(&positions, &[[0, 1, 2]], &HashSet::new());
+        assert_eq!(new_faces.len(), 4);
+        assert!(same(subdivided[0], Vec3::new(0.125, 0.125, 0.0)));
+        assert!(same(subdivided[1], Vec3::new(0.75, 0.125, 0.0)));
+        // Boundary edges are split in the middle.
+        let middles: Vec<Vec3> = subdivided[3..].to_vec();
+        assert!(middles.iter().any(|p| same(*p, Vec3::new(0.5, 0.0, 0.0))));
+        assert!(middles.iter().any(|p| same(*p, Vec3::new(0.5, 0.5, 0.0))));
+    }
+}
@@ -2,11 +2,8 @@ use std::sync::Arc;
 
 use serde_json::Value;
 
-use crate::materials::{
-    blank::Blank, dielectric, diffuse_light, isotropic, lambertian, metal, Material,
-};
-use crate::shapes::constant_medium::ConstantMedium;
-use crate::textures::TextureType;
+use crate::materials::{self, Blank, Material};
+use crate::shapes::heterogeneous_medium;
 use crate::transform::translate::Translate;
 use crate::util::{
     hitable::{HitRecord, Hitable},
@@ -60,8 +57,13 @@ impl Hitable for MovingSphere {
             if temp < t_max && temp > t_min {
                 rec.t = temp;
                 rec.p = r.point_at_parameter(rec.t);
-                rec.normal = (rec.p - self.get_center(r.time)) / self.radius;
-                math::get_sphere_uv(&rec.normal, &mut rec.u, &mut rec.v);
+                rec.local = rec.p - self.get_center(r.time);
+                let outward_normal = (rec.p - self.get_center(r.time)) / self.radius;
+                rec.set_normal(r, outward_normal);
+                math::get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+                let (dpdu, dpdv) = math::get_sphere_tangents(&outward_normal, self.radius);
+                rec.dpdu = dpdu;
+                rec.dpdv = dpdv;
                 rec.material = self.material.clone();
                 return true;
             }
@@ -69,8 +71,13 @@ impl Hitable for MovingSphere {
             if temp < t_max && temp > t_min {
                 rec.t = temp;
                 rec.p = r.point_at_parameter(rec.t);
-                rec.normal = (rec.p - self.get_center(r.time)) / self.radius;
-                math::get_sphere_uv(&rec.normal, &mut rec.u, &mut rec.v);
+                rec.local = rec.p - self.get_center(r.time);
+                let outward_normal = (rec.p - self.get_center(r.time)) / self.radius;
+                rec.set_normal(r, outward_normal);
+                math::get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+                let (dpdu, dpdv) = math::get_sphere_tangents(&outward_normal, self.radius);
+                rec.dpdu = dpdu;
+                rec.dpdv = dpdv;
                 rec.material = self.material.clone();
                 return true;
             }
@@ -97,7 +104,7 @@ pub fn load_from_json(values: &Value, _verbose: bool) -> Vec<Box<Hitable + Sync>
         } as usize;
 
         for _ in 0..copies {
-            let density = json::get_f64_or_rand(&values[id][i]["density"]);
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
 
             let p0x = json::get_f64_or_rand(&values[id][i]["positions"][0]["x"]);
             let p0y = json::get_f64_or_rand(&values[id][i]["positions"][0]["y"]);
@@ -142,44 +149,9 @@ pub fn load_from_json(values: &Value, _verbose: bool) -> Vec<Box<Hitable + Sync>
                 }
             };
 
-            let material: Arc<Material + Sync + Send> = match values[id][i]["material"]["type"]
-                .as_str()
-            {
-                Some("matte/constant") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("matte/checkered") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("matte/image") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("matte/noise") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("metal/constant") => {
-                    metal::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("metal/checkered") => {
-                    metal::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("metal/image") => metal::load_from_json(&values[id][i], TextureType::Image),
-                Some("metal/noise") => metal::load_from_json(&values[id][i], TextureType::Noise),
-                Some("isotropic/constant") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("isotropic/checkered") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("isotropic/image") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("isotropic/noise") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("dielectric") => dielectric::load_from_json(&values[id][i]),
-                Some("light") => diffuse_light::load_from_json(&values[id][i]),
-                _ => {
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
                     eprintln!(
                         "ERROR: Can't get material of moving_sphere {}! Skipping...",
                         i
@@ -191,8 +163,7 @@ pub fn load_from_json(values: &Value, _verbose: bool) -> Vec<Box<Hitable + Sync>
             match density {
                 Some(density) => {
                     list.push(Translate::translate(
-                        ConstantMedium::create(
-                            density,
+                        density.create_medium(
                             MovingSphere::create(
                                 Vec3::zero(),
                                 position_difference,
@@ -2,11 +2,9 @@ use std::sync::Arc;
 
 use serde_json::Value;
 
-use crate::materials::{
-    blank::Blank, dielectric, diffuse_light, isotropic, lambertian, metal, Material,
-};
-use crate::shapes::constant_medium::ConstantMedium;
-use crate::textures::TextureType;
+use crate::materials::{self, Blank, Material};
+use crate::shapes::heterogeneous_medium;
+use crate::textures;
 use crate::transform::{rotate::Rotate, translate::Translate};
 use crate::util::{
     hitable::{HitRecord, Hitable},
@@ -18,6 +16,12 @@ use crate::util::{
 pub struct Plane {
     position: Vec3,
     normal: Vec3,
+    // Which way u and v go along the plane, fixed when it's made so textures stay put.
+    tangent: Vec3,
+    bitangent: Vec3,
+    // How far apart whole numbers of u and v are, and what u and v are at `position`.
+    uv_scale: (f64, f64),
+    uv_offset: (f64, f64),
     pub material: Arc<Material + Sync + Send>,
 }
 
@@ -27,9 +31,26 @@ impl Plane {
         normal: Vec3,
         material: Arc<Material + Sync + Send>,
     ) -> Box<Plane> {
+        Plane::create_with(position, normal, None, (1.0, 1.0), (0.0, 0.0), material)
+    }
+
+    pub fn create_with(
+        position: Vec3,
+        normal: Vec3,
+        up: Option<Vec3>,
+        uv_scale: (f64, f64),
+        uv_offset: (f64, f64),
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Plane> {
+        let normal = math::unit_vector(&normal);
+        let (tangent, bitangent) = math::get_plane_tangents(&normal, up);
         Box::new(Plane {
             position,
             normal,
+            tangent,
+            bitangent,
+            uv_scale,
+            uv_offset,
             material,
         })
     }
@@ -38,18 +59,17 @@ impl Plane {
 impl Hitable for Plane {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
         let denominator = math::dot(&r.direction, &-self.normal);
-        if denominator > 1.0e-6 {
+        if denominator.abs() > 1.0e-6 {
             let t = math::dot(&(r.origin - self.position), &self.normal) / denominator;
             if t < t_max && t > t_min {
                 rec.t = t;
                 rec.p = r.point_at_parameter(rec.t);
-                rec.normal = self.normal;
-                math::get_plane_uv(
-                    &math::cross(&(r.origin - self.position), &rec.p),
-                    &rec.normal,
-                    &mut rec.u,
-                    &mut rec.v,
-                );
+                rec.local = rec.p - self.position;
+                rec.set_normal(r, self.normal);
+                rec.u = math::dot(&rec.local, &self.tangent) / self.uv_scale.0 + self.uv_offset.0;
+                rec.v = math::dot(&rec.local, &self.bitangent) / self.uv_scale.1 + self.uv_offset.1;
+                rec.dpdu = self.tangent * self.uv_scale.0;
+                rec.dpdv = self.bitangent * self.uv_scale.1;
                 rec.material = self.material.clone();
                 return true;
             }
@@ -76,7 +96,7 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
         } as usize;
 
         for _ in 0..copies {
-            let density = json::get_f64_or_rand(&values[id][i]["density"]);
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
 
             let px = json::get_f64_or_rand(&values[id][i]["position"]["x"]);
             let py = json::get_f64_or_rand(&values[id][i]["position"]["y"]);
@@ -116,45 +136,18 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
                 }
             };
 
-            let material: Arc<Material + Sync + Send> = match values[id][i]["material"]["type"]
-                .as_str()
-            {
-                Some("matte/constant") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("matte/checkered") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("matte/image") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("matte/noise") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("metal/constant") => {
-                    metal::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("metal/checkered") => {
-                    metal::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("metal/image") => metal::load_from_json(&values[id][i], TextureType::Image),
-                Some("metal/noise") => metal::load_from_json(&values[id][i], TextureType::Noise),
-                Some("isotropic/constant") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("isotropic/checkered") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("isotropic/image") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("isotropic/noise") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("dielectric") => dielectric::load_from_json(&values[id][i]),
-                Some("light") => diffuse_light::load_from_json(&values[id][i]),
-                _ => {
-                    eprintln!("ERRPR: Can't get material of plane {}! Skipping...", i);
+            let up = json::get_vec3_or_rand(&values[id][i]["up"]);
+            let uv_scale = textures::load_uv_from_json(&values[id][i]["uv_scale"], 1.0);
+            let uv_offset = textures::load_uv_from_json(&values[id][i]["uv_offset"], 0.0);
+            if uv_scale.0 == 0.0 || uv_scale.1 == 0.0 {
+                eprintln!("ERROR: UV scale of plane {} can't be 0! Skipping...", i);
+                continue;
+            }
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of plane {}! Skipping...", i);
                     continue;
                 }
             };
@@ -163,8 +156,7 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
                 Some(density) => {
                     list.push(Translate::translate(
                         Rotate::rotate(
-                            ConstantMedium::create(
-                                density,
+                            density.create_medium(
                                 Plane::create(Vec3::zero(), Vec3::new(nx, ny, nz), Blank::create()),
                                 material,
                             ),
@@ -176,7 +168,14 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
                 _ => {
                     list.push(Translate::translate(
                         Rotate::rotate(
-                            Plane::create(Vec3::zero(), Vec3::new(nx, ny, nz), material),
+                            Plane::create_with(
+                                Vec3::zero(),
+                                Vec3::new(nx, ny, nz),
+                                up,
+                                uv_scale,
+                                uv_offset,
+                                material,
+                            ),
                             Vec3::new(rx, ry, rz),
                         ),
                         Vec3::new(px, py, pz),
@@ -188,3 +187,29 @@ pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>>
 
     list
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_from_both_sides() {
+        let plane = Plane::create(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0), Blank::create());
+        let mut rec = HitRecord::new();
+
+        let above = Ray::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(plane.hit(&above, 0.001, 10.0, &mut rec));
+        assert_eq!(rec.t, 2.0);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal.y, 1.0);
+
+        let below = Ray::new(Vec3::new(0.0, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        assert!(plane.hit(&below, 0.001, 10.0, &mut rec));
+        assert_eq!(rec.t, 3.0);
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal.y, -1.0);
+
+        let along = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(!plane.hit(&along, 0.001, 10.0, &mut rec));
+    }
+}
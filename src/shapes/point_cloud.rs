@@ -0,0 +1,399 @@
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use crate::materials::{self, Material};
+use crate::textures::image_texture;
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    bounding_box::BoundingBox,
+    hitable::{HitRecord, Hitable},
+    json, math, point_file,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Splat {
+    Sphere,
+    // A flat disk facing the point's normal, which covers a surface with fewer gaps.
+    Disk,
+}
+
+impl Splat {
+    pub fn parse(name: &str) -> Option<Splat> {
+        match name {
+            "sphere" => Some(Splat::Sphere),
+            "disk" => Some(Splat::Disk),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Point {
+    pub center: Vec3,
+    pub normal: Vec3,
+    // 8-bit sRGB, for `point_color` textures. White when the file has no colors.
+    pub color: [u8; 3],
+}
+
+// Children are the next node and `second`, unless `count` is non-zero, which makes it a leaf of
+// `count` points from `start`.
+struct Node {
+    bounds: BoundingBox,
+    start: usize,
+    count: usize,
+    second: usize,
+    axis: usize,
+}
+
+// Lots of tiny spheres or disks of the same radius, with a BVH of their own so that a ray only
+// gets tested against the few points near it.
+pub struct PointCloud {
+    points: Vec<Point>,
+    nodes: Vec<Node>,
+    radius: f64,
+    splat: Splat,
+    material: Arc<Material + Sync + Send>,
+}
+
+const LEAF_SIZE: usize = 4;
+
+impl PointCloud {
+    pub fn create(
+        points: Vec<Point>,
+        radius: f64,
+        splat: Splat,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<PointCloud> {
+        let mut cloud = PointCloud {
+            points,
+            nodes: Vec::new(),
+            radius,
+            splat,
+            material,
+        };
+        if !cloud.points.is_empty() {
+            let length = cloud.points.len();
+            cloud.nodes.reserve(2 * length / LEAF_SIZE + 1);
+            cloud.build(0, length);
+        }
+        Box::new(cloud)
+    }
+
+    // Splits the points at the median along the longest side of their box until there are few
+    // enough in each leaf. Returns the index of the node.
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for point in &self.points[start..end] {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(point.center[axis]);
+                max[axis] = max[axis].max(point.center[axis]);
+            }
+        }
+        let bounds = BoundingBox::new(min - r, max + r);
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            start,
+            count: end - start,
+            second: 0,
+            axis: 0,
+        });
+        if end - start <= LEAF_SIZE {
+            return index;
+        }
+
+        let size = max - min;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        let middle = (end - start) / 2;
+        self.points[start..end]
+            .select_nth_unstable_by(middle, |a, b| a.center[axis].total_cmp(&b.center[axis]));
+
+        self.build(start, start + middle);
+        let second = self.build(start + middle, end);
+        let node = &mut self.nodes[index];
+        node.count = 0;
+        node.second = second;
+        node.axis = axis;
+        index
+    }
+
+    fn hit_point(&self, point: &Point, r: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        match self.splat {
+            Splat::Sphere => {
+                let oc = r.origin - point.center;
+                let a = math::dot(&r.direction, &r.direction);
+                let b = math::dot(&oc, &r.direction);
+                let c = math::dot(&oc, &oc) - self.radius * self.radius;
+                let discriminant = b * b - a * c;
+                if discriminant <= 0.0 {
+                    return None;
+                }
+                let root = discriminant.sqrt();
+                [(-b - root) / a, (-b + root) / a]
+                    .iter()
+                    .cloned()
+                    .find(|t| *t > t_min && *t < t_max)
+            }
+            Splat::Disk => {
+                let denominator = math::dot(&point.normal, &r.direction);
+                if denominator.abs() < 1.0e-12 {
+                    return None;
+                }
+                let t = math::dot(&point.normal, &(point.center - r.origin)) / denominator;
+                if t <= t_min || t >= t_max {
+                    return None;
+                }
+                let d = r.point_at_parameter(t) - point.center;
+                if d.squared_length() > self.radius * self.radius {
+                    return None;
+                }
+                Some(t)
+            }
+        }
+    }
+}
+
+impl Hitable for PointCloud {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut closest: Option<(f64, usize)> = None;
+        let mut t_max = t_max;
+        let mut stack = [0usize; 64];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let node = &self.nodes[stack[top]];
+            if node.bounds.hit(r, t_min, t_max).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                for index in node.start..node.start + node.count {
+                    if let Some(t) = self.hit_point(&self.points[index], r, t_min, t_max) {
+                        t_max = t;
+                        closest = Some((t, index));
+                    }
+                }
+            } else if top + 2 <= stack.len() {
+                // The child on the side the ray comes from goes on top, to be looked at first.
+                let first = stack[top] + 1;
+                if r.direction[node.axis] < 0.0 {
+                    stack[top] = first;
+                    stack[top + 1] = node.second;
+                } else {
+                    stack[top] = node.second;
+                    stack[top + 1] = first;
+                }
+                top += 2;
+            }
+        }
+
+        let (t, index) = match closest {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let point = &self.points[index];
+
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        rec.local = rec.p - point.center;
+        match self.splat {
+            Splat::Sphere => {
+                let outward_normal = (rec.p - point.center) / self.radius;
+                rec.set_normal(r, outward_normal);
+                math::get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+                let (dpdu, dpdv) = math::get_sphere_tangents(&outward_normal, self.radius);
+                rec.dpdu = dpdu;
+                rec.dpdv = dpdv;
+            }
+            Splat::Disk => {
+                // u and v go across the disk from one side to the other.
+                let (tangent, bitangent) = math::orthonormal_basis(&point.normal);
+                let d = rec.p - point.center;
+                rec.set_normal(r, point.normal);
+                rec.u = 0.5 + math::dot(&d, &tangent) / (2.0 * self.radius);
+                rec.v = 0.5 + math::dot(&d, &bitangent) / (2.0 * self.radius);
+                rec.dpdu = tangent * (2.0 * self.radius);
+                rec.dpdv = bitangent * (2.0 * self.radius);
+            }
+        }
+        let c = |n: u8| image_texture::srgb_to_linear(f64::from(n) / 255.0);
+        rec.color = Vec3::new(c(point.color[0]), c(point.color[1]), c(point.color[2]));
+        rec.material = self.material.clone();
+        true
+    }
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "point_clouds";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        let filename = match values[id][i]["filename"].as_str() {
+            Some(filename) => filename,
+            None => {
+                eprintln!(
+                    "ERROR: Can't get filename of point cloud {}! Skipping...",
+                    i
+                );
+                continue;
+            }
+        };
+        let data = match point_file::load(filename) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!(
+                    "ERROR: Can't load points {} of point cloud {}: {}! Skipping...",
+                    filename, i, e
+                );
+                continue;
+            }
+        };
+        if verbose {
+            let extras = match (data.normals.is_some(), data.colors.is_some()) {
+                (true, true) => " with normals and colors",
+                (true, false) => " with normals",
+                (false, true) => " with colors",
+                (false, false) => "",
+            };
+            println!(
+                "Loaded {} points from {}{}",
+                data.positions.len(),
+                filename,
+                extras
+            );
+        }
+
+        for _ in 0..copies {
+            let radius = match json::get_f64_or_rand(&values[id][i]["radius"]) {
+                Some(radius) if radius > 0.0 => radius,
+                _ => {
+                    eprintln!("ERROR: Can't get radius of point cloud {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let splat = match values[id][i]["shape"].as_str().map(Splat::parse) {
+                Some(Some(Splat::Disk)) if data.normals.is_none() => {
+                    eprintln!(
+                        "ERROR: Point cloud {} has no normals for disks! Using spheres...",
+                        i
+                    );
+                    Splat::Sphere
+                }
+                Some(Some(splat)) => splat,
+                Some(None) => {
+                    eprintln!(
+                        "ERROR: Unknown shape of point cloud {}! Using spheres...",
+                        i
+                    );
+                    Splat::Sphere
+                }
+                None => Splat::Sphere,
+            };
+
+            let scale = match json::get_f64_or_rand(&values[id][i]["scale"]) {
+                Some(scale) => scale,
+                None => 1.0,
+            };
+
+            let position = match json::get_vec3_or_rand(&values[id][i]["position"]) {
+                Some(position) => position,
+                None => Vec3::zero(),
+            };
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of point cloud {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            // Points with colors show them through a `point_color` texture, which the default
+            // material uses when there are any.
+            let material = if values[id][i]["material"].is_null() {
+                let texture = match data.colors {
+                    Some(_) => json!({"type": "matte/point_color"}),
+                    None => {
+                        json!({"type": "matte/constant", "color": {"r": 0.8, "g": 0.8, "b": 0.8}})
+                    }
+                };
+                materials::load_from_json(&json!({ "material": texture }))
+            } else {
+                materials::load_from_json(&values[id][i])
+            };
+            let material = match material {
+                Some(material) => material,
+                None => {
+                    eprintln!(
+                        "ERROR: Can't get material of point cloud {}! Skipping...",
+                        i
+                    );
+                    continue;
+                }
+            };
+
+            let points = data
+                .positions
+                .iter()
+                .enumerate()
+                .map(|(n, position)| Point {
+                    center: *position * scale,
+                    normal: match &data.normals {
+                        Some(normals) if normals[n].squared_length() > 0.0 => {
+                            math::unit_vector(&normals[n])
+                        }
+                        _ => Vec3::new(0.0, 1.0, 0.0),
+                    },
+                    color: match &data.colors {
+                        Some(colors) => colors[n],
+                        None => [255, 255, 255],
+                    },
+                })
+                .collect();
+
+            list.push(Translate::translate(
+                Rotate::rotate(
+                    PointCloud::create(points, radius, splat, material.clone()),
+                    rotation,
+                ),
+                position,
+            ));
+        }
+    }
+
+    list
+}
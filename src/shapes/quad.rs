@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::{self, Material};
+use crate::textures;
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// A parallelogram with a corner at `origin` and sides `u` and `v`, so it's easy to put finite
+// walls and panels anywhere, not just along the axes like `Rect`.
+pub struct Quad {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // Turns a point on the plane into how far along `u` and `v` it is.
+    w: Vec3,
+    // How much of each side one whole number of the texture coordinates covers, and what they
+    // are at `origin`.
+    uv_scale: (f64, f64),
+    uv_offset: (f64, f64),
+    material: Arc<Material + Sync + Send>,
+}
+
+impl Quad {
+    pub fn create(
+        origin: Vec3,
+        u: Vec3,
+        v: Vec3,
+        uv_scale: (f64, f64),
+        uv_offset: (f64, f64),
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Quad> {
+        let n = math::cross(&u, &v);
+        Box::new(Quad {
+            origin,
+            u,
+            v,
+            normal: math::unit_vector(&n),
+            w: n / math::dot(&n, &n),
+            uv_scale,
+            uv_offset,
+            material,
+        })
+    }
+}
+
+impl Hitable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let denominator = math::dot(&self.normal, &r.direction);
+        if denominator.abs() < 1.0e-9 {
+            return false; // The ray is parallel to the quad
+        }
+        let t = math::dot(&self.normal, &(self.origin - r.origin)) / denominator;
+        if t < t_min || t > t_max {
+            return false;
+        }
+
+        let p = r.point_at_parameter(t);
+        let planar = p - self.origin;
+        let alpha = math::dot(&self.w, &math::cross(&planar, &self.v));
+        let beta = math::dot(&self.w, &math::cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        rec.local = p;
+        rec.u = alpha / self.uv_scale.0 + self.uv_offset.0;
+        rec.v = beta / self.uv_scale.1 + self.uv_offset.1;
+        rec.set_normal(r, self.normal);
+        rec.dpdu = self.u * self.uv_scale.0;
+        rec.dpdv = self.v * self.uv_scale.1;
+        rec.material = self.material.clone();
+        true
+    }
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "quads";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            let position = match json::get_vec3_or_rand(&values[id][i]["position"]) {
+                Some(position) => position,
+                None => {
+                    eprintln!("ERROR: Can't get position of quad {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let u = json::get_vec3_or_rand(&values[id][i]["u"]);
+            let v = json::get_vec3_or_rand(&values[id][i]["v"]);
+            let (u, v) = match (u, v) {
+                (Some(u), Some(v)) if math::cross(&u, &v).squared_length() > 0.0 => (u, v),
+                (_, _) => {
+                    eprintln!("ERROR: Can't get sides u and v of quad {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of quad {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let uv_scale = textures::load_uv_from_json(&values[id][i]["uv_scale"], 1.0);
+            let uv_offset = textures::load_uv_from_json(&values[id][i]["uv_offset"], 0.0);
+            if uv_scale.0 == 0.0 || uv_scale.1 == 0.0 {
+                eprintln!("ERROR: UV scale of quad {} can't be 0! Skipping...", i);
+                continue;
+            }
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of quad {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            // `position` is the center, so it rotates around that.
+            list.push(Translate::translate(
+                Rotate::rotate(
+                    Quad::create(-(u + v) / 2.0, u, v, uv_scale, uv_offset, material),
+                    rotation,
+                ),
+                position,
+            ));
+        }
+    }
+
+    list
+}
@@ -0,0 +1,480 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::{self, Blank, Material};
+use crate::shapes::heterogeneous_medium;
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// A shape described by how far any point is from its surface, negative inside, so fractals and
+// blobs that would be a pain to mesh can be rendered directly.
+pub enum Node {
+    Sphere(f64),
+    // Half the size, and how much the edges are rounded off.
+    Cuboid(Vec3, f64),
+    // Lying flat like the `Torus` shape.
+    Torus(f64, f64),
+    // Radius and half the height, standing on the y axis.
+    Cylinder(f64, f64),
+    Capsule(f64, f64),
+    // Power and iterations.
+    Mandelbulb(f64, usize),
+    // Iterations of a sponge filling the cube from -1 to 1.
+    Menger(usize),
+    // The children and how smoothly they're blended where they meet, 0 for sharp edges.
+    Union(Vec<Node>, f64),
+    Intersection(Vec<Node>, f64),
+    // The first child with the others carved out.
+    Difference(Vec<Node>, f64),
+    // How many radians the child turns around the y axis per unit of height.
+    Twist(Box<Node>, f64),
+    // Copies of the child every `period` along each axis that isn't 0.
+    Repeat(Box<Node>, Vec3),
+    // Moved, rotated (in radians, in the same order as `Rotate`) and scaled.
+    Transform(Box<Node>, Vec3, Vec3, f64),
+}
+
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b * (1.0 - h) + a * h - k * h * (1.0 - h)
+}
+
+fn smooth_max(a: f64, b: f64, k: f64) -> f64 {
+    -smooth_min(-a, -b, k)
+}
+
+// The distances to `children` folded together in order.
+fn combine(children: &[Node], p: Vec3, f: impl Fn(f64, f64) -> f64) -> f64 {
+    let mut distances = children.iter().map(|child| child.distance(p));
+    let first = distances.next().unwrap_or(f64::INFINITY);
+    distances.fold(first, f)
+}
+
+fn abs(p: Vec3) -> Vec3 {
+    Vec3::new(p.x.abs(), p.y.abs(), p.z.abs())
+}
+
+fn max_component(p: Vec3) -> f64 {
+    p.x.max(p.y).max(p.z)
+}
+
+fn cuboid_distance(p: Vec3, half: Vec3) -> f64 {
+    let q = abs(p) - half;
+    let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0));
+    outside.length() + max_component(q).min(0.0)
+}
+
+// `p` turned back by `angles`, the way `Rotate` turns rays into the space of what it rotates.
+fn unrotate(p: Vec3, angles: Vec3) -> Vec3 {
+    let (sin, cos) = angles.z.sin_cos();
+    let p = Vec3::new(cos * p.x - sin * p.y, sin * p.x + cos * p.y, p.z);
+    let (sin, cos) = angles.y.sin_cos();
+    let p = Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+    let (sin, cos) = angles.x.sin_cos();
+    Vec3::new(p.x, cos * p.y - sin * p.z, sin * p.y + cos * p.z)
+}
+
+impl Node {
+    pub fn distance(&self, p: Vec3) -> f64 {
+        match self {
+            Node::Sphere(radius) => p.length() - radius,
+            Node::Cuboid(half, rounding) => {
+                cuboid_distance(p, *half - Vec3::unit() * *rounding) - rounding
+            }
+            Node::Torus(radius, tube_radius) => {
+                let around = (p.x * p.x + p.z * p.z).sqrt() - radius;
+                (around * around + p.y * p.y).sqrt() - tube_radius
+            }
+            Node::Cylinder(radius, half) => {
+                let (d0, d1) = ((p.x * p.x + p.z * p.z).sqrt() - radius, p.y.abs() - half);
+                d0.max(d1).min(0.0) + (d0.max(0.0).powi(2) + d1.max(0.0).powi(2)).sqrt()
+            }
+            Node::Capsule(radius, half) => {
+                (p - Vec3::new(0.0, p.y.clamp(-half, *half), 0.0)).length() - radius
+            }
+            Node::Mandelbulb(power, iterations) => {
+                let mut z = p;
+                let mut dr = 1.0;
+                let mut r = z.length();
+                for _ in 0..*iterations {
+                    if r > 2.0 || r == 0.0 {
+                        break;
+                    }
+                    let theta = (z.z / r).acos() * power;
+                    let phi = z.y.atan2(z.x) * power;
+                    dr = r.powf(power - 1.0) * power * dr + 1.0;
+                    z = Vec3::new(
+                        theta.sin() * phi.cos(),
+                        phi.sin() * theta.sin(),
+                        theta.cos(),
+                    ) * r.powf(*power)
+                        + p;
+                    r = z.length();
+                }
+                if r == 0.0 {
+                    return 0.0;
+                }
+                0.5 * r.ln() * r / dr
+            }
+            Node::Menger(iterations) => {
+                let mut d = cuboid_distance(p, Vec3::unit());
+                let mut scale = 1.0;
+                for _ in 0..*iterations {
+                    let a = p * scale;
+                    let a = Vec3::new(
+                        a.x.rem_euclid(2.0) - 1.0,
+                        a.y.rem_euclid(2.0) - 1.0,
+                        a.z.rem_euclid(2.0) - 1.0,
+                    );
+                    scale *= 3.0;
+                    let r = abs(Vec3::unit() - abs(a) * 3.0);
+                    let cross = r.x.max(r.y).min(r.y.max(r.z)).min(r.z.max(r.x));
+                    d = d.max((cross - 1.0) / scale);
+                }
+                d
+            }
+            Node::Union(children, k) => combine(children, p, |a, b| smooth_min(a, b, *k)),
+            Node::Intersection(children, k) => combine(children, p, |a, b| smooth_max(a, b, *k)),
+            Node::Difference(children, k) => combine(children, p, |a, b| smooth_max(a, -b, *k)),
+            Node::Twist(child, amount) => {
+                let (sin, cos) = (-amount * p.y).sin_cos();
+                child.distance(Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z))
+            }
+            Node::Repeat(child, period) => {
+                let mut q = p;
+                for i in 0..3 {
+                    if period[i] > 0.0 {
+                        q[i] -= period[i] * (q[i] / period[i]).round();
+                    }
+                }
+                child.distance(q)
+            }
+            Node::Transform(child, offset, angles, scale) => {
+                child.distance(unrotate(p - *offset, *angles) / *scale) * scale
+            }
+        }
+    }
+
+    // How far from the origin the surface can reach, or None if it goes on forever.
+    pub fn radius(&self) -> Option<f64> {
+        match self {
+            Node::Sphere(radius) => Some(*radius),
+            Node::Cuboid(half, _) => Some(half.length()),
+            Node::Torus(radius, tube_radius) => Some(radius + tube_radius),
+            Node::Cylinder(radius, half) => Some((radius * radius + half * half).sqrt()),
+            Node::Capsule(radius, half) => Some(radius + half),
+            Node::Mandelbulb(_, _) => Some(1.2),
+            Node::Menger(_) => Some(3.0_f64.sqrt()),
+            // Smooth blending can bulge out a bit past the children.
+            Node::Union(children, k) => children
+                .iter()
+                .map(Node::radius)
+                .try_fold(0.0, |a: f64, b| b.map(|b| a.max(b)))
+                .map(|r| r + k),
+            Node::Intersection(children, k) => children
+                .iter()
+                .filter_map(Node::radius)
+                .fold(None, |a: Option<f64>, b| Some(a.map_or(b, |a| a.min(b))))
+                .map(|r| r + k),
+            Node::Difference(children, k) => children.first()?.radius().map(|r| r + k),
+            Node::Twist(child, _) => child.radius(),
+            Node::Repeat(_, _) => None,
+            Node::Transform(child, offset, _, scale) => {
+                child.radius().map(|r| offset.length() + r * scale)
+            }
+        }
+    }
+}
+
+pub struct Sdf {
+    node: Node,
+    // Half the size of the box around the origin that the surface is inside of.
+    bounds: Vec3,
+    max_steps: usize,
+    // How much of the distance to step at once, less than 1 for nodes like twists that make the
+    // distance overestimate.
+    step: f64,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl Sdf {
+    pub fn create(
+        node: Node,
+        bounds: Vec3,
+        max_steps: usize,
+        step: f64,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Sdf> {
+        Box::new(Sdf {
+            node,
+            bounds,
+            max_steps,
+            step,
+            material,
+        })
+    }
+
+    // The gradient of the distance, from four samples around `p`.
+    fn normal(&self, p: Vec3, h: f64) -> Vec3 {
+        let offsets = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        let gradient = offsets.iter().fold(Vec3::zero(), |sum, offset| {
+            sum + *offset * self.node.distance(p + *offset * h)
+        });
+        if gradient.squared_length() > 0.0 {
+            math::unit_vector(&gradient)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        }
+    }
+
+    // Where the ray is inside of `bounds`.
+    fn clip(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let (mut near, mut far) = (t_min, t_max);
+        for i in 0..3 {
+            let inverse = 1.0 / r.direction[i];
+            let mut t0 = (-self.bounds[i] - r.origin[i]) * inverse;
+            let mut t1 = (self.bounds[i] - r.origin[i]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            near = near.max(t0);
+            far = far.min(t1);
+            if far <= near {
+                return None;
+            }
+        }
+        Some((near, far))
+    }
+}
+
+impl Hitable for Sdf {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (near, far) = match self.clip(r, t_min, t_max) {
+            Some(span) => span,
+            None => return false,
+        };
+
+        // Sphere tracing: it's always safe to step as far as the closest surface is. Rays that
+        // start on the surface, like reflected ones, have to get away from it before a hit counts.
+        let epsilon = 1.0e-4;
+        let speed = r.direction.length();
+        let mut t = near;
+        let mut left = false;
+        for _ in 0..self.max_steps {
+            if t > far {
+                return false;
+            }
+            let p = r.point_at_parameter(t);
+            let distance = self.node.distance(p).abs();
+            if distance < epsilon {
+                if left {
+                    let normal = self.normal(p, epsilon);
+                    rec.t = t;
+                    rec.p = p;
+                    rec.local = p;
+                    rec.set_normal(r, normal);
+                    math::get_sphere_uv(&math::unit_vector(&p), &mut rec.u, &mut rec.v);
+                    let (dpdu, dpdv) = math::orthonormal_basis(&normal);
+                    rec.dpdu = dpdu;
+                    rec.dpdv = dpdv;
+                    rec.material = self.material.clone();
+                    return true;
+                }
+                t += 2.0 * epsilon / speed;
+            } else {
+                left = true;
+                t += distance * self.step / speed;
+            }
+        }
+        false
+    }
+}
+
+// A node and its children. Any node can be moved, rotated and scaled with `position`,
+// `rotation` and `scale`.
+pub fn load_node_from_json(values: &Value) -> Option<Node> {
+    let number = |key: &str, default: f64| match json::get_f64_or_rand(&values[key]) {
+        Some(x) => x,
+        _ => default,
+    };
+    let children = || -> Option<Vec<Node>> {
+        let length = values["objects"].as_array()?.len();
+        let children: Vec<Node> = (0..length)
+            .filter_map(|i| load_node_from_json(&values["objects"][i]))
+            .collect();
+        if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        }
+    };
+    let child = || load_node_from_json(&values["object"]).map(Box::new);
+
+    let node = match values["type"].as_str()? {
+        "sphere" => Node::Sphere(number("radius", 1.0)),
+        "cuboid" => {
+            let size = match json::get_vec3_or_rand(&values["size"]) {
+                Some(size) => size,
+                None => Vec3::unit() * 2.0,
+            };
+            Node::Cuboid(size / 2.0, number("rounding", 0.0))
+        }
+        "torus" => Node::Torus(number("radius", 1.0), number("tube_radius", 0.25)),
+        "cylinder" => Node::Cylinder(number("radius", 1.0), number("height", 2.0) / 2.0),
+        "capsule" => Node::Capsule(number("radius", 0.5), number("height", 1.0) / 2.0),
+        "mandelbulb" => Node::Mandelbulb(number("power", 8.0), number("iterations", 8.0) as usize),
+        "menger" => Node::Menger(number("iterations", 4.0) as usize),
+        "union" => Node::Union(children()?, number("smoothness", 0.0)),
+        "intersection" => Node::Intersection(children()?, number("smoothness", 0.0)),
+        "difference" => Node::Difference(children()?, number("smoothness", 0.0)),
+        "twist" => Node::Twist(child()?, number("amount", 0.0).to_radians()),
+        "repeat" => {
+            let period = match json::get_vec3_or_rand(&values["period"]) {
+                Some(period) => period,
+                None => Vec3::zero(),
+            };
+            Node::Repeat(child()?, period)
+        }
+        name => {
+            eprintln!("ERROR: Unknown sdf type {}! Skipping...", name);
+            return None;
+        }
+    };
+
+    let position = json::get_vec3_or_rand(&values["position"]);
+    let rotation = json::get_vec3_or_rand(&values["rotation"]);
+    let scale = json::get_f64_or_rand(&values["scale"]);
+    if position.is_none() && rotation.is_none() && scale.is_none() {
+        return Some(node);
+    }
+    let rotation = match rotation {
+        Some(rotation) => rotation,
+        None => Vec3::zero(),
+    };
+    Some(Node::Transform(
+        Box::new(node),
+        match position {
+            Some(position) => position,
+            None => Vec3::zero(),
+        },
+        Vec3::new(
+            rotation.x.to_radians(),
+            rotation.y.to_radians(),
+            rotation.z.to_radians(),
+        ),
+        match scale {
+            Some(scale) if scale > 0.0 => scale,
+            _ => 1.0,
+        },
+    ))
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "sdfs";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
+
+            let position = match json::get_vec3_or_rand(&values[id][i]["position"]) {
+                Some(position) => position,
+                None => {
+                    eprintln!("ERROR: Can't get position of sdf {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let node = match load_node_from_json(&values[id][i]["shape"]) {
+                Some(node) => node,
+                None => {
+                    eprintln!("ERROR: Can't get shape of sdf {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let bounds = match json::get_vec3_or_rand(&values[id][i]["bounds"]) {
+                Some(bounds) => bounds / 2.0,
+                None => match node.radius() {
+                    Some(radius) => Vec3::unit() * radius,
+                    None => {
+                        eprintln!(
+                            "ERROR: Sdf {} goes on forever, it needs bounds! Skipping...",
+                            i
+                        );
+                        continue;
+                    }
+                },
+            };
+            let max_steps = match json::get_f64_or_rand(&values[id][i]["max_steps"]) {
+                Some(n) => n,
+                _ => 256.0,
+            } as usize;
+            let step = match json::get_f64_or_rand(&values[id][i]["step"]) {
+                Some(s) => s,
+                _ => 1.0,
+            };
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of sdf {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of sdf {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let object: Box<Hitable + Sync> = match density {
+                Some(density) => density.create_medium(
+                    Sdf::create(node, bounds, max_steps, step, Blank::create()),
+                    material,
+                ),
+                None => Sdf::create(node, bounds, max_steps, step, material),
+            };
+            list.push(Translate::translate(
+                Rotate::rotate(object, rotation),
+                position,
+            ));
+        }
+    }
+
+    list
+}
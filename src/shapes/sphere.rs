@@ -2,9 +2,8 @@ use std::sync::Arc;
 
 use serde_json::Value;
 
-use crate::materials::{dielectric, diffuse_light, isotropic, lambertian, metal, Blank, Material};
-use crate::shapes::constant_medium::ConstantMedium;
-use crate::textures::TextureType;
+use crate::materials::{self, Blank, Material};
+use crate::shapes::heterogeneous_medium;
 use crate::transform::translate::Translate;
 use crate::util::{
     hitable::{HitRecord, Hitable},
@@ -41,8 +40,13 @@ impl Hitable for Sphere {
             if temp < t_max && temp > t_min {
                 rec.t = temp;
                 rec.p = r.point_at_parameter(rec.t);
-                rec.normal = (rec.p - self.center) / self.radius;
-                math::get_sphere_uv(&rec.normal, &mut rec.u, &mut rec.v);
+                rec.local = rec.p - self.center;
+                let outward_normal = (rec.p - self.center) / self.radius;
+                rec.set_normal(r, outward_normal);
+                math::get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+                let (dpdu, dpdv) = math::get_sphere_tangents(&outward_normal, self.radius);
+                rec.dpdu = dpdu;
+                rec.dpdv = dpdv;
                 rec.material = self.material.clone();
                 return true;
             }
@@ -50,8 +54,13 @@ impl Hitable for Sphere {
             if temp < t_max && temp > t_min {
                 rec.t = temp;
                 rec.p = r.point_at_parameter(rec.t);
-                rec.normal = (rec.p - self.center) / self.radius;
-                math::get_sphere_uv(&rec.normal, &mut rec.u, &mut rec.v);
+                rec.local = rec.p - self.center;
+                let outward_normal = (rec.p - self.center) / self.radius;
+                rec.set_normal(r, outward_normal);
+                math::get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+                let (dpdu, dpdv) = math::get_sphere_tangents(&outward_normal, self.radius);
+                rec.dpdu = dpdu;
+                rec.dpdv = dpdv;
                 rec.material = self.material.clone();
                 return true;
             }
@@ -78,7 +87,7 @@ pub fn load_from_json(values: &Value, _verbose: bool) -> Vec<Box<Hitable + Sync>
         } as usize;
 
         for _ in 0..copies {
-            let density = json::get_f64_or_rand(&values[id][i]["density"]);
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
 
             let px = json::get_f64_or_rand(&values[id][i]["position"]["x"]);
             let py = json::get_f64_or_rand(&values[id][i]["position"]["y"]);
@@ -99,44 +108,9 @@ pub fn load_from_json(values: &Value, _verbose: bool) -> Vec<Box<Hitable + Sync>
                 }
             };
 
-            let material: Arc<Material + Sync + Send> = match values[id][i]["material"]["type"]
-                .as_str()
-            {
-                Some("matte/constant") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("matte/checkered") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("matte/image") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("matte/noise") => {
-                    lambertian::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("metal/constant") => {
-                    metal::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("metal/checkered") => {
-                    metal::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("metal/image") => metal::load_from_json(&values[id][i], TextureType::Image),
-                Some("metal/noise") => metal::load_from_json(&values[id][i], TextureType::Noise),
-                Some("isotropic/constant") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Constant)
-                }
-                Some("isotropic/checkered") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Checkered)
-                }
-                Some("isotropic/image") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Image)
-                }
-                Some("isotropic/noise") => {
-                    isotropic::load_from_json(&values[id][i], TextureType::Noise)
-                }
-                Some("dielectric") => dielectric::load_from_json(&values[id][i]),
-                Some("light") => diffuse_light::load_from_json(&values[id][i]),
-                _ => {
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
                     eprintln!("ERROR: Can't get material of sphere {}! Skipping...", i);
                     continue;
                 }
@@ -145,8 +119,7 @@ pub fn load_from_json(values: &Value, _verbose: bool) -> Vec<Box<Hitable + Sync>
             match density {
                 Some(density) => {
                     list.push(Translate::translate(
-                        ConstantMedium::create(
-                            density,
+                        density.create_medium(
                             Sphere::create(Vec3::zero(), radius, Blank::create()),
                             material,
                         ),
@@ -0,0 +1,336 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::materials::{self, Blank, Material};
+use crate::shapes::heterogeneous_medium;
+use crate::transform::{rotate::Rotate, translate::Translate};
+use crate::util::{
+    hitable::{HitRecord, Hitable},
+    json, math,
+    ray::Ray,
+    vector3::Vec3,
+};
+
+// A ring around the y axis, centered on the origin. `radius` is from the center to the middle of
+// the tube, and `tube_radius` how thick the tube is.
+pub struct Torus {
+    radius: f64,
+    tube_radius: f64,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl Torus {
+    pub fn create(
+        radius: f64,
+        tube_radius: f64,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Torus> {
+        Box::new(Torus {
+            radius,
+            tube_radius,
+            material,
+        })
+    }
+}
+
+impl Hitable for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (big, small) = (self.radius, self.tube_radius);
+
+        // Skip rays that miss the sphere around it, and start the others where they enter that
+        // sphere, since the quartic below loses a lot of precision far away from the torus.
+        let bound = big + small;
+        let a = math::dot(&r.direction, &r.direction);
+        let b = math::dot(&r.origin, &r.direction);
+        let c = math::dot(&r.origin, &r.origin) - bound * bound;
+        let discriminant = b * b - a * c;
+        if discriminant <= 0.0 {
+            return false;
+        }
+        let start = (-b - discriminant.sqrt()) / a;
+        if start > t_max || (-b + discriminant.sqrt()) / a < t_min {
+            return false;
+        }
+        let o = r.point_at_parameter(start);
+        let d = r.direction;
+
+        // (|p|² + R² - r²)² = 4R²(x² + z²) along the ray.
+        let g = a;
+        let h = 2.0 * math::dot(&o, &d);
+        let i = math::dot(&o, &o) + big * big - small * small;
+        let four_big = 4.0 * big * big;
+        let coefficients = [
+            g * g,
+            2.0 * g * h,
+            h * h + 2.0 * g * i - four_big * (d.x * d.x + d.z * d.z),
+            2.0 * h * i - 2.0 * four_big * (o.x * d.x + o.z * d.z),
+            i * i - four_big * (o.x * o.x + o.z * o.z),
+        ];
+
+        let t = solve_quartic(&coefficients)
+            .into_iter()
+            .map(|t| t + start)
+            .filter(|t| *t > t_min && *t < t_max)
+            .fold(f64::INFINITY, f64::min);
+        if t == f64::INFINITY {
+            return false;
+        }
+
+        let p = r.point_at_parameter(t);
+        // Where the middle of the tube is closest to `p`.
+        let around = (p.x * p.x + p.z * p.z).sqrt();
+        let outwards = if around > 0.0 {
+            Vec3::new(p.x, 0.0, p.z) / around
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let normal = math::unit_vector(&(p - outwards * big));
+
+        // u goes around the ring and v around the tube, starting on the inside.
+        let pi = std::f64::consts::PI;
+        let (u, dpdu) = math::get_revolution_u(&p);
+        let theta = normal.y.atan2(math::dot(&normal, &outwards));
+        rec.t = t;
+        rec.p = p;
+        rec.local = p;
+        rec.set_normal(r, normal);
+        rec.u = u;
+        rec.v = (theta + pi) / (2.0 * pi);
+        rec.dpdu = dpdu;
+        rec.dpdv =
+            (Vec3::new(0.0, 1.0, 0.0) * theta.cos() - outwards * theta.sin()) * (2.0 * pi * small);
+        rec.material = self.material.clone();
+        true
+    }
+}
+
+// The real roots of a cubic, from the coefficient of x³ down.
+fn solve_cubic(coefficients: &[f64; 4]) -> Vec<f64> {
+    let [a, b, c, d] = *coefficients;
+    let (b, c, d) = (b / a, c / a, d / a);
+
+    // Substituting x = y - b/3 gets rid of the square term.
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let shift = -b / 3.0;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    if discriminant > 0.0 {
+        let sqrt = discriminant.sqrt();
+        vec![(-q / 2.0 + sqrt).cbrt() + (-q / 2.0 - sqrt).cbrt() + shift]
+    } else if p.abs() < 1.0e-30 {
+        vec![shift]
+    } else {
+        // Three real roots, found with trigonometry.
+        let m = 2.0 * (-p / 3.0).sqrt();
+        let angle = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+        (0..3)
+            .map(|k| m * (angle - 2.0 * std::f64::consts::PI * f64::from(k) / 3.0).cos() + shift)
+            .collect()
+    }
+}
+
+// The real roots of a quartic, from the coefficient of x⁴ down, using Ferrari's method.
+fn solve_quartic(coefficients: &[f64; 5]) -> Vec<f64> {
+    let [a, b, c, d, e] = *coefficients;
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+
+    // Substituting x = y - b/4 gets rid of the cubic term.
+    let p = c - 3.0 * b * b / 8.0;
+    let q = d - b * c / 2.0 + b * b * b / 8.0;
+    let r = e - b * d / 4.0 + b * b * c / 16.0 - 3.0 * b * b * b * b / 256.0;
+    let shift = -b / 4.0;
+
+    let mut roots = Vec::new();
+    let mut quadratic = |b: f64, c: f64| {
+        let discriminant = b * b - 4.0 * c;
+        if discriminant >= 0.0 {
+            let sqrt = discriminant.sqrt();
+            roots.push((-b - sqrt) / 2.0 + shift);
+            roots.push((-b + sqrt) / 2.0 + shift);
+        }
+    };
+
+    if q.abs() < 1.0e-12 {
+        // Quadratic in y².
+        let discriminant = p * p - 4.0 * r;
+        if discriminant >= 0.0 {
+            for z in [
+                (-p - discriminant.sqrt()) / 2.0,
+                (-p + discriminant.sqrt()) / 2.0,
+            ]
+            .iter()
+            {
+                if *z >= 0.0 {
+                    quadratic(0.0, -z);
+                }
+            }
+        }
+    } else {
+        // Any positive root m of the resolvent splits it into two quadratics.
+        let m = solve_cubic(&[1.0, p, p * p / 4.0 - r, -q * q / 8.0])
+            .into_iter()
+            .fold(0.0, f64::max);
+        if m <= 0.0 {
+            return roots;
+        }
+        let s = (2.0 * m).sqrt();
+        quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        quadratic(s, p / 2.0 + m - q / (2.0 * s));
+    }
+
+    // A couple of Newton steps on the original quartic to clean up the rounding.
+    let f = |x: f64| (((x + b) * x + c) * x + d) * x + e;
+    let df = |x: f64| ((4.0 * x + 3.0 * b) * x + 2.0 * c) * x + d;
+    roots
+        .into_iter()
+        .map(|mut x| {
+            for _ in 0..2 {
+                let slope = df(x);
+                if slope != 0.0 {
+                    x -= f(x) / slope;
+                }
+            }
+            x
+        })
+        .collect()
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "tori";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            let density = heterogeneous_medium::load_density_from_json(&values[id][i]["density"]);
+
+            let position = json::get_vec3_or_rand(&values[id][i]["position"]);
+            let radius = json::get_f64_or_rand(&values[id][i]["radius"]);
+            let tube_radius = json::get_f64_or_rand(&values[id][i]["tube_radius"]);
+            let (position, radius, tube_radius) = match (position, radius, tube_radius) {
+                (Some(position), Some(radius), Some(tube_radius)) => {
+                    (position, radius, tube_radius)
+                }
+                (_, _, _) => {
+                    eprintln!(
+                        "ERROR: Can't get position, radius and tube radius of torus {}! Skipping...",
+                        i
+                    );
+                    continue;
+                }
+            };
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of torus {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of torus {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            let object: Box<Hitable + Sync> = match density {
+                Some(density) => density.create_medium(
+                    Torus::create(radius, tube_radius, Blank::create()),
+                    material,
+                ),
+                None => Torus::create(radius, tube_radius, material),
+            };
+            list.push(Translate::translate(
+                Rotate::rotate(object, rotation),
+                position,
+            ));
+        }
+    }
+
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut roots: Vec<f64>) -> Vec<f64> {
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        roots
+    }
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        let roots = sorted(roots);
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (root, expected) in roots.iter().zip(expected.iter()) {
+            assert!((root - expected).abs() < 1.0e-9, "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn cubic_roots() {
+        assert_roots(solve_cubic(&[1.0, -6.0, 11.0, -6.0]), &[1.0, 2.0, 3.0]);
+        assert_roots(solve_cubic(&[2.0, 0.0, 0.0, -2.0]), &[1.0]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            solve_quartic(&[1.0, -10.0, 35.0, -50.0, 24.0]),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // (x² - 1)(x² - 4), with no odd terms.
+        assert_roots(
+            solve_quartic(&[1.0, 0.0, -5.0, 0.0, 4.0]),
+            &[-2.0, -1.0, 1.0, 2.0],
+        );
+        // 2(x - 1)(x + 2)(x² + 1)
+        assert_roots(solve_quartic(&[2.0, 2.0, -2.0, 2.0, -4.0]), &[-2.0, 1.0]);
+        assert_roots(solve_quartic(&[1.0, 0.0, 0.0, 0.0, 1.0]), &[]);
+    }
+
+    #[test]
+    fn hits_the_tube_and_not_the_hole() {
+        let torus = Torus::create(2.0, 0.5, Blank::create());
+        let mut rec = HitRecord::new();
+
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(torus.hit(&r, 0.001, f64::MAX, &mut rec));
+        assert!((rec.t - 2.5).abs() < 1.0e-9);
+        assert!((rec.normal.x + 1.0).abs() < 1.0e-9);
+        // Far enough away for the quartic alone to lose precision.
+        let r = Ray::new(Vec3::new(-1.0e4, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(torus.hit(&r, 0.001, f64::MAX, &mut rec));
+        assert!((rec.p.x + 2.5).abs() < 1.0e-6);
+
+        let r = Ray::new(Vec3::new(2.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(torus.hit(&r, 0.001, f64::MAX, &mut rec));
+        assert!((rec.t - 4.5).abs() < 1.0e-9);
+        assert!(rec.normal.y > 0.999);
+
+        let r = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(!torus.hit(&r, 0.001, f64::MAX, &mut rec));
+    }
+}
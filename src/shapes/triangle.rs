@@ -1,9 +1,12 @@
 use std::sync::Arc;
 
-use crate::materials::Material;
+use serde_json::Value;
+
+use crate::materials::{self, Material};
+use crate::transform::{rotate::Rotate, translate::Translate};
 use crate::util::{
     hitable::{HitRecord, Hitable},
-    math,
+    json, math,
     ray::Ray,
     vector3::Vec3,
 };
@@ -66,9 +69,17 @@ impl Hitable for Triangle {
         if t > t_min + epsilon && t < t_max {
             rec.t = t;
             rec.p = r.point_at_parameter(t);
+            rec.local = rec.p;
             rec.u = u;
             rec.v = v;
-            rec.normal = math::unit_vector(&normal);
+            // The face's own normal follows the order of the corners.
+            rec.set_normals(
+                r,
+                math::unit_vector(&math::cross(&edge1, &edge2)),
+                math::unit_vector(&normal),
+            );
+            rec.dpdu = edge1;
+            rec.dpdv = edge2;
             rec.material = self.material.clone();
             true
         } else {
@@ -77,3 +88,85 @@ impl Hitable for Triangle {
         }
     }
 }
+
+// Single flat triangles, with corners relative to `position`, for when a whole mesh is too much.
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "triangles";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        // Get the parameters
+        let copies = match json::get_f64_or_rand(&values[id][i]["copies"]) {
+            Some(n) => n,
+            _ => 1.0,
+        } as usize;
+
+        for _ in 0..copies {
+            let position = match json::get_vec3_or_rand(&values[id][i]["position"]) {
+                Some(position) => position,
+                None => Vec3::zero(),
+            };
+
+            let a = json::get_vec3_or_rand(&values[id][i]["vertices"][0]);
+            let b = json::get_vec3_or_rand(&values[id][i]["vertices"][1]);
+            let c = json::get_vec3_or_rand(&values[id][i]["vertices"][2]);
+            let (a, b, c) = match (a, b, c) {
+                (Some(a), Some(b), Some(c)) => (a, b, c),
+                (_, _, _) => {
+                    eprintln!("ERROR: Can't get vertices of triangle {}! Skipping...", i);
+                    continue;
+                }
+            };
+            let normal = math::cross(&(b - a), &(c - a));
+            if normal.squared_length() <= 0.0 {
+                eprintln!("ERROR: Triangle {} has no area! Skipping...", i);
+                continue;
+            }
+            let normal = math::unit_vector(&normal);
+
+            let rotation = match json::get_vec3_or_rand(&values[id][i]["rotation"]) {
+                Some(rotation) => rotation,
+                None => {
+                    if verbose {
+                        eprintln!(
+                            "ERROR: Can't get rotation of triangle {}! Defaulting to (0,0,0)...",
+                            i
+                        );
+                    }
+                    Vec3::zero()
+                }
+            };
+
+            let material = match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of triangle {}! Skipping...", i);
+                    continue;
+                }
+            };
+
+            list.push(Translate::translate(
+                Rotate::rotate(
+                    Triangle::create(
+                        [
+                            Vertex::new(a, normal),
+                            Vertex::new(b, normal),
+                            Vertex::new(c, normal),
+                        ],
+                        material,
+                    ),
+                    rotation,
+                ),
+                position,
+            ));
+        }
+    }
+
+    list
+}
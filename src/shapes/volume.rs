@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use serde_json::Value;
+
+use crate::materials::{self, Isotropic, Material};
+use crate::textures::{self, ConstantTexture, Texture, VoxelTexture};
+use crate::util::{
+    bounding_box::BoundingBox,
+    hitable::{HitRecord, Hitable},
+    json,
+    ray::Ray,
+    vector3::Vec3,
+    voxel_file::{self, Format, VoxelData},
+};
+
+// Simulated smoke and fire loaded from voxel files. The grid is placed in world space between
+// `bounds.min` and `bounds.max` and ray marched: the optical depth is summed up in steps of
+// `step` until it reaches an exponentially distributed target, which is where the ray scatters.
+pub struct Volume {
+    bounds: BoundingBox,
+    density: Arc<VoxelTexture>,
+    density_scale: f64,
+    step: f64,
+    material: Arc<Material + Sync + Send>,
+}
+
+impl Volume {
+    pub fn create(
+        bounds: BoundingBox,
+        density: Arc<VoxelTexture>,
+        density_scale: f64,
+        step: f64,
+        material: Arc<Material + Sync + Send>,
+    ) -> Box<Volume> {
+        Box::new(Volume {
+            bounds,
+            density,
+            density_scale,
+            step,
+            material,
+        })
+    }
+
+    fn sigma(&self, p: Vec3) -> f64 {
+        self.density_scale * self.density.lookup(p).max(0.0)
+    }
+}
+
+impl Hitable for Volume {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let (t_enter, t_exit) = match self.bounds.hit(r, t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        let mut rng = rand::thread_rng();
+        let length = r.direction.length();
+        let dt = self.step / length;
+        let target = -(1.0 - rng.gen::<f64>()).ln();
+
+        // Jitter the first step so the steps don't show up as slices.
+        let mut t = t_enter - rng.gen::<f64>() * dt;
+        let mut optical_depth = 0.0;
+        while t < t_exit {
+            let t0 = t.max(t_enter);
+            let t1 = (t + dt).min(t_exit);
+            let sigma = self.sigma(r.point_at_parameter(0.5 * (t0 + t1)));
+            let step_depth = sigma * (t1 - t0) * length;
+            if optical_depth + step_depth >= target {
+                rec.t = t0 + (target - optical_depth) / (sigma * length);
+                rec.p = r.point_at_parameter(rec.t);
+                rec.local = rec.p;
+                rec.set_normal(r, Vec3::new(1.0, 0.0, 0.0)); // arbitrary
+                rec.material = self.material.clone();
+                return true;
+            }
+            optical_depth += step_depth;
+            t += dt;
+        }
+        false
+    }
+}
+
+// Scatters like `phase` and glows with whatever `emission` is at the scattering point. Since a
+// collision only happens with a probability proportional to the density, this ends up being
+// emission per unit density.
+struct EmissiveVolume {
+    phase: Arc<Material + Sync + Send>,
+    emission: Arc<Texture + Sync + Send>,
+}
+
+impl Material for EmissiveVolume {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        self.phase.scatter(r_in, rec, attenuation, scattered)
+    }
+
+    fn emit(&self, _r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        self.emission.sample(rec)
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Option<Vec3> {
+        self.phase.scattering(r_in, rec, direction)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        self.phase.scattering_pdf(r_in, rec, direction)
+    }
+}
+
+// Maps a temperature grid in Kelvin to the color of a black body at that temperature.
+struct BlackbodyTexture {
+    temperature: Arc<VoxelTexture>,
+    intensity: f64,
+}
+
+impl Texture for BlackbodyTexture {
+    fn value(&self, _: f64, _: f64, p: Vec3) -> Vec3 {
+        let kelvin = self.temperature.lookup(p);
+        if kelvin <= 0.0 {
+            return Vec3::zero();
+        }
+        // Stefan-Boltzmann: the emitted power goes up with the fourth power of the temperature.
+        blackbody(kelvin) * self.intensity * (kelvin / 1000.0).powi(4)
+    }
+}
+
+// Scales a scalar grid by a color.
+struct TintedTexture {
+    grid: Arc<VoxelTexture>,
+    color: Vec3,
+}
+
+impl Texture for TintedTexture {
+    fn value(&self, _: f64, _: f64, p: Vec3) -> Vec3 {
+        self.color * self.grid.lookup(p).max(0.0)
+    }
+}
+
+// Approximate linear RGB of a black body, normalized so the brightest channel is 1. Source:
+// http://www.tannerhelland.com/4435/convert-temperature-rgb-algorithm-code/
+pub fn blackbody(kelvin: f64) -> Vec3 {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2)
+    };
+    let g = if t <= 66.0 {
+        99.470_802_586_1 * t.ln() - 161.119_568_166_1
+    } else {
+        288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7
+    };
+    // The approximation is gamma encoded, and the renderer outputs with a gamma of 2.
+    let srgb = |c: f64| (c.clamp(0.0, 255.0) / 255.0).powi(2);
+    Vec3::new(srgb(r), srgb(g), srgb(b))
+}
+
+// Reads a grid from a `.nrrd` file, or a headerless file given a `resolution` and `format`.
+fn load_voxels_from_json(values: &Value) -> Option<VoxelData> {
+    let filename = values["filename"].as_str()?;
+
+    let result = if filename.ends_with(".nrrd") || filename.ends_with(".nhdr") {
+        voxel_file::load_nrrd(filename)
+    } else {
+        let nx = values["resolution"]["x"].as_u64();
+        let ny = values["resolution"]["y"].as_u64();
+        let nz = values["resolution"]["z"].as_u64();
+        let resolution = match (nx, ny, nz) {
+            (Some(x), Some(y), Some(z)) => (x as usize, y as usize, z as usize),
+            (_, _, _) => {
+                eprintln!("ERROR [{}]: Raw voxel files need a resolution!", filename);
+                return None;
+            }
+        };
+        let format = match values["format"].as_str() {
+            Some(name) => match Format::parse(name) {
+                Some(format) => format,
+                None => {
+                    eprintln!("ERROR [{}]: Unknown voxel format {}!", filename, name);
+                    return None;
+                }
+            },
+            None => Format::F32,
+        };
+        voxel_file::load_raw(filename, resolution, format)
+    };
+
+    match result {
+        Ok(voxels) => Some(voxels),
+        Err(e) => {
+            eprintln!("ERROR [{}]: {}", filename, e);
+            None
+        }
+    }
+}
+
+fn load_emission_from_json(
+    values: &Value,
+    bounds: BoundingBox,
+) -> Option<Arc<Texture + Sync + Send>> {
+    let voxels = load_voxels_from_json(values)?;
+    let grid = VoxelTexture::create(voxels.resolution, voxels.data, bounds.min, bounds.max);
+
+    let intensity = match json::get_f64_or_rand(&values["intensity"]) {
+        Some(i) => i,
+        _ => 1.0,
+    };
+
+    match values["type"].as_str() {
+        Some("temperature") => Some(Arc::new(BlackbodyTexture {
+            temperature: grid,
+            intensity,
+        })),
+        _ => {
+            let r = json::get_f64_or_rand(&values["color"]["r"]);
+            let g = json::get_f64_or_rand(&values["color"]["g"]);
+            let b = json::get_f64_or_rand(&values["color"]["b"]);
+            let color = match (r, g, b) {
+                (Some(r), Some(g), Some(b)) => Vec3::new(r, g, b),
+                (_, _, _) => Vec3::unit(),
+            };
+            Some(Arc::new(TintedTexture {
+                grid,
+                color: color * intensity,
+            }))
+        }
+    }
+}
+
+pub fn load_from_json(values: &Value, verbose: bool) -> Vec<Box<Hitable + Sync>> {
+    let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
+
+    let id = "volumes";
+
+    let length = match values[id].as_array() {
+        Some(n) => n.len(),
+        _ => 0,
+    };
+
+    for i in 0..length {
+        let voxels = match load_voxels_from_json(&values[id][i]) {
+            Some(voxels) => voxels,
+            None => {
+                eprintln!("ERROR: Can't load the voxels of volume {}! Skipping...", i);
+                continue;
+            }
+        };
+        let (nx, ny, nz) = voxels.resolution;
+        if nx * ny * nz == 0 {
+            eprintln!("ERROR: Volume {} has no voxels! Skipping...", i);
+            continue;
+        }
+
+        let (min, max) = textures::load_bounds_from_json(&values[id][i]);
+        let bounds = BoundingBox::new(min, max);
+
+        let density_scale = match json::get_f64_or_rand(&values[id][i]["density"]) {
+            Some(d) => d,
+            _ => 1.0,
+        };
+
+        // Half a voxel by default, which is enough to not skip over any of them.
+        let size = bounds.size();
+        let step = match json::get_f64_or_rand(&values[id][i]["step"]) {
+            Some(s) if s > 0.0 => s,
+            _ => {
+                0.5 * (size.x / nx as f64)
+                    .min(size.y / ny as f64)
+                    .min(size.z / nz as f64)
+            }
+        };
+
+        let phase = if values[id][i]["material"].is_null() {
+            Isotropic::create(ConstantTexture::create(Vec3::unit()))
+        } else {
+            match materials::load_from_json(&values[id][i]) {
+                Some(material) => material,
+                None => {
+                    eprintln!("ERROR: Can't get material of volume {}! Skipping...", i);
+                    continue;
+                }
+            }
+        };
+
+        let material: Arc<Material + Sync + Send> = if values[id][i]["emission"].is_null() {
+            phase
+        } else {
+            match load_emission_from_json(&values[id][i]["emission"], bounds) {
+                Some(emission) => Arc::new(EmissiveVolume { phase, emission }),
+                None => {
+                    eprintln!(
+                        "ERROR: Can't load the emission of volume {}! Ignoring it...",
+                        i
+                    );
+                    phase
+                }
+            }
+        };
+
+        if verbose {
+            println!(
+                "Loaded a {}x{}x{} volume with a step size of {}",
+                nx, ny, nz, step
+            );
+        }
+
+        list.push(Volume::create(
+            bounds,
+            VoxelTexture::create(voxels.resolution, voxels.data, min, max),
+            density_scale,
+            step,
+            material,
+        ));
+    }
+
+    list
+}
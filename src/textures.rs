@@ -1,23 +1,408 @@
-use crate::util::vector3::Vec3;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::textures::math_texture::Operation;
+use crate::textures::noise_texture::{Basis, NoiseSettings, Pattern};
+use crate::util::{hitable::HitRecord, json, vector3::Vec3};
 
 pub trait Texture {
     fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3;
+
+    // The value at a hit, where textures can use how much of them the ray covers to filter.
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        self.value(rec.u, rec.v, rec.p)
+    }
+
+    // How solid the texture is at a hit when it's used as a cutout mask, from 0 to 1. Images
+    // with an alpha channel use that, everything else its brightness.
+    fn opacity(&self, rec: &HitRecord) -> f64 {
+        brightness(&self.sample(rec))
+    }
+}
+
+// For textures used as a single number, like masks and heights.
+pub fn brightness(c: &Vec3) -> f64 {
+    (c.x + c.y + c.z) / 3.0
+}
+
+// Where patterns like checkers are laid out.
+pub enum Space {
+    World,
+    // Before the object was moved or rotated, so the pattern moves with it.
+    Object,
+    // Along the surface's (u, v).
+    UV,
+}
+
+impl Space {
+    pub fn parse(name: &str) -> Option<Space> {
+        match name {
+            "world" => Some(Space::World),
+            "object" => Some(Space::Object),
+            "uv" => Some(Space::UV),
+            _ => None,
+        }
+    }
+
+    // The point a hit is at in this space. UV space doesn't use it.
+    pub fn point(&self, rec: &HitRecord) -> Vec3 {
+        match self {
+            Space::Object => rec.local,
+            _ => rec.p,
+        }
+    }
 }
 
 // Used for loading into scene via JSON
 pub enum TextureType {
     Checkered,
+    Grid,
     Constant,
     Image,
     Noise,
+    Voxels,
+    PointColor,
+    // Nodes that put other textures together.
+    Mix,
+    Add,
+    Multiply,
+    Ramp,
+    Transform,
+    Triplanar,
+}
+
+impl TextureType {
+    pub fn parse(name: &str) -> Option<TextureType> {
+        match name {
+            "checkered" => Some(TextureType::Checkered),
+            "grid" => Some(TextureType::Grid),
+            "constant" => Some(TextureType::Constant),
+            "image" => Some(TextureType::Image),
+            "noise" => Some(TextureType::Noise),
+            "voxels" => Some(TextureType::Voxels),
+            "point_color" => Some(TextureType::PointColor),
+            "mix" => Some(TextureType::Mix),
+            "add" => Some(TextureType::Add),
+            "multiply" => Some(TextureType::Multiply),
+            "ramp" => Some(TextureType::Ramp),
+            "transform" => Some(TextureType::Transform),
+            "triplanar" => Some(TextureType::Triplanar),
+            _ => None,
+        }
+    }
 }
 
 pub mod checkered_texture;
 pub mod constant_texture;
+pub mod grid_texture;
 pub mod image_texture;
+pub mod math_texture;
+pub mod mix_texture;
 pub mod noise_texture;
+pub mod point_color_texture;
+pub mod ramp_texture;
+pub mod transform_texture;
+pub mod triplanar_texture;
+pub mod voxel_texture;
 
 pub use crate::textures::checkered_texture::CheckeredTexture;
 pub use crate::textures::constant_texture::ConstantTexture;
-pub use crate::textures::image_texture::ImageTexture;
+pub use crate::textures::grid_texture::GridTexture;
+pub use crate::textures::math_texture::MathTexture;
+pub use crate::textures::mix_texture::MixTexture;
 pub use crate::textures::noise_texture::NoiseTexture;
+pub use crate::textures::point_color_texture::PointColorTexture;
+pub use crate::textures::ramp_texture::RampTexture;
+pub use crate::textures::transform_texture::TransformTexture;
+pub use crate::textures::triplanar_texture::TriplanarTexture;
+pub use crate::textures::voxel_texture::VoxelTexture;
+
+// The black and magenta texture that shows up whenever something can't be loaded.
+pub fn missing_texture() -> Arc<Texture + Sync + Send> {
+    CheckeredTexture::create(
+        ConstantTexture::create(Vec3::new(0.0, 0.0, 0.0)),
+        ConstantTexture::create(Vec3::new(1.0, 0.0, 1.0)),
+        10.0,
+    )
+}
+
+// A texture nested in another one: an object with its own `type` and keys, or just a color or a
+// number.
+pub fn load_from_json(values: &Value) -> Arc<Texture + Sync + Send> {
+    if let Some(texture_type) = values["type"].as_str().and_then(TextureType::parse) {
+        return create_texture(values, texture_type);
+    }
+    if let Some(color) = json::get_vec3_or_rand(values) {
+        return ConstantTexture::create(color);
+    }
+    if let Some(value) = json::get_f64_or_rand(values) {
+        return ConstantTexture::create(Vec3::unit() * value);
+    }
+    eprintln!("ERROR: Can't get texture type! Using the missing texture...");
+    missing_texture()
+}
+
+// `values` is the JSON object holding the texture's keys, e.g. the `material` of an object.
+pub fn create_texture(values: &Value, texture_type: TextureType) -> Arc<Texture + Sync + Send> {
+    match texture_type {
+        TextureType::Checkered | TextureType::Grid => {
+            let space = match values["space"].as_str() {
+                Some(name) => match Space::parse(name) {
+                    Some(space) => space,
+                    None => {
+                        eprintln!("ERROR: Unknown texture space {}! Using world...", name);
+                        Space::World
+                    }
+                },
+                None => Space::World,
+            };
+            // One number for both, or separate `u` and `v` for UV space.
+            let scale = load_uv_from_json(&values["scale"], 1.0);
+
+            // Either colors or whole textures.
+            let color = |values: &Value, default: Vec3| {
+                if values.is_null() {
+                    ConstantTexture::create(default)
+                } else {
+                    load_from_json(values)
+                }
+            };
+
+            match texture_type {
+                TextureType::Grid => {
+                    let width = match json::get_f64_or_rand(&values["width"]) {
+                        Some(w) => w,
+                        _ => 0.05,
+                    };
+                    GridTexture::create(
+                        color(&values["colors"][0], Vec3::unit()),
+                        color(&values["colors"][1], Vec3::zero()),
+                        space,
+                        scale,
+                        width,
+                    )
+                }
+                _ => CheckeredTexture::create_with(
+                    color(&values["colors"][0], Vec3::zero()),
+                    color(&values["colors"][1], Vec3::zero()),
+                    space,
+                    scale,
+                ),
+            }
+        }
+
+        TextureType::Constant => {
+            let r = json::get_f64_or_rand(&values["color"]["r"]);
+            let g = json::get_f64_or_rand(&values["color"]["g"]);
+            let b = json::get_f64_or_rand(&values["color"]["b"]);
+            let (r, g, b) = match (r, g, b) {
+                (Some(r), Some(g), Some(b)) => (r, g, b),
+                (_, _, _) => (0.0, 0.0, 0.0),
+            };
+
+            ConstantTexture::create(Vec3::new(r, g, b))
+        }
+
+        TextureType::Image => image_texture::load_from_json(values, true),
+
+        TextureType::Noise => {
+            let scale = json::get_f64_or_rand(&values["scale"]);
+            let scale = match scale {
+                Some(s) => s,
+                _ => 1.0,
+            };
+
+            let mut settings = NoiseSettings::new(scale);
+            settings.pattern = match values["pattern"].as_str() {
+                Some("marble") | None => Pattern::Marble,
+                Some("fbm") => Pattern::Fbm,
+                Some("turbulence") => Pattern::Turbulence,
+                Some("cells") => Pattern::Cells,
+                Some("wood") => Pattern::Wood,
+                Some("granite") => Pattern::Granite,
+                Some(pattern) => {
+                    eprintln!("ERROR: Unknown noise pattern {}! Using marble...", pattern);
+                    Pattern::Marble
+                }
+            };
+            settings.basis = match values["noise"].as_str() {
+                Some("perlin") | None => Basis::Perlin,
+                Some("simplex") => Basis::Simplex,
+                Some(basis) => {
+                    eprintln!("ERROR: Unknown noise {}! Using Perlin...", basis);
+                    Basis::Perlin
+                }
+            };
+            if let Some(frequency) = json::get_f64_or_rand(&values["frequency"]) {
+                settings.frequency = frequency;
+            }
+            if let Some(distortion) = json::get_f64_or_rand(&values["distortion"]) {
+                settings.distortion = distortion;
+            }
+            if let Some(octaves) = values["octaves"].as_u64() {
+                settings.octaves = octaves as usize;
+            }
+            if let Some(lacunarity) = json::get_f64_or_rand(&values["lacunarity"]) {
+                settings.lacunarity = lacunarity;
+            }
+            if let Some(gain) = json::get_f64_or_rand(&values["gain"]) {
+                settings.gain = gain;
+            }
+
+            // Each pattern has colors that suit it, unless some are given.
+            let (low, high) = match settings.pattern {
+                Pattern::Wood => (Vec3::new(0.25, 0.12, 0.05), Vec3::new(0.6, 0.38, 0.2)),
+                Pattern::Granite => (Vec3::new(0.1, 0.1, 0.1), Vec3::new(0.75, 0.68, 0.65)),
+                _ => (Vec3::zero(), Vec3::unit()),
+            };
+            let color = |values: &Value, default: Vec3| {
+                if values.is_null() {
+                    ConstantTexture::create(default)
+                } else {
+                    load_from_json(values)
+                }
+            };
+
+            NoiseTexture::create_with(
+                settings,
+                color(&values["colors"][0], low),
+                color(&values["colors"][1], high),
+            )
+        }
+
+        TextureType::Voxels => {
+            let nx = values["resolution"]["x"].as_u64();
+            let ny = values["resolution"]["y"].as_u64();
+            let nz = values["resolution"]["z"].as_u64();
+            let (nx, ny, nz) = match (nx, ny, nz) {
+                (Some(x), Some(y), Some(z)) => (x as usize, y as usize, z as usize),
+                (_, _, _) => {
+                    eprintln!("ERROR: Can't get resolution of voxel grid!");
+                    return missing_texture();
+                }
+            };
+
+            let data: Vec<f64> = match values["data"].as_array() {
+                Some(data) => data.iter().map(|v| v.as_f64().unwrap_or(0.0)).collect(),
+                _ => Vec::new(),
+            };
+            if data.len() != nx * ny * nz {
+                eprintln!(
+                    "ERROR: Voxel grid has {} values but a resolution of {}x{}x{}!",
+                    data.len(),
+                    nx,
+                    ny,
+                    nz
+                );
+                return missing_texture();
+            }
+
+            let (min, max) = load_bounds_from_json(values);
+            VoxelTexture::create((nx, ny, nz), data, min, max)
+        }
+
+        TextureType::PointColor => PointColorTexture::create(),
+
+        TextureType::Mix => MixTexture::create(
+            load_from_json(&values["textures"][0]),
+            load_from_json(&values["textures"][1]),
+            load_from_json(&values["mask"]),
+        ),
+
+        TextureType::Add | TextureType::Multiply => {
+            let operation = match texture_type {
+                TextureType::Add => Operation::Add,
+                _ => Operation::Multiply,
+            };
+            let textures = match values["textures"].as_array() {
+                Some(textures) => textures.iter().map(load_from_json).collect(),
+                _ => Vec::new(),
+            };
+            MathTexture::create(operation, textures)
+        }
+
+        TextureType::Ramp => {
+            let mut stops = Vec::new();
+            if let Some(values) = values["stops"].as_array() {
+                for stop in values {
+                    match (
+                        json::get_f64_or_rand(&stop["position"]),
+                        json::get_vec3_or_rand(&stop["color"]),
+                    ) {
+                        (Some(position), Some(color)) => stops.push((position, color)),
+                        (_, _) => eprintln!("ERROR: Can't get position or color of ramp stop!"),
+                    }
+                }
+            }
+            if stops.is_empty() {
+                stops = vec![(0.0, Vec3::zero()), (1.0, Vec3::unit())];
+            }
+            RampTexture::create(load_from_json(&values["input"]), stops)
+        }
+
+        TextureType::Transform => {
+            let scale = load_uv_from_json(&values["scale"], 1.0);
+            let rotation = match json::get_f64_or_rand(&values["rotation"]) {
+                Some(r) => r,
+                _ => 0.0,
+            };
+            let offset = load_uv_from_json(&values["offset"], 0.0);
+            TransformTexture::create(
+                load_from_json(&values["texture"]),
+                scale,
+                rotation.to_radians(),
+                offset,
+            )
+        }
+
+        TextureType::Triplanar => {
+            let scale = match json::get_f64_or_rand(&values["scale"]) {
+                Some(s) => s,
+                _ => 1.0,
+            };
+            let sharpness = match json::get_f64_or_rand(&values["sharpness"]) {
+                Some(s) => s,
+                _ => 4.0,
+            };
+            TriplanarTexture::create(load_from_json(&values["texture"]), scale, sharpness)
+        }
+    }
+}
+
+// `min` and `max` corners of a box, defaulting to the unit cube centered at the origin.
+pub fn load_bounds_from_json(values: &Value) -> (Vec3, Vec3) {
+    let min = (
+        json::get_f64_or_rand(&values["min"]["x"]),
+        json::get_f64_or_rand(&values["min"]["y"]),
+        json::get_f64_or_rand(&values["min"]["z"]),
+    );
+    let max = (
+        json::get_f64_or_rand(&values["max"]["x"]),
+        json::get_f64_or_rand(&values["max"]["y"]),
+        json::get_f64_or_rand(&values["max"]["z"]),
+    );
+    match (min, max) {
+        ((Some(x0), Some(y0), Some(z0)), (Some(x1), Some(y1), Some(z1))) => {
+            (Vec3::new(x0, y0, z0), Vec3::new(x1, y1, z1))
+        }
+        (_, _) => (Vec3::unit() * -0.5, Vec3::unit() * 0.5),
+    }
+}
+
+// One number for both `u` and `v`, or separate `u` and `v`, each defaulting to `default`.
+pub fn load_uv_from_json(values: &Value, default: f64) -> (f64, f64) {
+    if let Some(x) = json::get_f64_or_rand(values) {
+        return (x, x);
+    }
+    (
+        match json::get_f64_or_rand(&values["u"]) {
+            Some(u) => u,
+            _ => default,
+        },
+        match json::get_f64_or_rand(&values["v"]) {
+            Some(v) => v,
+            _ => default,
+        },
+    )
+}
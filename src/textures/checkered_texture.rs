@@ -1,12 +1,15 @@
 use std::sync::Arc;
 
-use crate::textures::Texture;
-use crate::util::vector3::Vec3;
+use crate::textures::{Space, Texture};
+use crate::util::{hitable::HitRecord, vector3::Vec3};
 
 pub struct CheckeredTexture {
     odd: Arc<Texture + Sync + Send>,
     even: Arc<Texture + Sync + Send>,
-    scale: f64,
+    space: Space,
+    // In world and object space only the first is used, as the frequency of the sines. In UV
+    // space they're the number of squares along u and v.
+    scale: (f64, f64),
 }
 
 impl CheckeredTexture {
@@ -15,17 +18,51 @@ impl CheckeredTexture {
         even: Arc<Texture + Sync + Send>,
         scale: f64,
     ) -> Arc<CheckeredTexture> {
-        Arc::new(CheckeredTexture { odd, even, scale })
+        CheckeredTexture::create_with(odd, even, Space::World, (scale, scale))
+    }
+
+    pub fn create_with(
+        odd: Arc<Texture + Sync + Send>,
+        even: Arc<Texture + Sync + Send>,
+        space: Space,
+        scale: (f64, f64),
+    ) -> Arc<CheckeredTexture> {
+        Arc::new(CheckeredTexture {
+            odd,
+            even,
+            space,
+            scale,
+        })
+    }
+
+    fn is_odd(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        match self.space {
+            Space::World | Space::Object => {
+                let scale = self.scale.0;
+                (scale * p.x).sin() * (scale * p.y).sin() * (scale * p.z).sin() < 0.0
+            }
+            Space::UV => {
+                ((u * self.scale.0).floor() + (v * self.scale.1).floor()).rem_euclid(2.0) >= 1.0
+            }
+        }
     }
 }
 
 impl Texture for CheckeredTexture {
+    // Without a hit there's no object space, so it's the same as world space here.
     fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
-        let sines = (self.scale * p.x).sin() * (self.scale * p.y).sin() * (self.scale * p.z).sin();
-        if sines < 0.0 {
+        if self.is_odd(u, v, &p) {
             self.odd.value(u, v, p)
         } else {
             self.even.value(u, v, p)
         }
     }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        if self.is_odd(rec.u, rec.v, &self.space.point(rec)) {
+            self.odd.sample(rec)
+        } else {
+            self.even.sample(rec)
+        }
+    }
 }
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use crate::textures::{Space, Texture};
+use crate::util::{hitable::HitRecord, vector3::Vec3};
+
+// Lines on a background, along the edges of the squares a checkered texture with the same
+// space and scale would have.
+pub struct GridTexture {
+    background: Arc<Texture + Sync + Send>,
+    line: Arc<Texture + Sync + Send>,
+    space: Space,
+    scale: (f64, f64),
+    // How wide the lines are, as a fraction of a square.
+    width: f64,
+}
+
+impl GridTexture {
+    pub fn create(
+        background: Arc<Texture + Sync + Send>,
+        line: Arc<Texture + Sync + Send>,
+        space: Space,
+        scale: (f64, f64),
+        width: f64,
+    ) -> Arc<GridTexture> {
+        Arc::new(GridTexture {
+            background,
+            line,
+            space,
+            scale,
+            width,
+        })
+    }
+
+    fn is_line(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        let near = |x: f64| (x - x.round()).abs() < self.width / 2.0;
+        match self.space {
+            Space::World | Space::Object => {
+                let scale = self.scale.0 / std::f64::consts::PI;
+                near(p.x * scale) || near(p.y * scale) || near(p.z * scale)
+            }
+            Space::UV => near(u * self.scale.0) || near(v * self.scale.1),
+        }
+    }
+}
+
+impl Texture for GridTexture {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        if self.is_line(u, v, &p) {
+            self.line.value(u, v, p)
+        } else {
+            self.background.value(u, v, p)
+        }
+    }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        if self.is_line(rec.u, rec.v, &self.space.point(rec)) {
+            self.line.sample(rec)
+        } else {
+            self.background.sample(rec)
+        }
+    }
+}
@@ -1,50 +1,374 @@
+use std::ops::{Add, Mul};
 use std::sync::Arc;
 
 use image::DynamicImage;
+use serde_json::Value;
 
-use crate::textures::Texture;
-use crate::util::vector3::Vec3;
+use crate::textures::{self, Texture};
+use crate::util::{hitable::HitRecord, vector3::Vec3};
 
-pub struct ImageTexture {
+#[derive(Clone, Copy)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    // Bilinear, blended between the two mipmap levels closest to how much of the texture a ray
+    // covers.
+    Trilinear,
+}
+
+#[derive(Clone, Copy)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    // Which of `size` texels the texel `i` ends up on.
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::Clamp => i.clamp(0, size - 1),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+struct Level {
     pixels: Vec<Vec3>,
-    width: u32,
-    height: u32,
-    scale: f64,
+    alpha: Vec<f64>,
+    width: usize,
+    height: usize,
+}
+
+// The source pixels along one axis that make up pixel `i` of the downsampled level, and how much
+// each counts. Odd sizes use three pixels with the outer ones weighted so the whole row is
+// covered, rather than dropping the last one.
+fn taps(size: usize, i: usize) -> Vec<(usize, f64)> {
+    if size == 1 {
+        return vec![(0, 1.0)];
+    }
+    if size.is_multiple_of(2) {
+        return vec![(2 * i, 0.5), (2 * i + 1, 0.5)];
+    }
+    let half = (size / 2) as f64;
+    let i_f = i as f64;
+    vec![
+        (2 * i, (half - i_f) / size as f64),
+        (2 * i + 1, half / size as f64),
+        (2 * i + 2, (i_f + 1.0) / size as f64),
+    ]
+}
+
+impl Level {
+    // Half the size of `self`, each pixel the average of the ones it covers.
+    fn downsample(&self) -> Level {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut alpha = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let rows = taps(self.height, y);
+            for x in 0..width {
+                let columns = taps(self.width, x);
+                let mut pixel = Vec3::zero();
+                let mut a = 0.0;
+                for (y0, wy) in rows.iter() {
+                    for (x0, wx) in columns.iter() {
+                        let i = x0 + self.width * y0;
+                        pixel += self.pixels[i] * (wx * wy);
+                        a += self.alpha[i] * wx * wy;
+                    }
+                }
+                pixels.push(pixel);
+                alpha.push(a);
+            }
+        }
+        Level {
+            pixels,
+            alpha,
+            width,
+            height,
+        }
+    }
+}
+
+pub struct ImageTexture {
+    // Mipmaps, from the full image down to a single pixel.
+    levels: Vec<Level>,
+    filter: Filter,
+    wrap: Wrap,
+    // Whether the image had an alpha channel, rather than it being all opaque.
+    has_alpha: bool,
+    // How much of the surface's (u, v) one copy of the image covers, and where it starts.
+    scale: (f64, f64),
+    offset: (f64, f64),
+}
+
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
 }
 
 impl ImageTexture {
-    pub fn create(image: &DynamicImage, scale: f64) -> Arc<ImageTexture> {
-        let image = image.to_rgb();
+    // 8-bit images are usually sRGB encoded, so `srgb` turns them back to linear colors. Leave
+    // it off for images that hold data rather than colors, like normal maps.
+    pub fn create(
+        image: &DynamicImage,
+        filter: Filter,
+        wrap: Wrap,
+        scale: (f64, f64),
+        offset: (f64, f64),
+        srgb: bool,
+    ) -> Arc<ImageTexture> {
+        let has_alpha = matches!(
+            image.color(),
+            image::ColorType::GrayA(_) | image::ColorType::RGBA(_) | image::ColorType::BGRA(_)
+        );
+        let image = image.to_rgba();
         let (width, height) = image.dimensions();
+        let decode = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if srgb {
+                srgb_to_linear(c)
+            } else {
+                c
+            }
+        };
         let pixels: Vec<Vec3> = image
             .pixels()
-            .map(|rgb| {
-                Vec3::new(
-                    f64::from(rgb[0]) / 255.0,
-                    f64::from(rgb[1]) / 255.0,
-                    f64::from(rgb[2]) / 255.0,
-                )
-            })
+            .map(|rgb| Vec3::new(decode(rgb[0]), decode(rgb[1]), decode(rgb[2])))
+            .collect();
+        let alpha: Vec<f64> = image
+            .pixels()
+            .map(|rgba| f64::from(rgba[3]) / 255.0)
             .collect();
 
-        Arc::new(ImageTexture {
+        let mut levels = vec![Level {
             pixels,
-            width,
-            height,
+            alpha,
+            width: width.max(1) as usize,
+            height: height.max(1) as usize,
+        }];
+        if let Filter::Trilinear = filter {
+            loop {
+                let next = match levels.last() {
+                    Some(last) if last.width > 1 || last.height > 1 => last.downsample(),
+                    _ => break,
+                };
+                levels.push(next);
+            }
+        }
+
+        Arc::new(ImageTexture {
+            levels,
+            filter,
+            wrap,
+            has_alpha,
             scale,
+            offset,
         })
     }
+
+    // Index of the texel (`x`, `y`) of `level` after wrapping.
+    fn texel(&self, level: &Level, x: i64, y: i64) -> usize {
+        let x = self.wrap.apply(x, level.width);
+        let y = self.wrap.apply(y, level.height);
+        x + level.width * y
+    }
+
+    // `x` and `y` in texels of the level, not counting the half texel to their centers. `get`
+    // picks what to read out of a texel, the color or the alpha.
+    fn nearest<T>(&self, level: &Level, x: f64, y: f64, get: fn(&Level, usize) -> T) -> T {
+        get(level, self.texel(level, x.floor() as i64, y.floor() as i64))
+    }
+
+    fn bilinear<T>(&self, level: &Level, x: f64, y: f64, get: fn(&Level, usize) -> T) -> T
+    where
+        T: Add<Output = T> + Mul<f64, Output = T>,
+    {
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let at = |x, y| get(level, self.texel(level, x, y));
+        let top = at(x0, y0) * (1.0 - fx) + at(x0 + 1, y0) * fx;
+        let bottom = at(x0, y0 + 1) * (1.0 - fx) + at(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    // Image coordinates, from 0 to 1 across one copy of the image.
+    fn image_uv(&self, u: f64, v: f64) -> (f64, f64) {
+        (
+            u / self.scale.0 + self.offset.0,
+            v / self.scale.1 + self.offset.1,
+        )
+    }
+
+    fn lookup<T>(&self, s: f64, t: f64, lod: f64, get: fn(&Level, usize) -> T) -> T
+    where
+        T: Add<Output = T> + Mul<f64, Output = T>,
+    {
+        let at = |level: &Level| {
+            let (x, y) = (s * level.width as f64, t * level.height as f64);
+            match self.filter {
+                Filter::Nearest => self.nearest(level, x, y, get),
+                _ => self.bilinear(level, x, y, get),
+            }
+        };
+
+        let last = (self.levels.len() - 1) as f64;
+        let lod = lod.clamp(0.0, last);
+        let lower = lod.floor();
+        let fraction = lod - lower;
+        let color = at(&self.levels[lower as usize]);
+        if fraction > 0.0 {
+            color * (1.0 - fraction) + at(&self.levels[lower as usize + 1]) * fraction
+        } else {
+            color
+        }
+    }
+
+    // Which mipmap level fits how much of the texture the ray that hit covers.
+    fn lod(&self, rec: &HitRecord) -> f64 {
+        if self.levels.len() == 1 {
+            return 0.0;
+        }
+
+        // How many texels the ray covers along u and v, from how far a step in them moves on
+        // the surface.
+        let (du, dv) = (rec.dpdu.length(), rec.dpdv.length());
+        let texels = |texels_per_unit: f64, length: f64| {
+            if length > 0.0 {
+                rec.footprint / length * texels_per_unit
+            } else {
+                0.0
+            }
+        };
+        let level = &self.levels[0];
+        let texels = texels(level.width as f64 / self.scale.0.abs(), du)
+            .max(texels(level.height as f64 / self.scale.1.abs(), dv));
+        if texels > 1.0 {
+            texels.log2()
+        } else {
+            0.0
+        }
+    }
 }
 
 impl Texture for ImageTexture {
     fn value(&self, u: f64, v: f64, _: Vec3) -> Vec3 {
-        let i = ((u * f64::from(self.width) / self.scale) as u32 % self.width) as usize;
-        let j = ((v * f64::from(self.height) / self.scale) as u32 % self.height) as usize;
+        let (s, t) = self.image_uv(u, v);
+        self.lookup(s, t, 0.0, |level, i| level.pixels[i])
+    }
 
-        let red = self.pixels[i + self.width as usize * j].x;
-        let green = self.pixels[i + self.width as usize * j].y;
-        let blue = self.pixels[i + self.width as usize * j].z;
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        let (s, t) = self.image_uv(rec.u, rec.v);
+        self.lookup(s, t, self.lod(rec), |level, i| level.pixels[i])
+    }
+
+    fn opacity(&self, rec: &HitRecord) -> f64 {
+        if !self.has_alpha {
+            return textures::brightness(&self.sample(rec));
+        }
+        let (s, t) = self.image_uv(rec.u, rec.v);
+        self.lookup(s, t, self.lod(rec), |level, i| level.alpha[i])
+    }
+}
+
+// `srgb` is whether 8-bit images are taken to be sRGB when the JSON doesn't say.
+pub fn load_from_json(values: &Value, srgb: bool) -> Arc<Texture + Sync + Send> {
+    let filename = match values["filename"].as_str() {
+        Some(filename) => filename,
+        _ => return textures::missing_texture(),
+    };
+
+    let scale = textures::load_uv_from_json(&values["scale"], 1.0);
+    let offset = textures::load_uv_from_json(&values["offset"], 0.0);
+    let filter = match values["filter"].as_str() {
+        Some("nearest") => Filter::Nearest,
+        Some("bilinear") => Filter::Bilinear,
+        Some("trilinear") | None => Filter::Trilinear,
+        Some(filter) => {
+            eprintln!(
+                "ERROR: Unknown texture filter {}! Using trilinear...",
+                filter
+            );
+            Filter::Trilinear
+        }
+    };
+    let wrap = match values["wrap"].as_str() {
+        Some("repeat") | None => Wrap::Repeat,
+        Some("clamp") => Wrap::Clamp,
+        Some("mirror") => Wrap::Mirror,
+        Some(wrap) => {
+            eprintln!("ERROR: Unknown texture wrap {}! Using repeat...", wrap);
+            Wrap::Repeat
+        }
+    };
+    let srgb = values["srgb"].as_bool().unwrap_or(srgb);
+
+    let image_file = match image::open(filename) {
+        Ok(image_file) => image_file,
+        Err(e) => {
+            eprintln!("ERROR [{}]: {}", filename, e);
+            return textures::missing_texture();
+        }
+    };
+
+    ImageTexture::create(&image_file, filter, wrap, scale, offset, srgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(width: usize, height: usize, values: &[f64]) -> Level {
+        Level {
+            pixels: values.iter().map(|v| Vec3::new(*v, *v, *v)).collect(),
+            alpha: values.to_vec(),
+            width,
+            height,
+        }
+    }
+
+    fn mean(level: &Level) -> f64 {
+        level.alpha.iter().sum::<f64>() / level.alpha.len() as f64
+    }
+
+    #[test]
+    fn even_sizes_average_pairs() {
+        let half = level(4, 2, &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]).downsample();
+        assert_eq!((half.width, half.height), (2, 1));
+        assert_eq!(half.alpha, vec![2.5, 4.5]);
+    }
+
+    #[test]
+    fn odd_sizes_cover_every_pixel() {
+        // The last column and row only show up in the mean if they aren't dropped.
+        let values: Vec<f64> = (0..35)
+            .map(|i| if i % 7 == 6 || i >= 28 { 10.0 } else { 0.0 })
+            .collect();
+        let source = level(7, 5, &values);
+        let half = source.downsample();
+        assert_eq!((half.width, half.height), (3, 2));
+        assert!((mean(&half) - mean(&source)).abs() < 1.0e-12);
+        assert!((half.pixels[5].x - half.alpha[5]).abs() < 1.0e-12);
 
-        Vec3::new(red, green, blue)
+        let single = level(3, 1, &[1.0, 2.0, 6.0]).downsample();
+        assert_eq!((single.width, single.height), (1, 1));
+        assert!((single.alpha[0] - 3.0).abs() < 1.0e-12);
     }
 }
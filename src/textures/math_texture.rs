@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use crate::textures::Texture;
+use crate::util::{hitable::HitRecord, vector3::Vec3};
+
+pub enum Operation {
+    Add,
+    Multiply,
+}
+
+impl Operation {
+    fn apply(&self, a: Vec3, b: Vec3) -> Vec3 {
+        match self {
+            Operation::Add => a + b,
+            Operation::Multiply => a * b,
+        }
+    }
+}
+
+// Adds or multiplies any number of textures together.
+pub struct MathTexture {
+    operation: Operation,
+    textures: Vec<Arc<Texture + Sync + Send>>,
+}
+
+impl MathTexture {
+    pub fn create(
+        operation: Operation,
+        textures: Vec<Arc<Texture + Sync + Send>>,
+    ) -> Arc<MathTexture> {
+        Arc::new(MathTexture {
+            operation,
+            textures,
+        })
+    }
+
+    fn combine(&self, values: impl Iterator<Item = Vec3>) -> Vec3 {
+        let mut values = values;
+        match values.next() {
+            Some(first) => values.fold(first, |sum, value| self.operation.apply(sum, value)),
+            None => Vec3::zero(),
+        }
+    }
+}
+
+impl Texture for MathTexture {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        self.combine(self.textures.iter().map(|t| t.value(u, v, p)))
+    }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        self.combine(self.textures.iter().map(|t| t.sample(rec)))
+    }
+}
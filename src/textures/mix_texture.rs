@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use crate::textures::{self, Texture};
+use crate::util::{hitable::HitRecord, vector3::Vec3};
+
+// Blends from `a` where the mask is black to `b` where it's white. Masks with an alpha channel
+// use that instead, for decals.
+pub struct MixTexture {
+    a: Arc<Texture + Sync + Send>,
+    b: Arc<Texture + Sync + Send>,
+    mask: Arc<Texture + Sync + Send>,
+}
+
+impl MixTexture {
+    pub fn create(
+        a: Arc<Texture + Sync + Send>,
+        b: Arc<Texture + Sync + Send>,
+        mask: Arc<Texture + Sync + Send>,
+    ) -> Arc<MixTexture> {
+        Arc::new(MixTexture { a, b, mask })
+    }
+}
+
+impl Texture for MixTexture {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        let t = textures::brightness(&self.mask.value(u, v, p));
+        self.a.value(u, v, p) * (1.0 - t) + self.b.value(u, v, p) * t
+    }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        let t = self.mask.opacity(rec);
+        self.a.sample(rec) * (1.0 - t) + self.b.sample(rec) * t
+    }
+}
@@ -1,25 +1,144 @@
 use std::sync::Arc;
 
-use crate::textures::Texture;
-use crate::util::{perlin::Perlin, vector3::Vec3};
+use crate::textures::{ConstantTexture, Texture};
+use crate::util::{hitable::HitRecord, perlin::Perlin, vector3::Vec3, worley::Worley};
+
+pub enum Basis {
+    Perlin,
+    Simplex,
+}
+
+pub enum Pattern {
+    // Octaves of noise added up.
+    Fbm,
+    // Octaves of the absolute value of noise added up, which gives sharp creases.
+    Turbulence,
+    // Distance to the closest point of Worley noise.
+    Cells,
+    // Stripes along z bent by turbulence.
+    Marble,
+    // Rings around the y axis bent by fBm.
+    Wood,
+    // Cells with turbulence on top, for speckled stone.
+    Granite,
+}
+
+pub struct NoiseSettings {
+    pub pattern: Pattern,
+    pub basis: Basis,
+    // For marble and wood how close the stripes or rings are, otherwise how fine the noise is.
+    pub scale: f64,
+    // How fine the noise bending marble and wood is.
+    pub frequency: f64,
+    // How much that noise bends them.
+    pub distortion: f64,
+    pub octaves: usize,
+    // How much finer and fainter each octave is than the last.
+    pub lacunarity: f64,
+    pub gain: f64,
+}
+
+impl NoiseSettings {
+    // The marble pattern the noise texture has always had.
+    pub fn new(scale: f64) -> NoiseSettings {
+        NoiseSettings {
+            pattern: Pattern::Marble,
+            basis: Basis::Perlin,
+            scale,
+            frequency: 1.0,
+            distortion: 10.0,
+            octaves: 7,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
 
 pub struct NoiseTexture {
-    noise: Perlin,
-    scale: f64,
+    perlin: Perlin,
+    worley: Worley,
+    settings: NoiseSettings,
+    // What 0 and 1 of the pattern look like.
+    colors: (Arc<Texture + Sync + Send>, Arc<Texture + Sync + Send>),
 }
 
 impl NoiseTexture {
     pub fn create(scale: f64) -> Arc<NoiseTexture> {
+        NoiseTexture::create_with(
+            NoiseSettings::new(scale),
+            ConstantTexture::create(Vec3::zero()),
+            ConstantTexture::create(Vec3::unit()),
+        )
+    }
+
+    pub fn create_with(
+        settings: NoiseSettings,
+        low: Arc<Texture + Sync + Send>,
+        high: Arc<Texture + Sync + Send>,
+    ) -> Arc<NoiseTexture> {
         Arc::new(NoiseTexture {
-            noise: Perlin::new(),
-            scale,
+            perlin: Perlin::new(),
+            worley: Worley::new(),
+            settings,
+            colors: (low, high),
         })
     }
+
+    fn basis(&self, p: Vec3) -> f64 {
+        match self.settings.basis {
+            Basis::Perlin => self.perlin.noise(p),
+            Basis::Simplex => self.perlin.simplex(p),
+        }
+    }
+
+    // Octaves of the basis, each passed through `f` before being added up.
+    fn octaves(&self, p: Vec3, f: impl Fn(f64) -> f64) -> f64 {
+        let mut sum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..self.settings.octaves {
+            sum += weight * f(self.basis(p));
+            weight *= self.settings.gain;
+            p *= self.settings.lacunarity;
+        }
+        sum
+    }
+
+    // The pattern at `p`, from 0 to 1.
+    fn pattern(&self, p: Vec3) -> f64 {
+        let settings = &self.settings;
+        let t = match settings.pattern {
+            Pattern::Fbm => 0.5 * (1.0 + self.octaves(p * settings.scale, |n| n)),
+            Pattern::Turbulence => self.octaves(p * settings.scale, f64::abs),
+            Pattern::Cells => self.worley.noise(p * settings.scale).0,
+            Pattern::Marble => {
+                let turbulence = self.octaves(p * settings.frequency, |n| n).abs();
+                0.5 * (1.0 + (settings.scale * p.z + settings.distortion * turbulence).sin())
+            }
+            Pattern::Wood => {
+                let bend = self.octaves(p * settings.frequency, |n| n);
+                let rings = settings.scale * (p.x * p.x + p.z * p.z).sqrt()
+                    + 0.1 * settings.distortion * bend;
+                rings - rings.floor()
+            }
+            Pattern::Granite => {
+                let (f1, f2) = self.worley.noise(p * settings.scale);
+                let turbulence = self.octaves(p * settings.scale * 4.0, f64::abs);
+                (f2 - f1) * 0.7 + turbulence * 0.3
+            }
+        };
+        t.clamp(0.0, 1.0)
+    }
 }
 
 impl Texture for NoiseTexture {
-    fn value(&self, _: f64, _: f64, p: Vec3) -> Vec3 {
-        //Vec3::unit() * self.noise.noise(self.scale * p)
-        Vec3::unit() * 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turbulence(p, 7)).sin())
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        let t = self.pattern(p);
+        self.colors.0.value(u, v, p) * (1.0 - t) + self.colors.1.value(u, v, p) * t
+    }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        let t = self.pattern(rec.p);
+        self.colors.0.sample(rec) * (1.0 - t) + self.colors.1.sample(rec) * t
     }
 }
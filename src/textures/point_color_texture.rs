@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use crate::textures::Texture;
+use crate::util::{hitable::HitRecord, vector3::Vec3};
+
+// The color of the point that was hit, so a whole point cloud can share one material. White on
+// anything that doesn't have colored points.
+pub struct PointColorTexture {}
+
+impl PointColorTexture {
+    pub fn create() -> Arc<PointColorTexture> {
+        Arc::new(PointColorTexture {})
+    }
+}
+
+impl Texture for PointColorTexture {
+    fn value(&self, _: f64, _: f64, _: Vec3) -> Vec3 {
+        Vec3::unit()
+    }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        rec.color
+    }
+}
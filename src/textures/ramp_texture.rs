@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use crate::textures::{self, Texture};
+use crate::util::{hitable::HitRecord, vector3::Vec3};
+
+// Turns the brightness of `input` into a color, blending between the two stops on either side
+// of it.
+pub struct RampTexture {
+    input: Arc<Texture + Sync + Send>,
+    // Positions and colors, sorted by position.
+    stops: Vec<(f64, Vec3)>,
+}
+
+impl RampTexture {
+    pub fn create(input: Arc<Texture + Sync + Send>, stops: Vec<(f64, Vec3)>) -> Arc<RampTexture> {
+        let mut stops = stops;
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Arc::new(RampTexture { input, stops })
+    }
+
+    fn color(&self, x: f64) -> Vec3 {
+        let next = self.stops.partition_point(|stop| stop.0 <= x);
+        if next == 0 {
+            return match self.stops.first() {
+                Some(stop) => stop.1,
+                None => Vec3::zero(),
+            };
+        }
+        if next == self.stops.len() {
+            return self.stops[next - 1].1;
+        }
+        let (x0, c0) = self.stops[next - 1];
+        let (x1, c1) = self.stops[next];
+        let t = (x - x0) / (x1 - x0);
+        c0 * (1.0 - t) + c1 * t
+    }
+}
+
+impl Texture for RampTexture {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        self.color(textures::brightness(&self.input.value(u, v, p)))
+    }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        self.color(textures::brightness(&self.input.sample(rec)))
+    }
+}
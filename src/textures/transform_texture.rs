@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use crate::textures::Texture;
+use crate::util::{hitable::HitRecord, vector3::Vec3};
+
+// Moves another texture around the surface by changing the (u, v) it's looked up with.
+pub struct TransformTexture {
+    texture: Arc<Texture + Sync + Send>,
+    // How much bigger the texture gets along u and v, like an image's scale.
+    scale: (f64, f64),
+    // In radians, counterclockwise.
+    rotation: f64,
+    offset: (f64, f64),
+}
+
+impl TransformTexture {
+    pub fn create(
+        texture: Arc<Texture + Sync + Send>,
+        scale: (f64, f64),
+        rotation: f64,
+        offset: (f64, f64),
+    ) -> Arc<TransformTexture> {
+        Arc::new(TransformTexture {
+            texture,
+            scale,
+            rotation,
+            offset,
+        })
+    }
+
+    fn transform(&self, u: f64, v: f64) -> (f64, f64) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (u, v) = (u / self.scale.0, v / self.scale.1);
+        (
+            cos * u - sin * v + self.offset.0,
+            sin * u + cos * v + self.offset.1,
+        )
+    }
+}
+
+impl Texture for TransformTexture {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        let (u, v) = self.transform(u, v);
+        self.texture.value(u, v, p)
+    }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        let mut rec = rec.clone();
+        let (u, v) = self.transform(rec.u, rec.v);
+        // How the surface moves along the new (u, v), so filtering still knows how big the
+        // texture looks.
+        let (sin, cos) = self.rotation.sin_cos();
+        let (dpdu, dpdv) = (rec.dpdu, rec.dpdv);
+        rec.dpdu = dpdu * (cos * self.scale.0) - dpdv * (sin * self.scale.1);
+        rec.dpdv = dpdu * (sin * self.scale.0) + dpdv * (cos * self.scale.1);
+        rec.u = u;
+        rec.v = v;
+        self.texture.sample(&rec)
+    }
+}
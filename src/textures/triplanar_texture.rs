@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use crate::textures::Texture;
+use crate::util::{hitable::HitRecord, vector3::Vec3};
+
+// Projects another texture onto the surface along the x, y and z axes and blends the three by
+// how much the surface faces each axis, for surfaces without good (u, v) like meshes.
+pub struct TriplanarTexture {
+    texture: Arc<Texture + Sync + Send>,
+    // Size of one copy of the texture in world space.
+    scale: f64,
+    // Higher makes the seams between the projections sharper.
+    sharpness: f64,
+}
+
+impl TriplanarTexture {
+    pub fn create(
+        texture: Arc<Texture + Sync + Send>,
+        scale: f64,
+        sharpness: f64,
+    ) -> Arc<TriplanarTexture> {
+        Arc::new(TriplanarTexture {
+            texture,
+            scale,
+            sharpness,
+        })
+    }
+
+    // The (u, v) of each projection, with v going down like in an image.
+    fn projections(&self, p: &Vec3) -> [(f64, f64); 3] {
+        let p = *p / self.scale;
+        [(p.z, -p.y), (p.x, p.z), (p.x, -p.y)]
+    }
+
+    fn weights(&self, normal: &Vec3) -> [f64; 3] {
+        let w = [
+            normal.x.abs().powf(self.sharpness),
+            normal.y.abs().powf(self.sharpness),
+            normal.z.abs().powf(self.sharpness),
+        ];
+        let sum = w[0] + w[1] + w[2];
+        if sum > 0.0 {
+            [w[0] / sum, w[1] / sum, w[2] / sum]
+        } else {
+            [1.0 / 3.0; 3]
+        }
+    }
+}
+
+impl Texture for TriplanarTexture {
+    // Without a normal all three projections count the same.
+    fn value(&self, _: f64, _: f64, p: Vec3) -> Vec3 {
+        self.projections(&p)
+            .iter()
+            .fold(Vec3::zero(), |sum, (u, v)| {
+                sum + self.texture.value(*u, *v, p) / 3.0
+            })
+    }
+
+    fn sample(&self, rec: &HitRecord) -> Vec3 {
+        let axes = [
+            (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, -1.0, 0.0)),
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+        ];
+        let weights = self.weights(&rec.normal);
+        let mut color = Vec3::zero();
+        let mut projected = rec.clone();
+        for (i, (u, v)) in self.projections(&rec.p).iter().enumerate() {
+            if weights[i] <= 0.0 {
+                continue;
+            }
+            projected.u = *u;
+            projected.v = *v;
+            projected.dpdu = axes[i].0 * self.scale;
+            projected.dpdv = axes[i].1 * self.scale;
+            color += self.texture.sample(&projected) * weights[i];
+        }
+        color
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use crate::textures::Texture;
+use crate::util::vector3::Vec3;
+
+// A dense 3D grid of values spanning the box from `min` to `max`, stored with x varying
+// fastest, then y, then z. Values are looked up with trilinear interpolation between the voxel
+// centers and are zero outside of the box.
+pub struct VoxelTexture {
+    resolution: (usize, usize, usize),
+    data: Vec<f64>,
+    min: Vec3,
+    max: Vec3,
+}
+
+impl VoxelTexture {
+    pub fn create(
+        resolution: (usize, usize, usize),
+        data: Vec<f64>,
+        min: Vec3,
+        max: Vec3,
+    ) -> Arc<VoxelTexture> {
+        Arc::new(VoxelTexture {
+            resolution,
+            data,
+            min,
+            max,
+        })
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let (nx, ny, _) = self.resolution;
+        match self.data.get(x + nx * (y + ny * z)) {
+            Some(value) => *value,
+            None => 0.0,
+        }
+    }
+
+    pub fn lookup(&self, p: Vec3) -> f64 {
+        let (nx, ny, nz) = self.resolution;
+        if nx == 0 || ny == 0 || nz == 0 {
+            return 0.0;
+        }
+        let size = self.max - self.min;
+        let local = (p - self.min) / size;
+        if local.x < 0.0
+            || local.x > 1.0
+            || local.y < 0.0
+            || local.y > 1.0
+            || local.z < 0.0
+            || local.z > 1.0
+        {
+            return 0.0;
+        }
+
+        // Continuous voxel coordinates, with the voxel centers at whole numbers.
+        let gx = (local.x * nx as f64 - 0.5).clamp(0.0, (nx - 1) as f64);
+        let gy = (local.y * ny as f64 - 0.5).clamp(0.0, (ny - 1) as f64);
+        let gz = (local.z * nz as f64 - 0.5).clamp(0.0, (nz - 1) as f64);
+        let (x0, y0, z0) = (
+            gx.floor() as usize,
+            gy.floor() as usize,
+            gz.floor() as usize,
+        );
+        let (x1, y1, z1) = (
+            (x0 + 1).min(nx - 1),
+            (y0 + 1).min(ny - 1),
+            (z0 + 1).min(nz - 1),
+        );
+        let (fx, fy, fz) = (gx - x0 as f64, gy - y0 as f64, gz - z0 as f64);
+
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let c00 = lerp(self.voxel(x0, y0, z0), self.voxel(x1, y0, z0), fx);
+        let c10 = lerp(self.voxel(x0, y1, z0), self.voxel(x1, y1, z0), fx);
+        let c01 = lerp(self.voxel(x0, y0, z1), self.voxel(x1, y0, z1), fx);
+        let c11 = lerp(self.voxel(x0, y1, z1), self.voxel(x1, y1, z1), fx);
+        lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz)
+    }
+}
+
+impl Texture for VoxelTexture {
+    fn value(&self, _: f64, _: f64, p: Vec3) -> Vec3 {
+        Vec3::unit() * self.lookup(p)
+    }
+}
@@ -40,6 +40,15 @@ impl RotateY {
             cos_theta: radians.cos(),
         })
     }
+
+    // From the rotated space back to the original one.
+    fn rotate_back(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x + self.sin_theta * v.z,
+            v.y,
+            -self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
 }
 
 impl Hitable for RotateY {
@@ -56,18 +65,11 @@ impl Hitable for RotateY {
         );
         let rotated_r = Ray::new(origin, direction, r.time);
         if self.object.hit(&rotated_r, t_min, t_max, rec) {
-            let p = Vec3::new(
-                self.cos_theta * rec.p.x + self.sin_theta * rec.p.z,
-                rec.p.y,
-                -self.sin_theta * rec.p.x + self.cos_theta * rec.p.z,
-            );
-            let normal = Vec3::new(
-                self.cos_theta * rec.normal.x + self.sin_theta * rec.normal.z,
-                rec.normal.y,
-                -self.sin_theta * rec.normal.x + self.cos_theta * rec.normal.z,
-            );
-            rec.p = p;
-            rec.normal = normal;
+            rec.p = self.rotate_back(&rec.p);
+            rec.normal = self.rotate_back(&rec.normal);
+            rec.geometric_normal = self.rotate_back(&rec.geometric_normal);
+            rec.dpdu = self.rotate_back(&rec.dpdu);
+            rec.dpdv = self.rotate_back(&rec.dpdv);
             return true;
         }
 
@@ -90,6 +92,15 @@ impl RotateX {
             cos_theta: radians.cos(),
         })
     }
+
+    // From the rotated space back to the original one.
+    fn rotate_back(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            v.x,
+            self.cos_theta * v.y + self.sin_theta * v.z,
+            -self.sin_theta * v.y + self.cos_theta * v.z,
+        )
+    }
 }
 
 impl Hitable for RotateX {
@@ -106,18 +117,11 @@ impl Hitable for RotateX {
         );
         let rotated_r = Ray::new(origin, direction, r.time);
         if self.object.hit(&rotated_r, t_min, t_max, rec) {
-            let p = Vec3::new(
-                rec.p.x,
-                self.cos_theta * rec.p.y + self.sin_theta * rec.p.z,
-                -self.sin_theta * rec.p.y + self.cos_theta * rec.p.z,
-            );
-            let normal = Vec3::new(
-                rec.normal.x,
-                self.cos_theta * rec.normal.y + self.sin_theta * rec.normal.z,
-                -self.sin_theta * rec.normal.y + self.cos_theta * rec.normal.z,
-            );
-            rec.p = p;
-            rec.normal = normal;
+            rec.p = self.rotate_back(&rec.p);
+            rec.normal = self.rotate_back(&rec.normal);
+            rec.geometric_normal = self.rotate_back(&rec.geometric_normal);
+            rec.dpdu = self.rotate_back(&rec.dpdu);
+            rec.dpdv = self.rotate_back(&rec.dpdv);
             return true;
         }
         false
@@ -139,6 +143,15 @@ impl RotateZ {
             cos_theta: radians.cos(),
         })
     }
+
+    // From the rotated space back to the original one.
+    fn rotate_back(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x + self.sin_theta * v.y,
+            -self.sin_theta * v.x + self.cos_theta * v.y,
+            v.z,
+        )
+    }
 }
 
 impl Hitable for RotateZ {
@@ -155,18 +168,11 @@ impl Hitable for RotateZ {
         );
         let rotated_r = Ray::new(origin, direction, r.time);
         if self.object.hit(&rotated_r, t_min, t_max, rec) {
-            let p = Vec3::new(
-                self.cos_theta * rec.p.x + self.sin_theta * rec.p.y,
-                -self.sin_theta * rec.p.x + self.cos_theta * rec.p.y,
-                rec.p.z,
-            );
-            let normal = Vec3::new(
-                self.cos_theta * rec.normal.x + self.sin_theta * rec.normal.y,
-                -self.sin_theta * rec.normal.x + self.cos_theta * rec.normal.y,
-                rec.normal.z,
-            );
-            rec.p = p;
-            rec.normal = normal;
+            rec.p = self.rotate_back(&rec.p);
+            rec.normal = self.rotate_back(&rec.normal);
+            rec.geometric_normal = self.rotate_back(&rec.geometric_normal);
+            rec.dpdu = self.rotate_back(&rec.dpdu);
+            rec.dpdv = self.rotate_back(&rec.dpdv);
             return true;
         }
 
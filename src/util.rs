@@ -1,12 +1,21 @@
+pub mod aperture;
+pub mod background;
+pub mod bounding_box;
 pub mod camera;
 pub mod config;
+pub mod exr;
+pub mod hdr_image;
 pub mod hitable;
 pub mod hitable_list;
+pub mod ies;
 pub mod json;
 pub mod math;
 pub mod perlin;
+pub mod point_file;
 pub mod ray;
 pub mod vector3;
+pub mod voxel_file;
 pub mod world;
+pub mod worley;
 
 pub use crate::util::camera::Camera;
@@ -0,0 +1,87 @@
+use std::io::Result;
+
+use rand::Rng;
+
+use crate::util::{hdr_image, math};
+
+// The shape of the opening of a lens, which is also the shape out of focus highlights take.
+pub enum Aperture {
+    Circle,
+    // A polygon with this many blades, turned by an angle in radians.
+    Polygon(usize, f64),
+    // Any shape, from the brightness of an image.
+    Mask(ApertureMask),
+}
+
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    // Cumulative distribution of the pixels, row by row.
+    cdf: Vec<f64>,
+}
+
+impl ApertureMask {
+    pub fn load(filename: &str) -> Result<ApertureMask> {
+        let image = hdr_image::load(filename)?;
+        let mut cdf = Vec::with_capacity(image.width * image.height);
+        let mut sum = 0.0;
+        for p in image.pixels.iter().take(image.width * image.height) {
+            sum += ((p.x + p.y + p.z) / 3.0).max(0.0);
+            cdf.push(sum);
+        }
+        if sum <= 0.0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "aperture mask is all black",
+            ));
+        }
+        for c in cdf.iter_mut() {
+            *c /= sum;
+        }
+        Ok(ApertureMask {
+            width: image.width,
+            height: image.height,
+            cdf,
+        })
+    }
+}
+
+impl Aperture {
+    // A random point on the aperture, within the square from -1 to 1.
+    pub fn sample(&self) -> (f64, f64) {
+        let mut rng = rand::thread_rng();
+        match self {
+            Aperture::Circle => {
+                let p = math::random_in_unit_disk();
+                (p.x, p.y)
+            }
+            Aperture::Polygon(blades, rotation) => {
+                // A random point in one of the triangles between the middle and each edge.
+                let blades = (*blades).max(3);
+                let i = rng.gen_range(0, blades);
+                let angle =
+                    |i: usize| rotation + 2.0 * std::f64::consts::PI * i as f64 / blades as f64;
+                let (a, b) = (angle(i), angle(i + 1));
+                let (mut r1, mut r2) = (rng.gen::<f64>(), rng.gen::<f64>());
+                if r1 + r2 > 1.0 {
+                    r1 = 1.0 - r1;
+                    r2 = 1.0 - r2;
+                }
+                (a.cos() * r1 + b.cos() * r2, a.sin() * r1 + b.sin() * r2)
+            }
+            Aperture::Mask(mask) => {
+                let x: f64 = rng.gen();
+                let i = mask
+                    .cdf
+                    .partition_point(|c| *c <= x)
+                    .min(mask.cdf.len() - 1);
+                let (px, py) = (i % mask.width, i / mask.width);
+                // Rows go from the top of the image down.
+                (
+                    2.0 * (px as f64 + rng.gen::<f64>()) / mask.width as f64 - 1.0,
+                    1.0 - 2.0 * (py as f64 + rng.gen::<f64>()) / mask.height as f64,
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use serde_json::Value;
+
+use crate::lights::{environment_light, EnvironmentLight, Light};
+use crate::util::{json, math, vector3::Vec3};
+
+// What rays that don't hit anything see.
+pub enum Background {
+    Black,
+    Color(Vec3),
+    // Bottom and top colors.
+    Gradient(Vec3, Vec3),
+    // An equirectangular image that's only looked at, not sampled like an environment light.
+    Image(Box<EnvironmentLight>),
+    // Whatever the `environment` or `sky` lights are, or black without them.
+    Environment,
+}
+
+impl Background {
+    pub fn value(&self, direction: &Vec3) -> Vec3 {
+        match self {
+            Background::Black | Background::Environment => Vec3::zero(),
+            Background::Color(color) => *color,
+            Background::Gradient(bottom, top) => {
+                let t = 0.5 * (math::unit_vector(direction).y + 1.0);
+                *bottom * (1.0 - t) + *top * t
+            }
+            Background::Image(image) => match image.background(direction) {
+                Some(color) => color,
+                None => Vec3::zero(),
+            },
+        }
+    }
+}
+
+pub fn load_from_json(values: &Value) -> Background {
+    // The old way of setting a plain color.
+    if values["background"].is_null() {
+        return match json::get_vec3_or_rand(&values["skybox"]) {
+            Some(color) => Background::Color(color),
+            None => Background::Environment,
+        };
+    }
+
+    let values = &values["background"];
+    match values["type"].as_str() {
+        Some("black") => Background::Black,
+        Some("color") => match json::get_vec3_or_rand(&values["color"]) {
+            Some(color) => Background::Color(color),
+            None => {
+                eprintln!("ERROR: Can't get color of background! Using black...");
+                Background::Black
+            }
+        },
+        Some("gradient") => {
+            let bottom = match json::get_vec3_or_rand(&values["bottom"]) {
+                Some(color) => color,
+                _ => Vec3::unit(),
+            };
+            let top = match json::get_vec3_or_rand(&values["top"]) {
+                Some(color) => color,
+                _ => Vec3::new(0.5, 0.7, 1.0),
+            };
+            Background::Gradient(bottom, top)
+        }
+        Some("image") => match environment_light::load_from_json(values) {
+            Some(image) => Background::Image(image),
+            None => Background::Black,
+        },
+        Some("environment") => Background::Environment,
+        _ => {
+            eprintln!("ERROR: Can't get type of background! Using black...");
+            Background::Black
+        }
+    }
+}
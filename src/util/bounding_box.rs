@@ -0,0 +1,43 @@
+use crate::util::{ray::Ray, vector3::Vec3};
+
+// Axis-aligned bounding box.
+#[derive(Copy, Clone)]
+pub struct BoundingBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl BoundingBox {
+    pub fn new(min: Vec3, max: Vec3) -> BoundingBox {
+        BoundingBox { min, max }
+    }
+
+    // Slab test. Returns the ray parameters where the ray enters and leaves the box, clipped to
+    // [t_min, t_max].
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let (mut t0, mut t1) = (t_min, t_max);
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.direction[axis];
+            let mut near = (self.min[axis] - r.origin[axis]) * inv_d;
+            let mut far = (self.max[axis] - r.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut near, &mut far);
+            }
+            // NaNs (a zero direction component with the origin on a slab) leave t0/t1 alone.
+            if near > t0 {
+                t0 = near;
+            }
+            if far < t1 {
+                t1 = far;
+            }
+            if t1 <= t0 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+}
@@ -1,9 +1,8 @@
 use rand::Rng;
 
 use crate::defaults;
-use crate::util::{math, ray::Ray, vector3::Vec3};
+use crate::util::{aperture::Aperture, math, ray::Ray, vector3::Vec3};
 
-#[allow(dead_code)]
 struct WUV {
     w: Vec3,
     u: Vec3,
@@ -15,6 +14,50 @@ pub struct Look {
     pub to: Vec3,
 }
 
+pub enum FisheyeMapping {
+    // The angle from the middle grows evenly with the distance from the middle of the image.
+    Equidistant,
+    // Every pixel covers the same solid angle.
+    Equisolid,
+}
+
+pub enum Projection {
+    // Through a thin lens, like a real camera.
+    Perspective,
+    // Parallel rays from a rectangle this many units tall, so nothing shrinks with distance.
+    Orthographic(f64),
+    // A circle as tall as the image covering this many radians across.
+    Fisheye(FisheyeMapping, f64),
+    // Everything around the camera, longitude across and latitude up.
+    Equirectangular,
+    // Everything around the camera on the six faces of a cube, in two rows of three: left,
+    // front and right on top, then back, up and down.
+    CubeMap,
+}
+
+// What the lens of a perspective or orthographic camera does besides focusing.
+pub struct Lens {
+    pub aperture: Aperture,
+    // How much the edges of the image cut the aperture off into a cat's eye shape, and darken.
+    pub cat_eye: f64,
+    // How far the focal plane is turned around the camera's horizontal and vertical axes, in
+    // radians.
+    pub tilt: (f64, f64),
+    // How far the view is moved sideways and up, as a fraction of its size.
+    pub shift: (f64, f64),
+}
+
+impl Lens {
+    pub fn new() -> Lens {
+        Lens {
+            aperture: Aperture::Circle,
+            cat_eye: 0.0,
+            tilt: (0.0, 0.0),
+            shift: (0.0, 0.0),
+        }
+    }
+}
+
 pub struct Camera {
     lower_left_corner: Vec3,
     horizontal: Vec3,
@@ -23,6 +66,16 @@ pub struct Camera {
     lens_radius: f64,
     wuv: WUV,
     t: (f64, f64),
+    aspect: f64,
+    focus_dist: f64,
+    // Half the height of the perspective view at a distance of 1.
+    half_height: f64,
+    projection: Projection,
+    lens: Lens,
+    // Facing the camera, tilted with the lens.
+    focus_normal: Vec3,
+    // How much of the view each pixel covers, as an angle or for orthographic cameras a width.
+    spread: f64,
 }
 
 impl Camera {
@@ -52,6 +105,13 @@ impl Camera {
             lens_radius: aperture / 2.0,
             wuv: WUV { w, u, v },
             t,
+            aspect,
+            focus_dist,
+            half_height,
+            projection: Projection::Perspective,
+            lens: Lens::new(),
+            focus_normal: w,
+            spread: 2.0 * half_height / f64::from(defaults::HEIGHT),
         }
     }
 
@@ -83,15 +143,138 @@ impl Camera {
         )
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = math::random_in_unit_disk() * self.lens_radius;
-        let offset = self.wuv.u * rd.x + self.wuv.v * rd.y;
+    pub fn with_projection(mut self, projection: Projection) -> Camera {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_lens(mut self, lens: Lens) -> Camera {
+        let WUV { w, u, v } = self.wuv;
+        let (sin_x, cos_x) = lens.tilt.0.sin_cos();
+        let normal = w * cos_x + v * sin_x;
+        let (sin_y, cos_y) = lens.tilt.1.sin_cos();
+        self.focus_normal = normal * cos_y - u * sin_y;
+        self.lower_left_corner += self.horizontal * lens.shift.0 + self.vertical * lens.shift.1;
+        self.lens = lens;
+        self
+    }
+
+    // A random point on the lens for the pixel at (`s`, `t`), or None if the edge of the lens
+    // blocks it.
+    fn lens_point(&self, s: f64, t: f64) -> Option<Vec3> {
+        let (x, y) = self.lens.aperture.sample();
+        if self.lens.cat_eye > 0.0 && self.lens_radius > 0.0 {
+            // The pupil seen from the edges of the image is cut off by a second circle that
+            // moves outwards, so it's 1 at the corners.
+            let diagonal = (self.aspect * self.aspect + 1.0).sqrt();
+            let cx = (2.0 * s - 1.0) * self.aspect / diagonal * self.lens.cat_eye;
+            let cy = (2.0 * t - 1.0) / diagonal * self.lens.cat_eye;
+            if (x - cx) * (x - cx) + (y - cy) * (y - cy) > 1.0 {
+                return None;
+            }
+        }
+        Some((self.wuv.u * x + self.wuv.v * y) * self.lens_radius)
+    }
+
+    // How far along `direction` from `start` the focal plane is.
+    fn focus_distance(&self, start: &Vec3, direction: &Vec3) -> f64 {
+        let center = self.origin - self.wuv.w * self.focus_dist;
+        let t = math::dot(&self.focus_normal, &(center - *start))
+            / math::dot(&self.focus_normal, direction);
+        if t > 0.0 && t.is_finite() {
+            t
+        } else {
+            // Tilted so far that the ray never reaches it, so use the untilted plane.
+            math::dot(&self.wuv.w, &(center - *start)) / math::dot(&self.wuv.w, direction)
+        }
+    }
+
+    pub fn with_image_height(mut self, height: u32) -> Camera {
+        let view_height = match self.projection {
+            Projection::Perspective => 2.0 * self.half_height,
+            Projection::Orthographic(size) => size,
+            Projection::Fisheye(_, fov) => fov,
+            Projection::Equirectangular => std::f64::consts::PI,
+            // Each face is half the image tall and 2 across at a distance of 1.
+            Projection::CubeMap => 4.0,
+        };
+        self.spread = view_height / f64::from(height.max(1));
+        self
+    }
+
+    // `s` and `t` go from 0 to 1 across the image, from the bottom left. Fisheye cameras don't
+    // see anything outside of their circle.
+    pub fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
         let mut rng = rand::thread_rng();
         let time = self.t.0 + rng.gen::<f64>() * (self.t.1 - self.t.0);
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-            time,
-        )
+        let WUV { w, u, v } = self.wuv;
+
+        let mut ray = match &self.projection {
+            Projection::Perspective | Projection::Orthographic(_) => {
+                let (start, direction) = match self.projection {
+                    Projection::Orthographic(size) => (
+                        self.origin
+                            + u * ((s - 0.5 + self.lens.shift.0) * size * self.aspect)
+                            + v * ((t - 0.5 + self.lens.shift.1) * size),
+                        -w,
+                    ),
+                    _ => (
+                        self.origin,
+                        self.lower_left_corner + s * self.horizontal + t * self.vertical
+                            - self.origin,
+                    ),
+                };
+                let offset = self.lens_point(s, t)?;
+                let focus = start + direction * self.focus_distance(&start, &direction);
+                Ray::new(start + offset, focus - start - offset, time)
+            }
+            Projection::Fisheye(mapping, fov) => {
+                let x = (2.0 * s - 1.0) * self.aspect;
+                let y = 2.0 * t - 1.0;
+                let r = (x * x + y * y).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * fov / 2.0,
+                    FisheyeMapping::Equisolid => {
+                        2.0 * (r * (fov / 4.0).sin()).clamp(-1.0, 1.0).asin()
+                    }
+                };
+                let phi = y.atan2(x);
+                let direction = (u * phi.cos() + v * phi.sin()) * theta.sin() - w * theta.cos();
+                Ray::new(self.origin, direction, time)
+            }
+            Projection::Equirectangular => {
+                let longitude = (s - 0.5) * 2.0 * std::f64::consts::PI;
+                let latitude = (t - 0.5) * std::f64::consts::PI;
+                let direction = (u * longitude.sin() - w * longitude.cos()) * latitude.cos()
+                    + v * latitude.sin();
+                Ray::new(self.origin, direction, time)
+            }
+            Projection::CubeMap => {
+                let column = (s * 3.0).floor().clamp(0.0, 2.0);
+                let row = (t * 2.0).floor().clamp(0.0, 1.0);
+                let a = (s * 3.0 - column) * 2.0 - 1.0;
+                let b = (t * 2.0 - row) * 2.0 - 1.0;
+                // Where each face looks, and which ways its right and up are.
+                let (forward, right, up) = match (row as usize, column as usize) {
+                    (1, 0) => (-u, -w, v),
+                    (1, 1) => (-w, u, v),
+                    (1, _) => (u, w, v),
+                    (_, 0) => (w, -u, v),
+                    (_, 1) => (v, u, w),
+                    (_, _) => (-v, u, -w),
+                };
+                Ray::new(self.origin, forward + right * a + up * b, time)
+            }
+        };
+
+        if let Projection::Orthographic(_) = self.projection {
+            ray.width = self.spread;
+        } else {
+            ray.spread = self.spread;
+        }
+        Some(ray)
     }
 }
@@ -4,7 +4,8 @@ use serde_json::Value;
 
 use crate::defaults;
 use crate::util::{
-    camera::{Camera, Look},
+    aperture::{Aperture, ApertureMask},
+    camera::{Camera, FisheyeMapping, Lens, Look, Projection},
     vector3::Vec3,
 };
 
@@ -133,6 +134,11 @@ pub fn load_from_json(filename: String, verbose: bool) -> (Config, Camera) {
         _ => defaults::FOV,
     };
 
+    let camera_type = match values["camera"]["type"].as_str() {
+        Some(camera_type) => camera_type,
+        _ => "perspective",
+    };
+
     let aspect = values["camera"]["aspect_ratio"].as_f64();
     let aspect = match aspect {
         Some(aspect) => aspect,
@@ -160,6 +166,40 @@ pub fn load_from_json(filename: String, verbose: bool) -> (Config, Camera) {
         (_, _) => (defaults::T0, defaults::T1),
     };
 
+    let projection = match camera_type {
+        "orthographic" => {
+            // As tall as the perspective view would be at the focus distance.
+            let size = match values["camera"]["size"].as_f64() {
+                Some(size) => size,
+                _ => 2.0 * (vfov.to_radians() / 2.0).tan() * focus_dist,
+            };
+            Projection::Orthographic(size)
+        }
+        "fisheye" => {
+            let mapping = match values["camera"]["mapping"].as_str() {
+                Some("equisolid") => FisheyeMapping::Equisolid,
+                _ => FisheyeMapping::Equidistant,
+            };
+            let fov = match fov {
+                Some(fov) => fov,
+                _ => 180.0,
+            };
+            Projection::Fisheye(mapping, fov.to_radians())
+        }
+        "equirectangular" => Projection::Equirectangular,
+        "cubemap" => Projection::CubeMap,
+        "perspective" => Projection::Perspective,
+        _ => {
+            eprintln!(
+                "ERROR: Unknown camera type {}! Using perspective...",
+                camera_type
+            );
+            Projection::Perspective
+        }
+    };
+
+    let lens = load_lens_from_json(&values["camera"]);
+
     if verbose {
         println!("Loaded config JSON file.");
         println!(
@@ -172,6 +212,7 @@ pub fn load_from_json(filename: String, verbose: bool) -> (Config, Camera) {
             look_from.x, look_from.y, look_from.z, look_to.x, look_to.y, look_to.z
         );
         println!("Camera settings:");
+        println!("   Type: {}", camera_type);
         println!("   FOV: {}", vfov);
         println!("   Aspect ratio: {}", aspect);
         println!("   Aperture: {}", aperture);
@@ -192,6 +233,70 @@ pub fn load_from_json(filename: String, verbose: bool) -> (Config, Camera) {
             aperture,
             focus_dist,
             (t0, t1),
-        ),
+        )
+        .with_projection(projection)
+        .with_lens(lens)
+        .with_image_height(height),
     )
 }
+
+fn load_lens_from_json(values: &Value) -> Lens {
+    let mut lens = Lens::new();
+
+    let shape = &values["aperture_shape"];
+    lens.aperture = match shape["type"].as_str() {
+        Some("circle") | None => Aperture::Circle,
+        Some("polygon") => {
+            let blades = match shape["blades"].as_u64() {
+                Some(blades) => blades as usize,
+                _ => 6,
+            };
+            let rotation = match shape["rotation"].as_f64() {
+                Some(rotation) => rotation,
+                _ => 0.0,
+            };
+            Aperture::Polygon(blades, rotation.to_radians())
+        }
+        Some("image") => match shape["filename"].as_str().map(ApertureMask::load) {
+            Some(Ok(mask)) => Aperture::Mask(mask),
+            Some(Err(e)) => {
+                eprintln!("ERROR [aperture mask]: {}", e);
+                Aperture::Circle
+            }
+            None => {
+                eprintln!("ERROR: Can't get filename of aperture mask! Using a circle...");
+                Aperture::Circle
+            }
+        },
+        Some(shape) => {
+            eprintln!("ERROR: Unknown aperture shape {}! Using a circle...", shape);
+            Aperture::Circle
+        }
+    };
+
+    if let Some(cat_eye) = values["cat_eye"].as_f64() {
+        lens.cat_eye = cat_eye;
+    }
+    lens.tilt = (
+        match values["tilt"]["x"].as_f64() {
+            Some(x) => x.to_radians(),
+            _ => 0.0,
+        },
+        match values["tilt"]["y"].as_f64() {
+            Some(y) => y.to_radians(),
+            _ => 0.0,
+        },
+    );
+    lens.shift = (
+        match values["shift"]["x"].as_f64() {
+            Some(x) => x,
+            _ => 0.0,
+        },
+        match values["shift"]["y"].as_f64() {
+            Some(y) => y,
+            _ => 0.0,
+        },
+    );
+
+    lens
+}
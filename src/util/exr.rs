@@ -0,0 +1,585 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+
+use crate::util::{hdr_image::HdrImage, vector3::Vec3};
+
+// Just enough OpenEXR to read environment maps: scanline images with half or float R, G and B
+// channels, stored uncompressed or with RLE or ZIP compression.
+
+#[derive(Copy, Clone, PartialEq)]
+enum PixelType {
+    Uint,
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn size(self) -> usize {
+        match self {
+            PixelType::Half => 2,
+            PixelType::Uint | PixelType::Float => 4,
+        }
+    }
+}
+
+struct Channel {
+    name: String,
+    pixel_type: PixelType,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = match self.pos.checked_add(n) {
+            Some(end) if end <= self.bytes.len() => end,
+            _ => return Err(invalid("unexpected end of file")),
+        };
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        let b = self.take(4)?;
+        Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // Attribute and chunk sizes are stored signed.
+    fn size(&mut self) -> Result<usize> {
+        let size = self.i32()?;
+        if size < 0 {
+            return Err(invalid("negative size"));
+        }
+        Ok(size as usize)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let b = self.take(8)?;
+        let mut a = [0u8; 8];
+        a.copy_from_slice(b);
+        Ok(u64::from_le_bytes(a))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let start = self.pos;
+        while self.u8()? != 0 {}
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.pos - 1]).to_string())
+    }
+}
+
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((h >> 10) & 0x1f);
+    let mantissa = f32::from(h & 0x3ff);
+    match exponent {
+        0 => sign * mantissa * (2.0f32).powi(-24),
+        31 if mantissa == 0.0 => sign * std::f32::INFINITY,
+        31 => std::f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * (2.0f32).powi(exponent - 15),
+    }
+}
+
+fn decompress_rle(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let count = data[i] as i8;
+        i += 1;
+        if count < 0 {
+            let n = -i32::from(count) as usize;
+            if i + n > data.len() {
+                return Err(invalid("corrupt RLE data"));
+            }
+            out.extend_from_slice(&data[i..i + n]);
+            i += n;
+        } else {
+            if i >= data.len() {
+                return Err(invalid("corrupt RLE data"));
+            }
+            for _ in 0..=count {
+                out.push(data[i]);
+            }
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+// RLE and ZIP data is delta encoded and has its bytes split into two halves.
+fn unpredict(mut data: Vec<u8>) -> Vec<u8> {
+    for i in 1..data.len() {
+        data[i] = data[i - 1].wrapping_add(data[i]).wrapping_sub(128);
+    }
+    let half = data.len().div_ceil(2);
+    let mut out = Vec::with_capacity(data.len());
+    for i in 0..half {
+        out.push(data[i]);
+        if half + i < data.len() {
+            out.push(data[half + i]);
+        }
+    }
+    out
+}
+
+pub fn load(filename: &str) -> Result<HdrImage> {
+    decode(&fs::read(filename)?)
+}
+
+fn decode(bytes: &[u8]) -> Result<HdrImage> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(4)? != [0x76, 0x2f, 0x31, 0x01] {
+        return Err(invalid("not an OpenEXR file"));
+    }
+    let version = reader.i32()?;
+    if version & 0x200 != 0 {
+        return Err(invalid("tiled OpenEXR files aren't supported"));
+    }
+    if version & 0x1800 != 0 {
+        return Err(invalid("multipart and deep OpenEXR files aren't supported"));
+    }
+
+    let mut channels = Vec::new();
+    let mut compression = None;
+    let mut window = None;
+    loop {
+        let name = reader.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _kind = reader.string()?;
+        let size = reader.size()?;
+        let value = reader.take(size)?;
+        let mut attribute = Reader {
+            bytes: value,
+            pos: 0,
+        };
+        match name.as_str() {
+            "channels" => loop {
+                let channel = attribute.string()?;
+                if channel.is_empty() {
+                    break;
+                }
+                let pixel_type = match attribute.i32()? {
+                    0 => PixelType::Uint,
+                    1 => PixelType::Half,
+                    2 => PixelType::Float,
+                    _ => return Err(invalid("unknown channel type")),
+                };
+                // pLinear, reserved bytes and sampling.
+                attribute.take(12)?;
+                channels.push(Channel {
+                    name: channel,
+                    pixel_type,
+                });
+            },
+            "compression" => compression = Some(attribute.u8()?),
+            "dataWindow" => {
+                window = Some((
+                    attribute.i32()?,
+                    attribute.i32()?,
+                    attribute.i32()?,
+                    attribute.i32()?,
+                ))
+            }
+            _ => (),
+        }
+    }
+
+    let (x_min, y_min, x_max, y_max) = match window {
+        Some(window) => window,
+        None => return Err(invalid("missing dataWindow")),
+    };
+    if x_max < x_min || y_max < y_min {
+        return Err(invalid("empty dataWindow"));
+    }
+    let too_large = || invalid("image is too large");
+    let extent = |min: i32, max: i32| -> Result<usize> {
+        match max.checked_sub(min).and_then(|d| d.checked_add(1)) {
+            Some(extent) => Ok(extent as usize),
+            None => Err(too_large()),
+        }
+    };
+    let width = extent(x_min, x_max)?;
+    let height = extent(y_min, y_max)?;
+    let lines_per_chunk = match compression {
+        Some(0) | Some(1) | Some(2) => 1,
+        Some(3) => 16,
+        _ => {
+            return Err(invalid(
+                "only uncompressed, RLE and ZIP files are supported",
+            ))
+        }
+    };
+
+    // Which of R, G and B each channel is, if any. Gray images only have Y.
+    let target = |name: &str| -> Vec<usize> {
+        match name {
+            "R" => vec![0],
+            "G" => vec![1],
+            "B" => vec![2],
+            "Y" => vec![0, 1, 2],
+            _ => vec![],
+        }
+    };
+    let mut line_size: usize = 0;
+    for channel in channels.iter() {
+        line_size = channel
+            .pixel_type
+            .size()
+            .checked_mul(width)
+            .and_then(|size| line_size.checked_add(size))
+            .ok_or_else(too_large)?;
+    }
+
+    // The offset table has to fit in the file before anything gets allocated for it.
+    let chunks = height.div_ceil(lines_per_chunk);
+    match chunks
+        .checked_mul(8)
+        .and_then(|n| n.checked_add(reader.pos))
+    {
+        Some(end) if end <= bytes.len() => (),
+        _ => return Err(invalid("unexpected end of file")),
+    }
+    let mut offsets = Vec::with_capacity(chunks);
+    for _ in 0..chunks {
+        offsets.push(usize::try_from(reader.u64()?).map_err(|_| invalid("bad chunk offset"))?);
+    }
+
+    let pixel_count = width
+        .checked_mul(height)
+        .filter(|n| n.checked_mul(std::mem::size_of::<[f32; 3]>()).is_some())
+        .ok_or_else(too_large)?;
+    let mut pixels = vec![[0.0f32; 3]; pixel_count];
+    for offset in offsets {
+        reader.pos = offset;
+        let y = reader.i32()? - y_min;
+        if y < 0 || y as usize >= height {
+            return Err(invalid("chunk is outside of the image"));
+        }
+        let y = y as usize;
+        let size = reader.size()?;
+        let data = reader.take(size)?;
+
+        let lines = lines_per_chunk.min(height - y);
+        let expected = line_size.checked_mul(lines).ok_or_else(too_large)?;
+        let data = if size == expected {
+            data.to_vec()
+        } else {
+            match compression {
+                Some(1) => unpredict(decompress_rle(data)?),
+                Some(2) | Some(3) => {
+                    unpredict(inflate::inflate_bytes_zlib(data).map_err(|e| invalid(&e))?)
+                }
+                _ => return Err(invalid("wrong chunk size")),
+            }
+        };
+        if data.len() < expected {
+            return Err(invalid("chunk is too short"));
+        }
+
+        let mut pos = 0;
+        for line in 0..lines {
+            for channel in channels.iter() {
+                let size = channel.pixel_type.size();
+                let targets = target(&channel.name);
+                for x in 0..width {
+                    let b = &data[pos + x * size..pos + (x + 1) * size];
+                    let value = match channel.pixel_type {
+                        PixelType::Half => half_to_f32(u16::from_le_bytes([b[0], b[1]])),
+                        PixelType::Float => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                        PixelType::Uint => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+                    };
+                    for &t in targets.iter() {
+                        pixels[(y + line) * width + x][t] = value;
+                    }
+                }
+                pos += size * width;
+            }
+        }
+    }
+
+    Ok(HdrImage::new(
+        width,
+        height,
+        pixels
+            .iter()
+            .map(|p| Vec3::new(f64::from(p[0]), f64::from(p[1]), f64::from(p[2])))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a scanline file with one chunk per `lines_per_chunk` lines, the way `encode` packs it.
+    fn write_exr<F: Fn(&[u8]) -> Vec<u8>>(
+        channels: &[(&str, i32)],
+        width: i32,
+        height: i32,
+        compression: u8,
+        lines: &[Vec<u8>],
+        encode: F,
+    ) -> Vec<u8> {
+        let mut out = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+            out.extend_from_slice(name.as_bytes());
+            out.push(0);
+            out.extend_from_slice(kind.as_bytes());
+            out.push(0);
+            out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            out.extend_from_slice(value);
+        };
+
+        let mut list = Vec::new();
+        for (name, pixel_type) in channels {
+            list.extend_from_slice(name.as_bytes());
+            list.push(0);
+            list.extend_from_slice(&pixel_type.to_le_bytes());
+            list.extend_from_slice(&[0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        }
+        list.push(0);
+        attribute("channels", "chlist", &list);
+        attribute("compression", "compression", &[compression]);
+        let mut window = Vec::new();
+        for v in &[0, 0, width - 1, height - 1] {
+            window.extend_from_slice(&v.to_le_bytes());
+        }
+        attribute("dataWindow", "box2i", &window);
+        out.push(0);
+
+        let lines_per_chunk = if compression == 3 { 16 } else { 1 };
+        let chunks: Vec<Vec<u8>> = lines
+            .chunks(lines_per_chunk)
+            .map(|chunk| encode(&chunk.concat()))
+            .collect();
+        let mut offset = out.len() + 8 * chunks.len();
+        for chunk in chunks.iter() {
+            out.extend_from_slice(&(offset as u64).to_le_bytes());
+            offset += 8 + chunk.len();
+        }
+        for (i, chunk) in chunks.iter().enumerate() {
+            out.extend_from_slice(&((i * lines_per_chunk) as i32).to_le_bytes());
+            out.extend_from_slice(&(chunk.len() as i32).to_le_bytes());
+            out.extend_from_slice(chunk);
+        }
+        out
+    }
+
+    // The inverse of `unpredict`.
+    fn predict(data: &[u8]) -> Vec<u8> {
+        let half = data.len().div_ceil(2);
+        let mut split = vec![0u8; data.len()];
+        for (i, &b) in data.iter().enumerate() {
+            split[if i % 2 == 0 { i / 2 } else { half + i / 2 }] = b;
+        }
+        let mut out = split.clone();
+        for i in 1..split.len() {
+            out[i] = split[i].wrapping_sub(split[i - 1]).wrapping_add(128);
+        }
+        out
+    }
+
+    // Literal runs only, which is still valid RLE and always a byte longer than the input.
+    fn rle(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for run in data.chunks(127) {
+            out.push((-(run.len() as i32)) as u8);
+            out.extend_from_slice(run);
+        }
+        out
+    }
+
+    // A zlib stream made of stored deflate blocks.
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = data.chunks(0xffff).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            out.push(if i + 1 == blocks.len() { 1 } else { 0 });
+            let len = block.len() as u16;
+            out.extend_from_slice(&len.to_le_bytes());
+            out.extend_from_slice(&(!len).to_le_bytes());
+            out.extend_from_slice(block);
+        }
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in data {
+            a = (a + u32::from(byte)) % 65521;
+            b = (b + a) % 65521;
+        }
+        out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        out
+    }
+
+    fn half_lines(values: &[[u16; 3]], width: usize) -> Vec<Vec<u8>> {
+        // Channels are stored in alphabetical order, B, G then R, a whole line at a time.
+        values
+            .chunks(width)
+            .map(|row| {
+                let mut line = Vec::new();
+                for c in &[2, 1, 0] {
+                    for p in row {
+                        line.extend_from_slice(&p[*c].to_le_bytes());
+                    }
+                }
+                line
+            })
+            .collect()
+    }
+
+    const ONE: u16 = 0x3c00;
+    const TWO: u16 = 0x4000;
+    const HALF: u16 = 0x3800;
+
+    fn check(image: &HdrImage, values: &[[u16; 3]]) {
+        for (pixel, value) in image.pixels.iter().zip(values.iter()) {
+            assert_eq!(pixel.x, f64::from(half_to_f32(value[0])));
+            assert_eq!(pixel.y, f64::from(half_to_f32(value[1])));
+            assert_eq!(pixel.z, f64::from(half_to_f32(value[2])));
+        }
+    }
+
+    fn pixels(width: usize, height: usize) -> Vec<[u16; 3]> {
+        (0..width * height)
+            .map(|i| [ONE, [0, HALF, TWO][i % 3], i as u16])
+            .collect()
+    }
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(half_to_f32(0x0000), 0.0);
+        assert_eq!(half_to_f32(ONE), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(HALF), 0.5);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), (2.0f32).powi(-24));
+        assert_eq!(half_to_f32(0x3555), 0.333_251_95);
+        assert_eq!(half_to_f32(0x7c00), std::f32::INFINITY);
+        assert_eq!(half_to_f32(0xfc00), std::f32::NEG_INFINITY);
+        assert!(half_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn uncompressed() {
+        let values = pixels(3, 2);
+        let lines = half_lines(&values, 3);
+        let bytes = write_exr(&[("B", 1), ("G", 1), ("R", 1)], 3, 2, 0, &lines, |d| {
+            d.to_vec()
+        });
+        let image = decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        check(&image, &values);
+    }
+
+    #[test]
+    fn float_gray() {
+        let lines: Vec<Vec<u8>> = vec![[0.25f32, 4.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect()];
+        let bytes = write_exr(&[("Y", 2)], 2, 1, 0, &lines, |d| d.to_vec());
+        let image = decode(&bytes).unwrap();
+        assert_eq!(
+            (image.pixels[0].x, image.pixels[0].y, image.pixels[0].z),
+            (0.25, 0.25, 0.25)
+        );
+        assert_eq!(
+            (image.pixels[1].x, image.pixels[1].y, image.pixels[1].z),
+            (4.0, 4.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn rle_runs() {
+        assert_eq!(
+            decompress_rle(&[2, 7, 0xfe, 1, 2]).unwrap(),
+            vec![7, 7, 7, 1, 2]
+        );
+        assert!(decompress_rle(&[0xfd, 1]).is_err());
+        assert!(decompress_rle(&[3]).is_err());
+    }
+
+    #[test]
+    fn prediction_round_trip() {
+        let data: Vec<u8> = (0..11).map(|i| (i * 37 % 256) as u8).collect();
+        assert_eq!(unpredict(predict(&data)), data);
+    }
+
+    #[test]
+    fn rle_compressed() {
+        let values = pixels(5, 3);
+        let lines = half_lines(&values, 5);
+        let bytes = write_exr(&[("B", 1), ("G", 1), ("R", 1)], 5, 3, 1, &lines, |d| {
+            rle(&predict(d))
+        });
+        check(&decode(&bytes).unwrap(), &values);
+    }
+
+    #[test]
+    fn zip_compressed() {
+        // 20 lines make a full 16 line chunk and a short one.
+        let values = pixels(4, 20);
+        let lines = half_lines(&values, 4);
+        let bytes = write_exr(&[("B", 1), ("G", 1), ("R", 1)], 4, 20, 3, &lines, |d| {
+            zlib(&predict(d))
+        });
+        let image = decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (4, 20));
+        check(&image, &values);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(decode(b"#?RADIANCE\n").is_err());
+
+        let lines = vec![ONE.to_le_bytes().to_vec()];
+        let write = |compression| write_exr(&[("R", 1)], 1, 1, compression, &lines, |d| d.to_vec());
+        assert!(decode(&write(0)).is_ok());
+        // PIZ compression.
+        assert!(decode(&write(4)).is_err());
+        // Tiled.
+        let mut bytes = write(0);
+        bytes[5] |= 0x02;
+        assert!(decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_broken_headers() {
+        let lines = vec![ONE.to_le_bytes().to_vec(); 2];
+        let bytes = write_exr(&[("R", 1)], 1, 2, 0, &lines, |d| d.to_vec());
+        assert!(decode(&bytes).is_ok());
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err());
+        }
+
+        let key = b"dataWindow\0box2i\0";
+        let at = bytes.windows(key.len()).position(|w| w == key).unwrap() + key.len();
+        let with = |at: usize, value: i32| {
+            let mut bytes = bytes.clone();
+            bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+            bytes
+        };
+        // Negative attribute size.
+        assert!(decode(&with(at, -16)).is_err());
+        // x_min past x_max.
+        assert!(decode(&with(at + 4, 5)).is_err());
+        // A window too wide to count.
+        assert!(decode(&with(at + 4, i32::MIN)).is_err());
+        assert!(decode(&with(at + 16, i32::MAX)).is_err());
+        // A huge height whose offset table isn't in the file.
+        assert!(decode(&with(at + 16, i32::MAX - 1)).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::util::{exr, vector3::Vec3};
+
+// An image in linear floating point, rows from top to bottom.
+pub struct HdrImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vec3>,
+}
+
+impl HdrImage {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>) -> HdrImage {
+        HdrImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[x.min(self.width - 1) + self.width * y.min(self.height - 1)]
+    }
+}
+
+// Reads `.hdr` and `.exr` files as they are. Anything else `image` can open is taken to be gamma
+// encoded, the same way the renderer writes its output.
+pub fn load(filename: &str) -> Result<HdrImage> {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    let image = match extension.as_deref() {
+        Some("exr") => exr::load(filename)?,
+        Some("hdr") => {
+            let reader = BufReader::new(File::open(filename)?);
+            let decoder = image::hdr::HDRDecoder::new(reader)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+            HdrImage::new(
+                metadata.width as usize,
+                metadata.height as usize,
+                pixels
+                    .iter()
+                    .map(|p| Vec3::new(f64::from(p[0]), f64::from(p[1]), f64::from(p[2])))
+                    .collect(),
+            )
+        }
+        _ => {
+            let image = image::open(filename)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?
+                .to_rgb();
+            let (width, height) = image.dimensions();
+            let pixels = image
+                .pixels()
+                .map(|rgb| {
+                    let c = Vec3::new(
+                        f64::from(rgb[0]) / 255.0,
+                        f64::from(rgb[1]) / 255.0,
+                        f64::from(rgb[2]) / 255.0,
+                    );
+                    c * c
+                })
+                .collect();
+            HdrImage::new(width as usize, height as usize, pixels)
+        }
+    };
+
+    if image.width == 0 || image.height == 0 || image.pixels.len() < image.width * image.height {
+        return Err(Error::new(ErrorKind::InvalidData, "image is empty"));
+    }
+    Ok(image)
+}
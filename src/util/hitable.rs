@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use crate::materials::{blank::Blank, Material};
-use crate::util::{ray::Ray, vector3::Vec3};
+use crate::util::{math, ray::Ray, vector3::Vec3};
 
 #[derive(Clone)]
 pub struct HitRecord {
@@ -9,7 +9,21 @@ pub struct HitRecord {
     pub u: f64,
     pub v: f64,
     pub p: Vec3,
+    // Where `p` is on the object before it was moved or rotated, for textures that stick to it.
+    pub local: Vec3,
+    // The normal used for shading, which can be smoothed or bumped, and the true normal of the
+    // surface. Both point towards the side the ray came from.
     pub normal: Vec3,
+    pub geometric_normal: Vec3,
+    // Whether that's the outside (the way the shape faces) rather than the back or inside.
+    pub front_face: bool,
+    // How `p` changes with `u` and `v`, for bump and normal maps.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    // Width of the ray that hit, for filtering textures.
+    pub footprint: f64,
+    // Color of the point that was hit, for clouds with colored points. White everywhere else.
+    pub color: Vec3,
     pub material: Arc<Material>,
 }
 
@@ -20,12 +34,84 @@ impl HitRecord {
             u: 0.0,
             v: 0.0,
             p: Vec3::zero(),
+            local: Vec3::zero(),
             normal: Vec3::zero(),
+            geometric_normal: Vec3::zero(),
+            front_face: true,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
+            footprint: 0.0,
+            color: Vec3::unit(),
             material: Blank::create(),
         }
     }
+
+    // Takes the normals the way the shape faces and turns them towards the side `r` came from,
+    // remembering which side that was. The shading normal is kept on the same side as the
+    // geometric one, so smoothed normals that disagree with the surface don't let light through.
+    pub fn set_normals(&mut self, r: &Ray, geometric_normal: Vec3, normal: Vec3) {
+        self.front_face = math::dot(&r.direction, &geometric_normal) <= 0.0;
+        self.geometric_normal = if self.front_face {
+            geometric_normal
+        } else {
+            -geometric_normal
+        };
+        self.normal = if math::dot(&normal, &self.geometric_normal) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+    }
+
+    // For surfaces where the two are the same.
+    pub fn set_normal(&mut self, r: &Ray, normal: Vec3) {
+        self.set_normals(r, normal, normal);
+    }
 }
 
 pub trait Hitable {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normals_face_the_ray() {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let mut rec = HitRecord::new();
+
+        let front = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        rec.set_normal(&front, normal);
+        assert!(rec.front_face);
+        assert_eq!(rec.geometric_normal.z, 1.0);
+        assert_eq!(rec.normal.z, 1.0);
+
+        let back = Ray::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        rec.set_normal(&back, normal);
+        assert!(!rec.front_face);
+        assert_eq!(rec.geometric_normal.z, -1.0);
+        assert_eq!(rec.normal.z, -1.0);
+    }
+
+    #[test]
+    fn shading_normal_stays_on_the_geometric_side() {
+        let geometric = Vec3::new(0.0, 0.0, 1.0);
+        let shading = Vec3::new(0.0, 0.6, -0.8);
+        let mut rec = HitRecord::new();
+
+        let front = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        rec.set_normals(&front, geometric, shading);
+        assert!(rec.front_face);
+        assert_eq!(rec.geometric_normal.z, 1.0);
+        assert_eq!((rec.normal.y, rec.normal.z), (-0.6, 0.8));
+
+        // From the back the geometric normal flips, and the shading normal already agrees with it.
+        let back = Ray::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        rec.set_normals(&back, geometric, shading);
+        assert!(!rec.front_face);
+        assert_eq!(rec.geometric_normal.z, -1.0);
+        assert_eq!((rec.normal.y, rec.normal.z), (0.6, -0.8));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::util::{
     hitable::{HitRecord, Hitable},
     ray::Ray,
+    vector3::Vec3,
 };
 
 pub struct HitableList {
@@ -19,20 +20,58 @@ impl Hitable for HitableList {
         let mut hit_anything = false;
         let mut closest_so_far: f64 = t_max;
         for i in 0..self.list.len() {
+            // Only some shapes set a color, so one left over from an earlier hit mustn't stick.
+            temp_rec.color = Vec3::unit();
             if self.list[i].hit(r, t_min, closest_so_far, &mut temp_rec) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
-                *rec = HitRecord {
-                    t: temp_rec.t,
-                    u: temp_rec.u,
-                    v: temp_rec.v,
-                    p: temp_rec.p,
-                    normal: temp_rec.normal,
-                    material: temp_rec.material.clone(),
-                };
+                *rec = temp_rec.clone();
             }
         }
 
         hit_anything
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hits at `t`, painting the hit red if it's `colored`.
+    struct Wall {
+        t: f64,
+        colored: bool,
+    }
+
+    impl Hitable for Wall {
+        fn hit(&self, _r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+            if self.t <= t_min || self.t >= t_max {
+                return false;
+            }
+            rec.t = self.t;
+            if self.colored {
+                rec.color = Vec3::new(1.0, 0.0, 0.0);
+            }
+            true
+        }
+    }
+
+    #[test]
+    fn colors_only_come_from_the_closest_hit() {
+        let list = HitableList::new(vec![
+            Box::new(Wall {
+                t: 2.0,
+                colored: true,
+            }),
+            Box::new(Wall {
+                t: 1.0,
+                colored: false,
+            }),
+        ]);
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let mut rec = HitRecord::new();
+        assert!(list.hit(&r, 0.001, 10.0, &mut rec));
+        assert_eq!(rec.t, 1.0);
+        assert_eq!((rec.color.x, rec.color.y, rec.color.z), (1.0, 1.0, 1.0));
+    }
+}
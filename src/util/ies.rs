@@ -0,0 +1,119 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+
+// Reads the candela table of an IES LM-63 photometric file and averages it over the horizontal
+// angles, giving (vertical angle in degrees, intensity) pairs with the intensities scaled so the
+// brightest is 1. A vertical angle of 0 points straight out of the fixture.
+pub fn load(filename: &str) -> Result<Vec<(f64, f64)>> {
+    let data = fs::read_to_string(filename)?;
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+    // Everything before TILT is free-form keywords.
+    let mut lines = data.lines();
+    loop {
+        match lines.next() {
+            Some(line) if line.trim_start().starts_with("TILT=") => {
+                if line.trim() != "TILT=NONE" {
+                    return Err(invalid("only TILT=NONE IES files are supported"));
+                }
+                break;
+            }
+            Some(_) => continue,
+            None => return Err(invalid("missing TILT line")),
+        }
+    }
+
+    let numbers: Vec<f64> = lines
+        .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse::<f64>().unwrap_or(0.0))
+        .collect();
+
+    // Lamps, lumens per lamp, multiplier, vertical angles, horizontal angles, photometric type,
+    // units, width, length, height, ballast factor, future use and input watts.
+    if numbers.len() < 13 {
+        return Err(invalid("truncated IES header"));
+    }
+    let table = &numbers[13..];
+    // Both counts have to fit in the table before they're multiplied together.
+    let count = |n: f64| {
+        if n >= 1.0 && n < table.len() as f64 {
+            Some(n as usize)
+        } else {
+            None
+        }
+    };
+    let (vertical, horizontal) = match (count(numbers[3]), count(numbers[4])) {
+        (Some(vertical), Some(horizontal)) => (vertical, horizontal),
+        _ => return Err(invalid("truncated IES candela table")),
+    };
+    let needed = (vertical + 1)
+        .checked_mul(horizontal)
+        .and_then(|n| n.checked_add(vertical));
+    match needed {
+        Some(needed) if needed <= table.len() => (),
+        _ => return Err(invalid("truncated IES candela table")),
+    }
+
+    let angles = &table[..vertical];
+    let candela = &table[vertical + horizontal..];
+    let mut profile: Vec<(f64, f64)> = angles
+        .iter()
+        .enumerate()
+        .map(|(i, angle)| {
+            let sum: f64 = (0..horizontal).map(|h| candela[h * vertical + i]).sum();
+            (*angle, sum / horizontal as f64)
+        })
+        .collect();
+
+    let brightest = profile.iter().fold(0.0, |max: f64, (_, c)| max.max(*c));
+    if brightest > 0.0 {
+        for entry in profile.iter_mut() {
+            entry.1 /= brightest;
+        }
+    }
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rt1w-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn averages_horizontal_angles() {
+        // Three vertical angles and two horizontal ones, with numbers split across lines and
+        // commas the way real files do.
+        let file = "IESNA:LM-63-2002\n[TEST] test\n[MANUFAC] none\nTILT=NONE\n\
+                    1 1000 1 3 2 1 1 0 0 0\n1.0 1.0 100\n0 45,90\n0 180\n\
+                    200 100 0\n400 100\n0\n";
+        let profile = load(&write("lamp.ies", file)).unwrap();
+        assert_eq!(profile, vec![(0.0, 1.0), (45.0, 1.0 / 3.0), (90.0, 0.0)]);
+    }
+
+    #[test]
+    fn errors() {
+        let header = "IESNA:LM-63-2002\nTILT=INCLUDE\n1 1000 1 1 1 1 1 0 0 0\n1 1 100\n0\n0\n1\n";
+        assert!(load(&write("tilt.ies", header)).is_err());
+        assert!(load(&write("notilt.ies", "IESNA:LM-63-2002\n1 1000 1\n")).is_err());
+        let header =
+            "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1 3 2 1 1 0 0 0\n1 1 100\n0 45 90\n0 180\n1 2\n";
+        assert!(load(&write("short.ies", header)).is_err());
+    }
+
+    #[test]
+    fn rejects_huge_counts() {
+        for counts in &["inf 1", "1 inf", "NaN 1", "1e30 1e30", "-3 1", "0 1"] {
+            let header = format!(
+                "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1 {} 1 1 0 0 0\n1 1 100\n0\n0\n1\n",
+                counts
+            );
+            assert!(load(&write("huge.ies", &header)).is_err());
+        }
+    }
+}
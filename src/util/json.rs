@@ -1,6 +1,8 @@
 use rand::Rng;
 use serde_json::Value;
 
+use crate::util::vector3::Vec3;
+
 pub fn get_f64_or_rand(item: &Value) -> Option<f64> {
     match (item.is_number(), item.is_string()) {
         (true, false) => item.as_f64(),
@@ -29,3 +31,20 @@ pub fn get_f64_or_rand(item: &Value) -> Option<f64> {
         (_, _) => None,
     }
 }
+
+// Reads an {"x", "y", "z"} or {"r", "g", "b"} object, each of which can be random.
+pub fn get_vec3_or_rand(item: &Value) -> Option<Vec3> {
+    let keys = if item["r"].is_null() {
+        ["x", "y", "z"]
+    } else {
+        ["r", "g", "b"]
+    };
+    match (
+        get_f64_or_rand(&item[keys[0]]),
+        get_f64_or_rand(&item[keys[1]]),
+        get_f64_or_rand(&item[keys[2]]),
+    ) {
+        (Some(x), Some(y), Some(z)) => Some(Vec3::new(x, y, z)),
+        (_, _, _) => None,
+    }
+}
@@ -27,6 +27,11 @@ pub fn random_in_unit_sphere() -> Vec3 {
     p
 }
 
+// Uniformly distributed on the surface of the unit sphere.
+pub fn random_unit_vector() -> Vec3 {
+    unit_vector(&random_in_unit_sphere())
+}
+
 pub fn random_in_unit_disk() -> Vec3 {
     let mut rng = rand::thread_rng();
     let mut p = 2.0 * Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), 0.0) - Vec3::new(1.0, 1.0, 0.0);
@@ -58,6 +63,25 @@ pub fn schlik(cosine: f64, refractive_index: f64) -> f64 {
     r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
 }
 
+// Both times a ray with `origin` and `direction` crosses the sphere around `center`.
+pub fn sphere_roots(
+    origin: Vec3,
+    direction: Vec3,
+    center: Vec3,
+    radius: f64,
+) -> Option<(f64, f64)> {
+    let oc = origin - center;
+    let a = dot(&direction, &direction);
+    let b = dot(&oc, &direction);
+    let c = dot(&oc, &oc) - radius * radius;
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt = discriminant.sqrt();
+    Some(((-b - sqrt) / a, (-b + sqrt) / a))
+}
+
 pub fn get_sphere_uv(p: &Vec3, u: &mut f64, v: &mut f64) {
     let phi = p.z.atan2(p.x);
     let theta = p.y.asin();
@@ -66,11 +90,54 @@ pub fn get_sphere_uv(p: &Vec3, u: &mut f64, v: &mut f64) {
     *v = -(theta + pi / 2.0) / pi;
 }
 
-pub fn get_plane_uv(p: &Vec3, n: &Vec3, u: &mut f64, v: &mut f64) {
-    *v = (p.x * dot(n, &Vec3::new(0.0, 1.0, 0.0)))
-        + (p.x * dot(n, &Vec3::new(0.0, 0.0, 1.0)))
-        + (p.z * -dot(n, &Vec3::new(1.0, 0.0, 0.0)));
-    *u = (p.z * dot(n, &Vec3::new(0.0, 1.0, 0.0)))
-        + (p.y * dot(n, &Vec3::new(0.0, 0.0, 1.0)))
-        + (p.y * dot(n, &Vec3::new(1.0, 0.0, 0.0)));
+// How a point on a sphere of `radius` with unit `normal` moves with the u and v above.
+pub fn get_sphere_tangents(normal: &Vec3, radius: f64) -> (Vec3, Vec3) {
+    let pi = std::f64::consts::PI;
+    let cos_theta = (normal.x * normal.x + normal.z * normal.z).sqrt();
+    if cos_theta < 1.0e-9 {
+        // u doesn't go anywhere at the poles.
+        let (a, b) = orthonormal_basis(normal);
+        return (a * radius, b * radius);
+    }
+    let dpdu = Vec3::new(normal.z, 0.0, -normal.x) * (2.0 * pi * radius);
+    let dpdtheta = Vec3::new(
+        -normal.y * normal.x / cos_theta,
+        cos_theta,
+        -normal.y * normal.z / cos_theta,
+    ) * radius;
+    (dpdu, dpdtheta * -pi)
+}
+
+// Which way u and v go along a plane facing the unit vector `n`, as unit vectors. v goes along
+// `up` flattened onto the plane, or world y if there's no `up` (or it points straight out of the
+// plane), or -z for planes facing up or down, so floors read like a map. u goes to its right.
+pub fn get_plane_tangents(n: &Vec3, up: Option<Vec3>) -> (Vec3, Vec3) {
+    let flatten = |up: Vec3| up - *n * dot(n, &up);
+    let bitangent = match up {
+        Some(up) if flatten(up).length() > 1.0e-6 * up.length() => flatten(up),
+        _ if n.y.abs() < 0.999 => flatten(Vec3::new(0.0, 1.0, 0.0)),
+        _ => flatten(Vec3::new(0.0, 0.0, -1.0)),
+    };
+    let bitangent = unit_vector(&bitangent);
+    (cross(&bitangent, n), bitangent)
+}
+
+// u around the y axis, going the same way as on spheres, and how `p` moves with it. For shapes
+// that are turned around the y axis, like cylinders.
+pub fn get_revolution_u(p: &Vec3) -> (f64, Vec3) {
+    let pi = std::f64::consts::PI;
+    let u = 1.0 - (p.z.atan2(p.x) + pi) / (2.0 * pi);
+    (u, Vec3::new(p.z, 0.0, -p.x) * (2.0 * pi))
+}
+
+// Two unit vectors that together with the unit vector `w` form an orthonormal basis.
+pub fn orthonormal_basis(w: &Vec3) -> (Vec3, Vec3) {
+    let a = if w.x.abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let v = unit_vector(&cross(w, &a));
+    let u = cross(w, &v);
+    (u, v)
 }
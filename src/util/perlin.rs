@@ -19,38 +19,76 @@ impl Perlin {
         }
     }
 
+    // The random gradient at the lattice point (i, j, k). Masking the signed indices keeps the
+    // lattice going the same way on both sides of zero.
+    fn gradient(&self, i: i64, j: i64, k: i64) -> Vec3 {
+        self.random_vectors[(self.perm_x[(i & 255) as usize]
+            ^ self.perm_y[(j & 255) as usize]
+            ^ self.perm_z[(k & 255) as usize]) as usize]
+    }
+
+    // Signed, roughly between -1 and 1.
     pub fn noise(&self, p: Vec3) -> f64 {
         let u = p.x - p.x.floor();
         let v = p.y - p.y.floor();
         let w = p.z - p.z.floor();
-        let i = p.x.floor() as usize;
-        let j = p.y.floor() as usize;
-        let k = p.z.floor() as usize;
+        let i = p.x.floor() as i64;
+        let j = p.y.floor() as i64;
+        let k = p.z.floor() as i64;
         let mut c = vec![vec![vec![Vec3::zero(); 2]; 2]; 2];
         for (di, c) in c.iter_mut().enumerate() {
             for (dj, c) in c.iter_mut().enumerate() {
                 for (dk, c) in c.iter_mut().enumerate() {
-                    *c = self.random_vectors[(self.perm_x[(i + di) & 255]
-                        ^ self.perm_y[(j + dj) & 255]
-                        ^ self.perm_z[(k + dk) & 255])
-                        as usize];
+                    *c = self.gradient(i + di as i64, j + dj as i64, k + dk as i64);
                 }
             }
         }
         trilinear_interp(&c, u, v, w)
     }
 
-    pub fn turbulence(&self, p: Vec3, depth: usize) -> f64 {
-        let mut accum = 0.0;
-        let mut temp_p = p;
-        let mut weight = 1.0;
-        for _ in 0..depth {
-            accum += weight * self.noise(temp_p);
-            weight *= 0.5;
-            temp_p *= 2.0;
-        }
+    // Simplex noise with the same gradients, which looks less blocky along the axes. Signed,
+    // roughly between -1 and 1.
+    pub fn simplex(&self, p: Vec3) -> f64 {
+        const F3: f64 = 1.0 / 3.0;
+        const G3: f64 = 1.0 / 6.0;
+
+        // Which simplex of the skewed lattice `p` is in.
+        let s = (p.x + p.y + p.z) * F3;
+        let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+        let t = (i + j + k) * G3;
+        let d0 = Vec3::new(p.x - (i - t), p.y - (j - t), p.z - (k - t));
+
+        // The middle two corners, from the order of the offsets.
+        let (o1, o2) = if d0.x >= d0.y {
+            if d0.y >= d0.z {
+                ((1, 0, 0), (1, 1, 0))
+            } else if d0.x >= d0.z {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if d0.y < d0.z {
+            ((0, 0, 1), (0, 1, 1))
+        } else if d0.x < d0.z {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
 
-        accum.abs()
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+        let corners = [(0, 0, 0), o1, o2, (1, 1, 1)];
+        let mut sum = 0.0;
+        for (n, (di, dj, dk)) in corners.iter().enumerate() {
+            let offset = Vec3::new(f64::from(*di), f64::from(*dj), f64::from(*dk));
+            let d = d0 - offset + Vec3::unit() * (n as f64 * G3);
+            let falloff = 0.6 - d.squared_length();
+            if falloff > 0.0 {
+                let gradient =
+                    self.gradient(i + i64::from(*di), j + i64::from(*dj), k + i64::from(*dk));
+                sum += falloff.powi(4) * math::dot(&gradient, &d);
+            }
+        }
+        32.0 * sum
     }
 }
 
@@ -101,3 +139,63 @@ fn trilinear_interp(c: &[Vec<Vec<Vec3>>], u: f64, v: f64, w: f64) -> f64 {
     }
     accum
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A spread of points on both sides of zero, off the lattice.
+    fn points() -> Vec<Vec3> {
+        (0..2000)
+            .map(|i| {
+                let f = i as f64;
+                Vec3::new(
+                    (f * 0.731).sin() * 40.0,
+                    (f * 1.379).cos() * 40.0,
+                    f * 0.0173 - 17.0,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn simplex_is_bounded_and_not_flat() {
+        let perlin = Perlin::new();
+        let values: Vec<f64> = points().iter().map(|p| perlin.simplex(*p)).collect();
+        assert!(values.iter().all(|v| v.abs() <= 1.0));
+        assert!(values.iter().any(|v| v.abs() > 0.1));
+    }
+
+    #[test]
+    fn simplex_is_zero_on_the_lattice() {
+        let perlin = Perlin::new();
+        for p in points() {
+            // Unskewed back from a corner of the skewed lattice.
+            let (i, j, k) = (p.x.floor(), p.y.floor(), p.z.floor());
+            let t = (i + j + k) / 6.0;
+            let corner = Vec3::new(i - t, j - t, k - t);
+            assert!(perlin.simplex(corner).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn simplex_is_continuous() {
+        // Across the simplex boundaries too, where the corners change.
+        let perlin = Perlin::new();
+        for p in points() {
+            let step = Vec3::new(1.0e-6, -1.0e-6, 1.0e-6);
+            assert!((perlin.simplex(p) - perlin.simplex(p + step)).abs() < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn noise_repeats_across_zero() {
+        // The lattice wraps every 256 units, the same on the negative side.
+        let perlin = Perlin::new();
+        let period = Vec3::new(256.0, 256.0, 256.0);
+        for p in points() {
+            assert!((perlin.noise(p) - perlin.noise(p - period)).abs() < 1.0e-9);
+            assert!((perlin.simplex(p) - perlin.simplex(p - period)).abs() < 1.0e-9);
+        }
+    }
+}
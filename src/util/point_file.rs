@@ -0,0 +1,511 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::util::vector3::Vec3;
+
+// Points read from a scan, with normals and 8-bit sRGB colors if the file has them.
+pub struct PointData {
+    pub positions: Vec<Vec3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub colors: Option<Vec<[u8; 3]>>,
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// `.ply` files by their header, anything else as text with one point per line.
+pub fn load(filename: &str) -> Result<PointData> {
+    let bytes = fs::read(filename)?;
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    let data = if extension.as_deref() == Some("ply") || bytes.starts_with(b"ply") {
+        load_ply(&bytes)?
+    } else {
+        load_xyz(&bytes)?
+    };
+    if data.positions.is_empty() {
+        return Err(invalid("no points in file".to_string()));
+    }
+    let finite = |p: &Vec3| p.x.is_finite() && p.y.is_finite() && p.z.is_finite();
+    if let Some(n) = data.positions.iter().position(|p| !finite(p)) {
+        return Err(invalid(format!(
+            "point {} isn't at a finite position",
+            n + 1
+        )));
+    }
+    Ok(data)
+}
+
+fn to_color(r: f64, g: f64, b: f64) -> [u8; 3] {
+    let c = |x: f64| x.round().clamp(0.0, 255.0) as u8;
+    [c(r), c(g), c(b)]
+}
+
+// `x y z`, `x y z r g b`, `x y z intensity r g b` (like `.pts`) or `x y z nx ny nz r g b` on each
+// line, separated by spaces or commas, with colors from 0 to 255. Empty lines, lines starting
+// with `#` or `//` and the point count `.pts` files start with are skipped.
+fn load_xyz(bytes: &[u8]) -> Result<PointData> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut data = PointData {
+        positions: Vec::new(),
+        normals: None,
+        colors: None,
+    };
+    let mut columns = None;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let values: Vec<f64> = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<f64>())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| invalid(format!("line {}: {}", number + 1, e)))?;
+        if columns.is_none() && values.len() == 1 {
+            continue;
+        }
+
+        // The first point decides what the columns are.
+        let columns = *columns.get_or_insert_with(|| {
+            if values.len() >= 9 {
+                data.normals = Some(Vec::new());
+                data.colors = Some(Vec::new());
+            } else if values.len() >= 6 {
+                data.colors = Some(Vec::new());
+            }
+            values.len()
+        });
+        if values.len() < columns.min(9) || values.len() < 3 {
+            return Err(invalid(format!(
+                "line {}: expected {} numbers but found {}",
+                number + 1,
+                columns,
+                values.len()
+            )));
+        }
+
+        data.positions
+            .push(Vec3::new(values[0], values[1], values[2]));
+        if let Some(normals) = data.normals.as_mut() {
+            normals.push(Vec3::new(values[3], values[4], values[5]));
+        }
+        if let Some(colors) = data.colors.as_mut() {
+            let c = match columns {
+                6 => 3,
+                7 | 8 => 4,
+                _ => 6,
+            };
+            colors.push(to_color(values[c], values[c + 1], values[c + 2]));
+        }
+    }
+
+    Ok(data)
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn decode(self, bytes: &[u8], big_endian: bool) -> f64 {
+        let mut b = [0u8; 8];
+        b[..bytes.len()].copy_from_slice(bytes);
+        if big_endian {
+            b[..bytes.len()].reverse();
+        }
+        match self {
+            Scalar::I8 => f64::from(b[0] as i8),
+            Scalar::U8 => f64::from(b[0]),
+            Scalar::I16 => f64::from(i16::from_le_bytes([b[0], b[1]])),
+            Scalar::U16 => f64::from(u16::from_le_bytes([b[0], b[1]])),
+            Scalar::I32 => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            Scalar::U32 => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            Scalar::F32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            Scalar::F64 => f64::from_le_bytes(b),
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    scalar: Scalar,
+    // The type of the count in front of a list property, like the vertex indices of a face.
+    count: Option<Scalar>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Reads the values of one element at a time, whichever way the file is encoded. Lists are read
+// but only their count is kept, since points don't have any.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    encoding: Encoding,
+    tokens: Vec<&'a str>,
+    token: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn next_line(&mut self) -> Result<()> {
+        if self.encoding != Encoding::Ascii {
+            return Ok(());
+        }
+        loop {
+            if self.offset >= self.bytes.len() {
+                return Err(invalid("file ends before all the elements".to_string()));
+            }
+            let end = self.bytes[self.offset..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map_or(self.bytes.len(), |n| self.offset + n);
+            let line = std::str::from_utf8(&self.bytes[self.offset..end])
+                .map_err(|e| invalid(e.to_string()))?;
+            self.offset = end + 1;
+            self.tokens = line.split_whitespace().collect();
+            self.token = 0;
+            if !self.tokens.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read(&mut self, scalar: Scalar) -> Result<f64> {
+        match self.encoding {
+            Encoding::Ascii => {
+                let token = self
+                    .tokens
+                    .get(self.token)
+                    .ok_or_else(|| invalid("too few values on a line".to_string()))?;
+                self.token += 1;
+                token.parse::<f64>().map_err(|e| invalid(e.to_string()))
+            }
+            _ => {
+                let size = scalar.size();
+                if self.offset + size > self.bytes.len() {
+                    return Err(invalid("file ends before all the elements".to_string()));
+                }
+                let value = scalar.decode(
+                    &self.bytes[self.offset..self.offset + size],
+                    self.encoding == Encoding::BigEndian,
+                );
+                self.offset += size;
+                Ok(value)
+            }
+        }
+    }
+
+    fn read_element(&mut self, element: &Element, values: &mut Vec<f64>) -> Result<()> {
+        self.next_line()?;
+        values.clear();
+        for property in &element.properties {
+            match property.count {
+                Some(count) => {
+                    let n = self.read(count)?;
+                    for _ in 0..n.max(0.0) as usize {
+                        self.read(property.scalar)?;
+                    }
+                    values.push(n);
+                }
+                None => values.push(self.read(property.scalar)?),
+            }
+        }
+        Ok(())
+    }
+}
+
+// The `vertex` element of a PLY file (http://paulbourke.net/dataformats/ply/), in any of the three
+// encodings. `x`, `y` and `z` are needed, `nx`, `ny` and `nz` and `red`, `green` and `blue` are
+// used if they're there. Floating point colors are taken to go from 0 to 1.
+fn load_ply(bytes: &[u8]) -> Result<PointData> {
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    // The header ends with `end_header`.
+    let mut offset = 0;
+    let mut ended = false;
+    for line in bytes.split(|b| *b == b'\n') {
+        offset += line.len() + 1;
+        let line = String::from_utf8_lossy(line);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => {
+                ended = true;
+                break;
+            }
+            ["format", format, ..] => {
+                encoding = match *format {
+                    "ascii" => Some(Encoding::Ascii),
+                    "binary_little_endian" => Some(Encoding::LittleEndian),
+                    "binary_big_endian" => Some(Encoding::BigEndian),
+                    _ => return Err(invalid(format!("unknown PLY format {}", format))),
+                }
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("bad count for element {}", name)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, scalar, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    scalar: Scalar::parse(scalar)
+                        .ok_or_else(|| invalid(format!("unknown type {}", scalar)))?,
+                    count: Some(
+                        Scalar::parse(count)
+                            .ok_or_else(|| invalid(format!("unknown type {}", count)))?,
+                    ),
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("property before any element".to_string()))?
+                    .properties
+                    .push(property);
+            }
+            ["property", scalar, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    scalar: Scalar::parse(scalar)
+                        .ok_or_else(|| invalid(format!("unknown type {}", scalar)))?,
+                    count: None,
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("property before any element".to_string()))?
+                    .properties
+                    .push(property);
+            }
+            _ => (), // `ply`, comments and `obj_info`
+        }
+    }
+    let encoding = match encoding {
+        Some(encoding) if ended => encoding,
+        _ => return Err(invalid("incomplete PLY header".to_string())),
+    };
+
+    let mut reader = Reader {
+        bytes,
+        offset: offset.min(bytes.len()),
+        encoding,
+        tokens: Vec::new(),
+        token: 0,
+    };
+    let mut values = Vec::new();
+
+    for element in &elements {
+        if element.name != "vertex" {
+            // Whatever comes before the points has to be read past.
+            for _ in 0..element.count {
+                reader.read_element(element, &mut values)?;
+            }
+            continue;
+        }
+
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| names.contains(&p.name.as_str()))
+        };
+        let position = match (find(&["x"]), find(&["y"]), find(&["z"])) {
+            (Some(x), Some(y), Some(z)) => [x, y, z],
+            (_, _, _) => return Err(invalid("vertices without x, y and z".to_string())),
+        };
+        let normal = match (find(&["nx"]), find(&["ny"]), find(&["nz"])) {
+            (Some(x), Some(y), Some(z)) => Some([x, y, z]),
+            (_, _, _) => None,
+        };
+        let color = match (
+            find(&["red", "r", "diffuse_red"]),
+            find(&["green", "g", "diffuse_green"]),
+            find(&["blue", "b", "diffuse_blue"]),
+        ) {
+            (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+            (_, _, _) => None,
+        };
+        let range = |p: usize| match element.properties[p].scalar {
+            Scalar::F32 | Scalar::F64 => 255.0,
+            Scalar::U16 | Scalar::I16 => 255.0 / 65535.0,
+            _ => 1.0,
+        };
+
+        let mut data = PointData {
+            positions: Vec::with_capacity(element.count),
+            normals: normal.map(|_| Vec::with_capacity(element.count)),
+            colors: color.map(|_| Vec::with_capacity(element.count)),
+        };
+        for _ in 0..element.count {
+            reader.read_element(element, &mut values)?;
+            let get = |p: [usize; 3]| Vec3::new(values[p[0]], values[p[1]], values[p[2]]);
+            data.positions.push(get(position));
+            if let (Some(normals), Some(n)) = (data.normals.as_mut(), normal) {
+                normals.push(get(n));
+            }
+            if let (Some(colors), Some(c)) = (data.colors.as_mut(), color) {
+                colors.push(to_color(
+                    values[c[0]] * range(c[0]),
+                    values[c[1]] * range(c[1]),
+                    values[c[2]] * range(c[2]),
+                ));
+            }
+        }
+        return Ok(data);
+    }
+
+    Err(invalid("no vertex element".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(name: &str, contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("rt1w-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn same(a: Vec3, b: (f64, f64, f64)) -> bool {
+        (a.x, a.y, a.z) == b
+    }
+
+    #[test]
+    fn xyz_columns() {
+        let data = load_xyz(b"# comment\n1 2 3\n4,5,6\n\n").unwrap();
+        assert_eq!(data.positions.len(), 2);
+        assert!(same(data.positions[1], (4.0, 5.0, 6.0)));
+        assert!(data.normals.is_none() && data.colors.is_none());
+
+        let data = load_xyz(b"1 2 3 255 128 0\n").unwrap();
+        assert_eq!(data.colors.unwrap(), vec![[255, 128, 0]]);
+
+        // `.pts` files start with a count and have an intensity before the color.
+        let data = load_xyz(b"2\n0 0 0 -500 10 20 30\n1 1 1 -500 40 50 300\n").unwrap();
+        assert_eq!(data.colors.unwrap(), vec![[10, 20, 30], [40, 50, 255]]);
+
+        let data = load_xyz(b"0 0 0 0 0 1 1 2 3\n").unwrap();
+        assert!(same(data.normals.unwrap()[0], (0.0, 0.0, 1.0)));
+        assert_eq!(data.colors.unwrap(), vec![[1, 2, 3]]);
+    }
+
+    #[test]
+    fn xyz_errors() {
+        assert!(load_xyz(b"1 2 3 4 5 6\n1 2 3\n").is_err());
+        assert!(load_xyz(b"1 2 x\n").is_err());
+    }
+
+    #[test]
+    fn ascii_ply() {
+        let file = b"ply\nformat ascii 1.0\ncomment made by hand\nelement vertex 2\n\
+                     property float x\nproperty float y\nproperty float z\n\
+                     property uchar red\nproperty uchar green\nproperty uchar blue\n\
+                     element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                     0 1 2 255 0 0\n3 4 5 0 255 0\n3 0 1 1\n";
+        let data = load_ply(file).unwrap();
+        assert_eq!(data.positions.len(), 2);
+        assert!(same(data.positions[1], (3.0, 4.0, 5.0)));
+        assert!(data.normals.is_none());
+        assert_eq!(data.colors.unwrap(), vec![[255, 0, 0], [0, 255, 0]]);
+    }
+
+    #[test]
+    fn binary_ply() {
+        // A face element before the vertices has to be skipped over, list and all.
+        let header = "ply\nformat binary_big_endian 1.0\nelement face 1\n\
+                      property list uchar int vertex_indices\nelement vertex 2\n\
+                      property double x\nproperty double y\nproperty double z\n\
+                      property float nx\nproperty float ny\nproperty float nz\n\
+                      property float red\nproperty float green\nproperty float blue\nend_header\n";
+        let mut file = header.as_bytes().to_vec();
+        file.push(2);
+        file.extend_from_slice(&7i32.to_be_bytes());
+        file.extend_from_slice(&8i32.to_be_bytes());
+        for (p, n, c) in &[
+            ((1.0, 2.0, 3.0), 1.0f32, 1.0f32),
+            ((-1.0, 0.5, 0.0), -1.0, 0.5),
+        ] {
+            for v in &[p.0, p.1, p.2] {
+                file.extend_from_slice(&f64::to_be_bytes(*v));
+            }
+            for v in &[*n, 0.0, 0.0, *c, 0.0, *c] {
+                file.extend_from_slice(&f32::to_be_bytes(*v));
+            }
+        }
+
+        let data = load_ply(&file).unwrap();
+        assert!(same(data.positions[0], (1.0, 2.0, 3.0)));
+        assert!(same(data.positions[1], (-1.0, 0.5, 0.0)));
+        assert!(same(data.normals.unwrap()[1], (-1.0, 0.0, 0.0)));
+        assert_eq!(data.colors.unwrap(), vec![[255, 0, 255], [128, 0, 128]]);
+
+        // Cut short in the middle of the second point.
+        assert!(load_ply(&file[..file.len() - 10]).is_err());
+    }
+
+    #[test]
+    fn ply_errors() {
+        assert!(load_ply(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n").is_err());
+        let file = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n1\n";
+        assert!(load_ply(file).is_err());
+        let file = b"ply\nformat binary_middle_endian 1.0\nend_header\n";
+        assert!(load_ply(file).is_err());
+    }
+
+    #[test]
+    fn rejects_points_that_are_not_finite() {
+        assert!(load(&write("points.xyz", b"0 0 0\n1 1 1\n")).is_ok());
+        assert!(load(&write("nan.xyz", b"0 0 0\nNaN 1 1\n")).is_err());
+        assert!(load(&write("inf.xyz", b"0 0 inf\n")).is_err());
+        assert!(load(&write("empty.xyz", b"# nothing\n")).is_err());
+    }
+}
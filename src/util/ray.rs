@@ -4,6 +4,10 @@ pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub time: f64,
+    // How wide the ray is at its origin, and how much wider it gets per unit of distance, so
+    // textures know how much of them a pixel covers.
+    pub width: f64,
+    pub spread: f64,
 }
 
 impl Ray {
@@ -12,9 +16,16 @@ impl Ray {
             origin,
             direction,
             time,
+            width: 0.0,
+            spread: 0.0,
         }
     }
 
+    // Width of the ray after going `t` along it.
+    pub fn footprint(&self, t: f64) -> f64 {
+        self.width + self.spread * t * self.direction.length()
+    }
+
     pub fn point_at_parameter(&self, t: f64) -> Vec3 {
         self.origin + self.direction * t
     }
@@ -0,0 +1,298 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+// A dense grid of voxels read from a file, x changing fastest, then y, then z.
+pub struct VoxelData {
+    pub resolution: (usize, usize, usize),
+    pub data: Vec<f64>,
+}
+
+#[derive(Copy, Clone)]
+pub enum Format {
+    U8,
+    U16,
+    F32,
+    F64,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        // The names NRRD uses, plus the short ones.
+        match name {
+            "u8" | "uchar" | "unsigned char" | "uint8" | "uint8_t" => Some(Format::U8),
+            "u16" | "ushort" | "unsigned short" | "uint16" | "uint16_t" => Some(Format::U16),
+            "f32" | "float" => Some(Format::F32),
+            "f64" | "double" => Some(Format::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Format::U8 => 1,
+            Format::U16 => 2,
+            Format::F32 => 4,
+            Format::F64 => 8,
+        }
+    }
+
+    fn decode(self, bytes: &[u8], big_endian: bool) -> f64 {
+        let mut b = [0u8; 8];
+        b[..bytes.len()].copy_from_slice(bytes);
+        if big_endian {
+            b[..bytes.len()].reverse();
+        }
+        match self {
+            Format::U8 => f64::from(b[0]) / 255.0,
+            Format::U16 => f64::from(u16::from_le_bytes([b[0], b[1]])) / 65535.0,
+            Format::F32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            Format::F64 => f64::from_le_bytes(b),
+        }
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn voxel_count(resolution: (usize, usize, usize)) -> Result<usize> {
+    let (nx, ny, nz) = resolution;
+    nx.checked_mul(ny)
+        .and_then(|n| n.checked_mul(nz))
+        .ok_or_else(|| invalid("voxel grid is too large".to_string()))
+}
+
+fn decode_raw(bytes: &[u8], count: usize, format: Format, big_endian: bool) -> Result<Vec<f64>> {
+    let size = format.size();
+    let total = match count.checked_mul(size) {
+        Some(total) => total,
+        None => return Err(invalid("voxel grid is too large".to_string())),
+    };
+    if bytes.len() < total {
+        return Err(invalid(format!(
+            "expected {} bytes of voxel data but found {}",
+            total,
+            bytes.len()
+        )));
+    }
+    Ok(bytes[..total]
+        .chunks(size)
+        .map(|chunk| format.decode(chunk, big_endian))
+        .collect())
+}
+
+fn decode_ascii(bytes: &[u8], count: usize, format: Format) -> Result<Vec<f64>> {
+    let range = match format {
+        Format::U8 => 255.0,
+        Format::U16 => 65535.0,
+        Format::F32 | Format::F64 => 1.0,
+    };
+    let text = String::from_utf8_lossy(bytes);
+    let data: Vec<f64> = text
+        .split_whitespace()
+        .take(count)
+        .map(|n| n.parse::<f64>().unwrap_or(0.0) / range)
+        .collect();
+    if data.len() < count {
+        return Err(invalid(format!(
+            "expected {} voxel values but found {}",
+            count,
+            data.len()
+        )));
+    }
+    Ok(data)
+}
+
+// Headerless little-endian voxels, so the resolution and format have to come from elsewhere.
+pub fn load_raw(
+    filename: &str,
+    resolution: (usize, usize, usize),
+    format: Format,
+) -> Result<VoxelData> {
+    let count = voxel_count(resolution)?;
+    let bytes = fs::read(filename)?;
+    let data = decode_raw(&bytes, count, format, false)?;
+    Ok(VoxelData { resolution, data })
+}
+
+// Integer voxels are normalized to [0, 1].
+//
+// A small subset of NRRD (http://teem.sourceforge.net/nrrd/format.html): 3 dimensions, `raw` or
+// `ascii` encoding, and the data either following the header or in a separate `data file`.
+pub fn load_nrrd(filename: &str) -> Result<VoxelData> {
+    let bytes = fs::read(filename)?;
+    if !bytes.starts_with(b"NRRD") {
+        return Err(invalid("not a NRRD file".to_string()));
+    }
+
+    let mut format = None;
+    let mut sizes: Vec<usize> = Vec::new();
+    let mut encoding = "raw".to_string();
+    let mut big_endian = false;
+    let mut data_file = None;
+
+    // The header ends at the first empty line.
+    let mut offset = 0;
+    for line in bytes.split(|b| *b == b'\n') {
+        offset += line.len() + 1;
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with('#') || line.starts_with("NRRD") {
+            continue;
+        }
+
+        let mut field = line.splitn(2, ':');
+        let key = field.next().unwrap_or("").trim();
+        let value = field.next().unwrap_or("").trim_start_matches('=').trim();
+        match key {
+            "type" => format = Format::parse(value),
+            "dimension" if value != "3" => {
+                return Err(invalid(format!(
+                    "only 3D grids are supported, not {}D",
+                    value
+                )));
+            }
+            "sizes" => {
+                sizes = value
+                    .split_whitespace()
+                    .map(|n| n.parse::<usize>().unwrap_or(0))
+                    .collect()
+            }
+            "encoding" => encoding = value.to_string(),
+            "endian" => big_endian = value == "big",
+            "data file" | "datafile" => data_file = Some(value.to_string()),
+            _ => (),
+        }
+    }
+
+    let format = match format {
+        Some(format) => format,
+        None => return Err(invalid("missing or unsupported NRRD type".to_string())),
+    };
+    if sizes.len() != 3 {
+        return Err(invalid("missing NRRD sizes".to_string()));
+    }
+    let resolution = (sizes[0], sizes[1], sizes[2]);
+    let count = voxel_count(resolution)?;
+
+    // Detached data files are relative to the header.
+    let detached;
+    let payload = match data_file {
+        Some(data_file) => {
+            let path = match Path::new(filename).parent() {
+                Some(parent) => parent.join(data_file),
+                None => Path::new(&data_file).to_path_buf(),
+            };
+            detached = fs::read(path)?;
+            &detached[..]
+        }
+        None => &bytes[offset.min(bytes.len())..],
+    };
+
+    let data = match encoding.as_str() {
+        "raw" => decode_raw(payload, count, format, big_endian)?,
+        "ascii" | "text" | "txt" => decode_ascii(payload, count, format)?,
+        _ => {
+            return Err(invalid(format!(
+                "unsupported NRRD encoding \"{}\"",
+                encoding
+            )))
+        }
+    };
+
+    Ok(VoxelData { resolution, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes `contents` to a file of its own in the temporary directory.
+    fn write(name: &str, contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("rt1w-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(Format::U8.decode(&[255], false), 1.0);
+        assert_eq!(Format::U16.decode(&[0xff, 0xff], false), 1.0);
+        assert_eq!(Format::U16.decode(&[0x00, 0x80], false), 32768.0 / 65535.0);
+        assert_eq!(Format::U16.decode(&[0x80, 0x00], true), 32768.0 / 65535.0);
+        assert_eq!(Format::F32.decode(&0.5f32.to_le_bytes(), false), 0.5);
+        assert_eq!(Format::F32.decode(&0.5f32.to_be_bytes(), true), 0.5);
+        assert_eq!(Format::F64.decode(&(-2.0f64).to_le_bytes(), false), -2.0);
+        assert!(Format::parse("unsigned char").is_some());
+        assert!(Format::parse("int32").is_none());
+    }
+
+    #[test]
+    fn raw_file() {
+        let filename = write("raw.raw", &[0, 51, 102, 153, 204, 255, 7]);
+        let voxels = load_raw(&filename, (3, 2, 1), Format::U8).unwrap();
+        assert_eq!(voxels.resolution, (3, 2, 1));
+        assert_eq!(voxels.data, vec![0.0, 0.2, 0.4, 0.6, 0.8, 1.0]);
+        assert!(load_raw(&filename, (2, 2, 2), Format::U8).is_err());
+    }
+
+    #[test]
+    fn rejects_grids_that_are_too_large() {
+        let filename = write("huge.raw", &[0; 8]);
+        let huge = 1 << (usize::BITS / 2);
+        assert!(load_raw(&filename, (huge, huge, 2), Format::U8).is_err());
+        assert!(load_raw(&filename, (usize::MAX / 2, 1, 1), Format::F64).is_err());
+        assert!(decode_raw(&[0; 8], usize::MAX / 4, Format::F32, false).is_err());
+
+        let header = format!(
+            "NRRD0004\ntype: uchar\ndimension: 3\nsizes: {} {} 2\nencoding: raw\n\n",
+            huge, huge
+        );
+        let error = load_nrrd(&write("huge.nrrd", header.as_bytes()))
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "voxel grid is too large");
+    }
+
+    #[test]
+    fn nrrd_with_attached_data() {
+        let mut bytes = b"NRRD0004\n# A comment\ntype: float\ndimension: 3\nsizes: 2 1 1\nendian: big\nencoding: raw\n\n".to_vec();
+        bytes.extend_from_slice(&0.25f32.to_be_bytes());
+        bytes.extend_from_slice(&4.0f32.to_be_bytes());
+        let voxels = load_nrrd(&write("attached.nrrd", &bytes)).unwrap();
+        assert_eq!(voxels.resolution, (2, 1, 1));
+        assert_eq!(voxels.data, vec![0.25, 4.0]);
+    }
+
+    #[test]
+    fn nrrd_with_ascii_data_file() {
+        let data = write("detached.txt", b"0 65535\n 13107\n\n");
+        let name = std::path::Path::new(&data)
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let header = format!(
+            "NRRD0004\ntype: ushort\ndimension: 3\nsizes: 1 1 3\nencoding: ascii\ndata file: {}\n",
+            name
+        );
+        let voxels = load_nrrd(&write("detached.nhdr", header.as_bytes())).unwrap();
+        assert_eq!(voxels.resolution, (1, 1, 3));
+        assert_eq!(voxels.data, vec![0.0, 1.0, 0.2]);
+    }
+
+    #[test]
+    fn nrrd_errors() {
+        let header = "NRRD0004\ntype: uchar\ndimension: 2\nsizes: 2 2\nencoding: raw\n\n";
+        assert!(load_nrrd(&write("2d.nrrd", header.as_bytes())).is_err());
+        let header = "NRRD0004\ntype: uchar\ndimension: 3\nsizes: 2 2 2\nencoding: gzip\n\n";
+        assert!(load_nrrd(&write("gzip.nrrd", header.as_bytes())).is_err());
+        let header = "NRRD0004\ntype: uchar\ndimension: 3\nsizes: 2 2 2\nencoding: raw\n\n\x01";
+        assert!(load_nrrd(&write("short.nrrd", header.as_bytes())).is_err());
+        assert!(load_nrrd(&write("other.nrrd", b"P5\n")).is_err());
+    }
+}
@@ -5,112 +5,183 @@ use image;
 use rand::Rng;
 use serde_json::Value;
 
+use crate::lights::{self, environment_light, sky, Light};
 use crate::materials::{
     dielectric::Dielectric, diffuse_light::DiffuseLight, lambertian::Lambertian, metal::Metal,
     Material,
 };
 use crate::shapes::{
-    cuboid, mesh, moving_sphere,
-    plane::{self, Plane},
+    capsule, csg, cuboid, curve, cylinder, disk, heightfield, mesh, moving_sphere, plane,
+    point_cloud, quad, sdf,
     sphere::{self, Sphere},
+    torus, triangle, volume,
 };
 use crate::textures::{
-    checkered_texture::CheckeredTexture, constant_texture::ConstantTexture,
-    image_texture::ImageTexture, noise_texture::NoiseTexture,
+    checkered_texture::CheckeredTexture,
+    constant_texture::ConstantTexture,
+    image_texture::{Filter, ImageTexture, Wrap},
+    noise_texture::NoiseTexture,
 };
 use crate::util::{
+    background::{self, Background},
     hitable::{HitRecord, Hitable},
     hitable_list::HitableList,
-    json, math,
     ray::Ray,
     vector3::Vec3,
 };
 
-pub fn color(r: &Ray, world: &HitableList, depth: usize) -> Vec3 {
+pub struct World {
+    pub objects: HitableList,
+    pub lights: Vec<Box<Light + Sync + Send>>,
+    pub background: Background,
+}
+
+impl World {
+    pub fn new(objects: HitableList) -> World {
+        World {
+            objects,
+            lights: Vec::new(),
+            background: Background::Environment,
+        }
+    }
+
+    // The closest hit along `r`, going through the parts of surfaces that are cut out.
+    fn hit(&self, r: &Ray, t_max: f64, rec: &mut HitRecord) -> bool {
+        let mut rng = rand::thread_rng();
+        let mut t_min = 0.001;
+        while self.objects.hit(r, t_min, t_max, rec) {
+            rec.footprint = r.footprint(rec.t);
+            let opacity = rec.material.opacity(rec);
+            if opacity >= 1.0 || rng.gen::<f64>() < opacity {
+                return true;
+            }
+            t_min = rec.t + 0.001;
+        }
+        false
+    }
+
+    fn occluded(&self, r: &Ray, distance: f64) -> bool {
+        let mut rec = HitRecord::new();
+        self.hit(r, distance * (1.0 - 1.0e-6), &mut rec)
+    }
+
+    // Light arriving at `rec` directly from the lights and scattered along `r_in`.
+    fn sample_lights(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        let mut direct = Vec3::zero();
+        for light in self.lights.iter() {
+            let sample = match light.sample(&rec.p) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            let scattering = match rec.material.scattering(r_in, rec, &sample.direction) {
+                Some(scattering) => scattering,
+                None => continue,
+            };
+            if scattering.x <= 0.0 && scattering.y <= 0.0 && scattering.z <= 0.0 {
+                continue;
+            }
+            if self.occluded(
+                &Ray::new(rec.p, sample.direction, r_in.time),
+                sample.distance,
+            ) {
+                continue;
+            }
+
+            // Lights that can also be hit by scattered rays are counted by both, so both get
+            // weighted with the power heuristic.
+            let weight = if sample.pdf > 0.0 {
+                let scattering_pdf = rec.material.scattering_pdf(r_in, rec, &sample.direction);
+                sample.pdf / (sample.pdf * sample.pdf + scattering_pdf * scattering_pdf)
+            } else {
+                1.0
+            };
+            direct += scattering * sample.radiance * weight;
+        }
+        direct
+    }
+
+    // What rays that miss everything see of the lights around the scene, if there are any.
+    fn environment(&self, r: &Ray, scattering_pdf: Option<f64>) -> Option<Vec3> {
+        let mut background = None;
+        for light in self.lights.iter() {
+            if let Some(radiance) = light.background(&r.direction) {
+                let weight = match scattering_pdf {
+                    Some(pdf) => {
+                        let light_pdf = light.pdf(&r.origin, &r.direction);
+                        pdf * pdf / (pdf * pdf + light_pdf * light_pdf)
+                    }
+                    None => 1.0,
+                };
+                background = Some(match background {
+                    Some(b) => b + radiance * weight,
+                    None => radiance * weight,
+                });
+            }
+        }
+        background
+    }
+
+    // The other half of the power heuristic, for a scattered ray that hit a light.
+    fn emission_weight(&self, r: &Ray, rec: &HitRecord, scattering_pdf: f64) -> f64 {
+        for light in self.lights.iter() {
+            if light.emits_with(&rec.material) {
+                let light_pdf = light.pdf(&r.origin, &r.direction);
+                return scattering_pdf * scattering_pdf
+                    / (scattering_pdf * scattering_pdf + light_pdf * light_pdf);
+            }
+        }
+        1.0
+    }
+}
+
+pub fn color(r: &Ray, world: &World, depth: usize) -> Vec3 {
+    trace(r, world, depth, None)
+}
+
+// `scattering_pdf` is the density the previous bounce picked `r` with, if that bounce also
+// sampled the lights directly.
+fn trace(r: &Ray, world: &World, depth: usize, scattering_pdf: Option<f64>) -> Vec3 {
     let mut rec = HitRecord::new();
-    if world.hit(r, 0.001, std::f64::MAX, &mut rec) {
+    if world.hit(r, std::f64::MAX, &mut rec) {
         let mut scattered = Ray::new(Vec3::zero(), Vec3::zero(), r.time);
         let mut attenuation = Vec3::zero();
         let rec_clone = rec.clone();
-        let emitted = rec_clone.material.emit(rec.u, rec.v, &rec.p);
+        let mut emitted = rec_clone.material.emit(r, &rec);
+        if let Some(pdf) = scattering_pdf {
+            emitted *= world.emission_weight(r, &rec, pdf);
+        }
         if depth < 50
             && rec_clone
                 .material
                 .scatter(&r, &mut rec, &mut attenuation, &mut scattered)
         {
-            attenuation * color(&scattered, world, depth + 1)
-                + if crate::defaults::ENABLE_LIGHTS {
-                    emitted
-                } else {
-                    Vec3::zero()
-                }
+            // Carry on from however wide the ray got, without knowing how curved the surface was.
+            scattered.width = rec.footprint;
+            scattered.spread = r.spread;
+            let (direct, pdf) = if world.lights.is_empty() {
+                (Vec3::zero(), None)
+            } else {
+                let pdf = rec.material.scattering_pdf(r, &rec, &scattered.direction);
+                (
+                    world.sample_lights(r, &rec),
+                    if pdf > 0.0 { Some(pdf) } else { None },
+                )
+            };
+            attenuation * trace(&scattered, world, depth + 1, pdf) + direct + emitted
         } else {
             emitted
         }
-    } else if crate::defaults::ENABLE_LIGHTS {
-        Vec3::zero()
     } else {
-        let unit_direction = math::unit_vector(&r.direction);
-        let t = 0.5 * (unit_direction.y + 1.0);
-        Vec3::unit() * (1.0 - t) + Vec3::new(0.5, 0.7, 1.0) * t
+        // Any other background only replaces the environment for rays straight from the camera,
+        // so the scene is still lit by it.
+        match (&world.background, world.environment(r, scattering_pdf)) {
+            (Background::Environment, Some(environment)) => environment,
+            (_, Some(environment)) if depth > 0 => environment,
+            (background, _) => background.value(&r.direction),
+        }
     }
 }
 
-fn load_skybox_from_json(values: &Value) -> Vec<Box<Hitable + Sync>> {
-    let sr = json::get_f64_or_rand(&values["skybox"]["r"]);
-    let sg = json::get_f64_or_rand(&values["skybox"]["g"]);
-    let sb = json::get_f64_or_rand(&values["skybox"]["b"]);
-    let (sr, sg, sb) = match (sr, sg, sb) {
-        (Some(r), Some(g), Some(b)) => (r, g, b),
-        (_, _, _) => return Vec::new(),
-    };
-
-    skybox(Vec3::new(sr, sg, sb))
-}
-
-fn skybox(color: Vec3) -> Vec<Box<Hitable + Sync>> {
-    let min = std::f64::MIN / 2.0;
-    let max = std::f64::MAX / 2.0;
-    let mat = DiffuseLight::create(ConstantTexture::create(color));
-    let list: Vec<Box<Hitable + Sync>> = vec![
-        // Y planes
-        Plane::create(
-            Vec3::new(0.0, min, 0.0),
-            Vec3::new(0.0, 1.0, 0.0),
-            mat.clone(),
-        ),
-        Plane::create(
-            Vec3::new(0.0, max, 0.0),
-            Vec3::new(0.0, -1.0, 0.0),
-            mat.clone(),
-        ),
-        // Z planes
-        Plane::create(
-            Vec3::new(0.0, 0.0, max),
-            Vec3::new(0.0, 0.0, -1.0),
-            mat.clone(),
-        ),
-        Plane::create(
-            Vec3::new(0.0, 0.0, min),
-            Vec3::new(0.0, 0.0, 1.0),
-            mat.clone(),
-        ),
-        // X planes
-        Plane::create(
-            Vec3::new(max, 0.0, 0.0),
-            Vec3::new(-1.0, 0.0, 0.0),
-            mat.clone(),
-        ),
-        Plane::create(
-            Vec3::new(min, 0.0, 0.0),
-            Vec3::new(1.0, 0.0, 0.0),
-            mat.clone(),
-        ),
-    ];
-
-    list
-}
-
 #[allow(dead_code)]
 fn choose_random_texture() -> Arc<Material + Sync + Send> {
     let mut rng = rand::thread_rng();
@@ -135,7 +206,11 @@ fn choose_random_texture() -> Arc<Material + Sync + Send> {
                 _ => "res/images/Snow Planet CH16.png",
             })
             .expect("Failed to open file."),
-            1.0,
+            Filter::Trilinear,
+            Wrap::Repeat,
+            (1.0, 1.0),
+            (0.0, 0.0),
+            true,
         ))
     } else {
         Lambertian::create(CheckeredTexture::create(
@@ -154,7 +229,7 @@ fn choose_random_texture() -> Arc<Material + Sync + Send> {
     }
 }
 
-pub fn random_scene() -> HitableList {
+pub fn random_scene() -> World {
     let mut list: Vec<Box<Hitable + Sync>> = Vec::new();
     let mut rng = rand::thread_rng();
 
@@ -219,10 +294,12 @@ pub fn random_scene() -> HitableList {
         Metal::create(ConstantTexture::create(Vec3::new(0.7, 0.6, 0.5)), 0.0),
     ));
 
-    HitableList::new(list)
+    let mut world = World::new(HitableList::new(list));
+    world.background = Background::Black;
+    world
 }
 
-pub fn load_from_json(filename: String, verbose: bool) -> HitableList {
+pub fn load_from_json(filename: String, verbose: bool) -> World {
     if filename == "" {
         println!("Generating random scene...");
         return random_scene();
@@ -258,12 +335,81 @@ pub fn load_from_json(filename: String, verbose: bool) -> HitableList {
     list.append(&mut sphere::load_from_json(&values, verbose));
     list.append(&mut moving_sphere::load_from_json(&values, verbose));
     list.append(&mut plane::load_from_json(&values, verbose));
+    list.append(&mut quad::load_from_json(&values, verbose));
+    list.append(&mut disk::load_from_json(&values, verbose));
+    list.append(&mut triangle::load_from_json(&values, verbose));
     list.append(&mut cuboid::load_from_json(&values, verbose));
+    list.append(&mut cylinder::load_from_json(&values, verbose));
+    list.append(&mut torus::load_from_json(&values, verbose));
+    list.append(&mut capsule::load_from_json(&values, verbose));
+    list.append(&mut csg::load_from_json(&values, verbose));
+    list.append(&mut sdf::load_from_json(&values, verbose));
+    list.append(&mut curve::load_from_json(&values, verbose));
+    list.append(&mut heightfield::load_from_json(&values, verbose));
     list.append(&mut mesh::load_from_json(&values, verbose));
-    list.append(&mut load_skybox_from_json(&values));
+    list.append(&mut point_cloud::load_from_json(&values, verbose));
+    list.append(&mut volume::load_from_json(&values, verbose));
+    let mut lights = lights::load_from_json(&values, &mut list, verbose);
+    if !values["environment"].is_null() {
+        if let Some(environment) = environment_light::load_from_json(&values["environment"]) {
+            lights.push(environment);
+        }
+    }
+    if !values["sky"].is_null() {
+        lights.append(&mut sky::load_from_json(&values["sky"]));
+    }
     if verbose {
         println!("Done loading.");
     }
 
-    HitableList::new(list)
+    World {
+        objects: HitableList::new(list),
+        lights,
+        background: background::load_from_json(&values),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lights::point_light::PointLight;
+
+    #[test]
+    fn lambertian_under_a_point_light() {
+        let mut world = World::new(HitableList::new(Vec::new()));
+        let intensity = 100.0;
+        world.lights.push(PointLight::create(
+            Vec3::new(3.0, 0.0, 4.0),
+            Vec3::new(intensity, intensity, intensity),
+            None,
+        ));
+
+        let albedo = 0.5;
+        let r_in = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::new();
+        rec.set_normal(&r_in, Vec3::new(0.0, 0.0, 1.0));
+        rec.material =
+            Lambertian::create(ConstantTexture::create(Vec3::new(albedo, albedo, albedo)));
+
+        // The light is 5 away, 0.8 of the way up from the surface.
+        let expected = albedo / std::f64::consts::PI * intensity / 25.0 * 0.8;
+        let direct = world.sample_lights(&r_in, &rec);
+        for c in &[direct.x, direct.y, direct.z] {
+            assert!((c - expected).abs() < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn occlusion_stops_at_the_light() {
+        let world = World::new(HitableList::new(vec![Sphere::create(
+            Vec3::new(0.0, 0.0, 5.0),
+            1.0,
+            Lambertian::create(ConstantTexture::create(Vec3::unit())),
+        )]));
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(!world.occluded(&r, 3.0));
+        assert!(!world.occluded(&r, 4.0));
+        assert!(world.occluded(&r, 4.5));
+        assert!(world.occluded(&r, 100.0));
+    }
 }
@@ -0,0 +1,52 @@
+use rand::Rng;
+
+use crate::util::vector3::Vec3;
+
+// Worley (cellular) noise: every cell of the integer lattice has one random point in it, and the
+// noise is the distance to the closest of those points.
+pub struct Worley {
+    points: Vec<Vec3>,
+    perm: Vec<usize>,
+}
+
+impl Worley {
+    pub fn new() -> Worley {
+        let mut rng = rand::thread_rng();
+        let points = (0..256)
+            .map(|_| Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()))
+            .collect();
+        let mut perm: Vec<usize> = (0..256).collect();
+        for i in (1..perm.len()).rev() {
+            let target = rng.gen_range(0, i + 1);
+            perm.swap(i, target);
+        }
+        Worley { points, perm }
+    }
+
+    fn point(&self, i: i64, j: i64, k: i64) -> Vec3 {
+        let hash = self.perm[(self.perm
+            [(self.perm[(i & 255) as usize] + (j & 255) as usize) & 255]
+            + (k & 255) as usize)
+            & 255];
+        Vec3::new(i as f64, j as f64, k as f64) + self.points[hash]
+    }
+
+    // Distances to the closest and second closest points.
+    pub fn noise(&self, p: Vec3) -> (f64, f64) {
+        let (i, j, k) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+        let mut closest = (f64::INFINITY, f64::INFINITY);
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let distance = (self.point(i + di, j + dj, k + dk) - p).length();
+                    if distance < closest.0 {
+                        closest = (distance, closest.0);
+                    } else if distance < closest.1 {
+                        closest.1 = distance;
+                    }
+                }
+            }
+        }
+        closest
+    }
+}